@@ -1,18 +1,37 @@
 use cashu::CurrencyUnit;
 use clap::{Parser, Subcommand};
+use ecash_402_wallet::models::SendFeeOptions;
+use ecash_402_wallet::price::{FilePriceOracle, PriceOracle};
+use ecash_402_wallet::restore::{self, RestoreOptions};
+use nip60::keystore::{self, EncryptedSecret};
 use nip60::nip60::Nip60Wallet;
 use nip60::wallet_operations::WalletOperations;
 use nostr_sdk::prelude::*;
 use serde::{Deserialize, Serialize};
 use std::fs;
+use std::io::{self, Write};
 use std::path::PathBuf;
 use std::str::FromStr;
+use zeroize::Zeroizing;
+
+/// Environment variable read for the keystore password instead of prompting
+const PASSWORD_ENV: &str = "NIP60_PASSWORD";
+/// Environment variable read for a mnemonic instead of prompting
+const MNEMONIC_ENV: &str = "NIP60_MNEMONIC";
+/// Environment variable read for a new default private key instead of prompting
+const PRIVATE_KEY_ENV: &str = "NIP60_PRIVATE_KEY";
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct LocalConfig {
     pub relays: Vec<String>,
     pub mints: Vec<MintInfo>,
     pub default_private_key: Option<String>,
+    /// NIP-49 `ncryptsec` of the default private key
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encrypted_private_key: Option<String>,
+    /// Mnemonic used by `restore` when none is given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encrypted_mnemonic: Option<EncryptedSecret>,
 }
 
 impl Default for LocalConfig {
@@ -24,6 +43,8 @@ impl Default for LocalConfig {
             ],
             mints: vec![],
             default_private_key: None,
+            encrypted_private_key: None,
+            encrypted_mnemonic: None,
         }
     }
 }
@@ -121,10 +142,107 @@ impl LocalConfig {
             mints
         };
 
-        let final_key = private_key.or_else(|| self.default_private_key.clone());
+        let final_key = private_key
+            .or_else(|| self.default_private_key.clone())
+            .or_else(|| self.encrypted_private_key.clone());
 
         (final_relays, final_mints, final_key)
     }
+
+    /// Whether a secret is already encrypted with a keystore password
+    fn has_keystore(&self) -> bool {
+        self.encrypted_private_key.is_some() || self.encrypted_mnemonic.is_some()
+    }
+
+    /// The default keys, decrypting them if they are stored encrypted
+    fn keys(&self) -> Result<Keys, Box<dyn std::error::Error>> {
+        let key = self
+            .default_private_key
+            .as_ref()
+            .or(self.encrypted_private_key.as_ref())
+            .ok_or("No default private key set")?;
+        parse_private_key(key)
+    }
+}
+
+/// Keys from an nsec, hex key or NIP-49 ncryptsec, asking for the password
+/// of the latter
+fn parse_private_key(key: &str) -> Result<Keys, Box<dyn std::error::Error>> {
+    if keystore::is_ncryptsec(key) {
+        let password = read_password("Keystore password: ")?;
+        Ok(keystore::decrypt_keys(key, &password)?)
+    } else {
+        Ok(Keys::from_str(key)?)
+    }
+}
+
+/// The value of the environment variable `env`, or else a line from stdin
+fn read_secret(
+    env: &str,
+    prompt: &str,
+    name: &str,
+) -> Result<Zeroizing<String>, Box<dyn std::error::Error>> {
+    if let Ok(secret) = std::env::var(env) {
+        return Ok(Zeroizing::new(secret));
+    }
+
+    eprint!("{}", prompt);
+    io::stderr().flush()?;
+    let mut secret = Zeroizing::new(String::new());
+    io::stdin().read_line(&mut secret)?;
+    let len = secret.trim_end_matches(['\r', '\n']).len();
+    secret.truncate(len);
+    if secret.is_empty() {
+        return Err(format!("Empty {}", name).into());
+    }
+    Ok(secret)
+}
+
+/// The keystore password from `NIP60_PASSWORD`, or else from stdin
+fn read_password(prompt: &str) -> Result<Zeroizing<String>, Box<dyn std::error::Error>> {
+    read_secret(PASSWORD_ENV, prompt, "password")
+}
+
+/// A mnemonic from `NIP60_MNEMONIC`, or else from stdin
+fn read_mnemonic() -> Result<Zeroizing<String>, Box<dyn std::error::Error>> {
+    read_secret(MNEMONIC_ENV, "Mnemonic: ", "mnemonic")
+}
+
+/// A private key from `NIP60_PRIVATE_KEY`, or else from stdin
+fn read_private_key() -> Result<Keys, Box<dyn std::error::Error>> {
+    let key = read_secret(
+        PRIVATE_KEY_ENV,
+        "Private key (nsec or hex): ",
+        "private key",
+    )?;
+    Ok(Keys::from_str(&key)?)
+}
+
+/// The keystore password, checked against the secrets it already encrypts
+fn read_keystore_password(
+    config: &LocalConfig,
+) -> Result<Zeroizing<String>, Box<dyn std::error::Error>> {
+    let password = read_password("Keystore password: ")?;
+    if let Some(key) = &config.encrypted_private_key {
+        keystore::decrypt_keys(key, &password)?;
+    }
+    if let Some(mnemonic) = &config.encrypted_mnemonic {
+        keystore::decrypt_mnemonic(mnemonic, &password)?;
+    }
+    Ok(password)
+}
+
+/// A new keystore password, asked twice when prompting
+fn read_new_password() -> Result<Zeroizing<String>, Box<dyn std::error::Error>> {
+    if std::env::var(PASSWORD_ENV).is_ok() {
+        return read_password("");
+    }
+    let password = read_password("New keystore password: ")?;
+    let confirmation = read_password("Repeat password: ")?;
+    if *password != *confirmation {
+        return Err("Passwords do not match".into());
+    }
+    Ok(password)
 }
 
 #[derive(Parser)]
@@ -170,7 +288,15 @@ enum Commands {
     /// Load wallet from Nostr
     LoadFromNostr {},
     /// Get wallet balance
-    GetBalance {},
+    GetBalance {
+        #[arg(long, default_value = "sat", help = "Unit to report the total in")]
+        currency: String,
+        #[arg(
+            long,
+            help = "JSON file of bitcoin prices per unit, e.g. {\"usd\": 6500000} (in cents)"
+        )]
+        rates: Option<PathBuf>,
+    },
     /// Calculate balance
     CalculateBalance {},
     /// Get wallet state
@@ -185,6 +311,34 @@ enum Commands {
     GetSpendingHistory {},
     /// Get wallet stats
     GetStats {},
+    /// Move proofs off keysets the mints have rotated out
+    MigrateKeysets {},
+    /// Drop proofs the mints report spent and list pending ones
+    SyncProofs {
+        #[arg(long, help = "Repeat every this many seconds")]
+        interval: Option<u64>,
+    },
+    /// Restore a wallet's proofs from its mnemonic (NUT-13)
+    Restore {
+        #[arg(
+            long,
+            help = "Read the mnemonic from NIP60_MNEMONIC or stdin instead of using the encrypted one in the local config"
+        )]
+        enter_mnemonic: bool,
+        #[arg(long, default_value = "", help = "BIP39 passphrase of the mnemonic")]
+        passphrase: String,
+        #[arg(
+            long,
+            help = "Mints to restore from (defaults to the configured mints)"
+        )]
+        mints: Vec<String>,
+        #[arg(long, default_value = "restored.db")]
+        db_name: String,
+        #[arg(long, help = "Empty batches in a row before a keyset is done")]
+        gap_limit: Option<u32>,
+        #[arg(long, help = "Counters checked per request")]
+        batch_size: Option<u32>,
+    },
     /// Parse cashu token
     ParseCashuToken {
         #[arg(short, long)]
@@ -195,6 +349,11 @@ enum Commands {
         #[arg(short, long)]
         token: String,
     },
+    /// Verify the DLEQ proofs of a token (NUT-12)
+    VerifyToken {
+        #[arg(short, long)]
+        token: String,
+    },
     /// Record receive
     RecordReceive {
         #[arg(short, long)]
@@ -234,6 +393,13 @@ enum Commands {
         amount: u64,
         #[arg(short, long)]
         memo: Option<String>,
+        #[arg(
+            long,
+            help = "Add the receiver's redeem fee so they get exactly the amount"
+        )]
+        include_fees: bool,
+        #[arg(long, help = "Show the proofs and fees without sending")]
+        dry_run: bool,
     },
     /// Send with target mint
     SendWithTargetMint {
@@ -270,8 +436,13 @@ enum Commands {
         relays: Vec<String>,
         #[arg(short, long, help = "Cashu mint URLs (overwrites existing)")]
         mints: Vec<String>,
-        #[arg(short, long, help = "Default private key in nsec format or hex")]
-        default_private_key: Option<String>,
+        #[arg(
+            short = 'k',
+            long,
+            help = "Set the default private key from NIP60_PRIVATE_KEY or stdin, encrypted when a keystore password is set",
+            action
+        )]
+        set_private_key: bool,
     },
     /// Add to local configuration
     AddToLocalConfig {
@@ -289,6 +460,16 @@ enum Commands {
         #[arg(long, help = "Clear default private key", action)]
         clear_key: bool,
     },
+    /// Encrypt the default private key (NIP-49) and optionally store an
+    /// encrypted mnemonic, using a password from NIP60_PASSWORD or stdin
+    Encrypt {
+        #[arg(
+            long,
+            help = "Also store a mnemonic from NIP60_MNEMONIC or stdin, used by restore",
+            action
+        )]
+        store_mnemonic: bool,
+    },
     /// Get event history by mint
     GetEventHistoryByMint {
         #[arg(short, long, help = "Specific mint URL to filter by")]
@@ -333,7 +514,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
             let (keys, is_generated) = if let Some(sk) = final_private_key {
                 println!("Using provided private key...");
-                match Keys::from_str(&sk) {
+                match parse_private_key(&sk) {
                     Ok(keys) => (keys, false),
                     Err(e) => {
                         println!(
@@ -408,7 +589,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             let (final_relays, final_mints, final_private_key) =
                 local_config.merge_with_args(relays, mints, Some(private_key));
 
-            let keys = Keys::from_str(&final_private_key.unwrap())?;
+            let keys = parse_private_key(&final_private_key.unwrap())?;
             let relay_refs: Vec<&str> = final_relays.iter().map(|s| s.as_str()).collect();
 
             println!("Loading wallet from config...");
@@ -421,7 +602,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
         Commands::LoadFromNostr {} => {
             let local_config = LocalConfig::load().unwrap_or_default();
-            let keys = Keys::from_str(&local_config.default_private_key.unwrap())?;
+            let keys = local_config.keys()?;
             let relay_refs: Vec<&str> = local_config.relays.iter().map(|s| s.as_str()).collect();
 
             println!("Loading wallet from Nostr...");
@@ -436,19 +617,37 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
         }
 
-        Commands::GetBalance {} => {
+        Commands::GetBalance { currency, rates } => {
             let local_config = LocalConfig::load().unwrap_or_default();
-            let keys = Keys::from_str(&local_config.default_private_key.unwrap())?;
+            let keys = local_config.keys()?;
             let relay_refs: Vec<&str> = local_config.relays.iter().map(|s| s.as_str()).collect();
 
             if let Some(wallet) = Nip60Wallet::load_from_nostr(keys, relay_refs.clone()).await? {
-                let detailed_balance = WalletOperations::get_detailed_balance(&wallet).await?;
+                let oracle = rates.map(FilePriceOracle::new);
+                let detailed_balance = WalletOperations::get_detailed_balance_in(
+                    &wallet,
+                    &currency,
+                    oracle.as_ref().map(|oracle| oracle as &dyn PriceOracle),
+                )
+                .await?;
 
                 println!("=== Wallet Balance ===");
                 println!(
                     "Total: {} sat ({} msat)",
                     detailed_balance.total_sats, detailed_balance.total_msats
                 );
+                if detailed_balance.display_unit != "sat" {
+                    println!(
+                        "Total in {}: {}",
+                        currency, detailed_balance.display_total_formatted
+                    );
+                }
+                if !detailed_balance.unpriced_units.is_empty() {
+                    println!(
+                        "No exchange rate for {}, totals are incomplete (see --rates)",
+                        detailed_balance.unpriced_units.join(", ")
+                    );
+                }
                 println!("\nBy Mint:");
                 for mint_balance in &detailed_balance.by_mint {
                     println!("  • {}", mint_balance.mint_url);
@@ -474,7 +673,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         Commands::CalculateBalance {} => {
             let local_config = LocalConfig::load().unwrap_or_default();
 
-            let keys = Keys::from_str(&local_config.default_private_key.unwrap())?;
+            let keys = local_config.keys()?;
             let relay_refs: Vec<&str> = local_config.relays.iter().map(|s| s.as_str()).collect();
 
             if let Some(wallet) = Nip60Wallet::load_from_nostr(keys, relay_refs.clone()).await? {
@@ -488,7 +687,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         Commands::GetWalletState {} => {
             let local_config = LocalConfig::load().unwrap_or_default();
 
-            let keys = Keys::from_str(&local_config.default_private_key.unwrap())?;
+            let keys = local_config.keys()?;
             let relay_refs: Vec<&str> = local_config.relays.iter().map(|s| s.as_str()).collect();
 
             if let Some(wallet) = Nip60Wallet::load_from_nostr(keys, relay_refs.clone()).await? {
@@ -504,7 +703,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
         Commands::GetUnspentProofs {} => {
             let local_config = LocalConfig::load().unwrap_or_default();
-            let keys = Keys::from_str(&local_config.default_private_key.unwrap())?;
+            let keys = local_config.keys()?;
             let relay_refs: Vec<&str> = local_config.relays.iter().map(|s| s.as_str()).collect();
 
             if let Some(wallet) = Nip60Wallet::load_from_nostr(keys, relay_refs.clone()).await? {
@@ -538,7 +737,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
         Commands::CalculateBalanceFromHistory {} => {
             let local_config = LocalConfig::load().unwrap_or_default();
-            let keys = Keys::from_str(&local_config.default_private_key.unwrap())?;
+            let keys = local_config.keys()?;
             let relay_refs: Vec<&str> = local_config.relays.iter().map(|s| s.as_str()).collect();
 
             if let Some(wallet) = Nip60Wallet::load_from_nostr(keys, relay_refs.clone()).await? {
@@ -556,7 +755,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
         Commands::FetchTokenEvents {} => {
             let local_config = LocalConfig::load().unwrap_or_default();
-            let keys = Keys::from_str(&local_config.default_private_key.unwrap())?;
+            let keys = local_config.keys()?;
             let relay_refs: Vec<&str> = local_config.relays.iter().map(|s| s.as_str()).collect();
 
             if let Some(wallet) = Nip60Wallet::load_from_nostr(keys, relay_refs.clone()).await? {
@@ -577,7 +776,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
         Commands::GetSpendingHistory {} => {
             let local_config = LocalConfig::load().unwrap_or_default();
-            let keys = Keys::from_str(&local_config.default_private_key.unwrap())?;
+            let keys = local_config.keys()?;
             let relay_refs: Vec<&str> = local_config.relays.iter().map(|s| s.as_str()).collect();
 
             if let Some(wallet) = Nip60Wallet::load_from_nostr(keys, relay_refs.clone()).await? {
@@ -593,9 +792,146 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
         }
 
+        Commands::MigrateKeysets {} => {
+            let local_config = LocalConfig::load().unwrap_or_default();
+            let keys = local_config.keys()?;
+            let relay_refs: Vec<&str> = local_config.relays.iter().map(|s| s.as_str()).collect();
+
+            if let Some(mut wallet) = Nip60Wallet::load_from_nostr(keys, relay_refs.clone()).await?
+            {
+                let migrations = wallet.migrate_inactive_keysets().await?;
+
+                println!("=== Keyset Migration ===");
+                if migrations.is_empty() {
+                    println!("No proofs on inactive keysets");
+                }
+                for migration in &migrations {
+                    println!("• {}", migration.mint_url);
+                    println!("  From keysets: {}", migration.inactive_keysets.join(", "));
+                    println!("  Proofs moved: {}", migration.proofs_moved);
+                    println!(
+                        "  Amount moved: {} {}",
+                        migration.amount_moved, migration.unit
+                    );
+                    println!("  Fee: {} {}", migration.fee, migration.unit);
+                }
+            } else {
+                println!("No wallet found");
+            }
+        }
+
+        Commands::SyncProofs { interval } => {
+            let local_config = LocalConfig::load().unwrap_or_default();
+            let keys = local_config.keys()?;
+            let relay_refs: Vec<&str> = local_config.relays.iter().map(|s| s.as_str()).collect();
+
+            if let Some(wallet) = Nip60Wallet::load_from_nostr(keys, relay_refs.clone()).await? {
+                loop {
+                    let syncs = match (wallet.sync_proof_states().await, interval) {
+                        (Ok(syncs), _) => syncs,
+                        (Err(e), Some(seconds)) => {
+                            eprintln!("Proof state sync failed, retrying in {}s: {}", seconds, e);
+                            tokio::time::sleep(std::time::Duration::from_secs(seconds)).await;
+                            continue;
+                        }
+                        (Err(e), None) => return Err(e.into()),
+                    };
+
+                    println!("=== Proof State Sync ===");
+                    for sync in &syncs {
+                        println!("• {}", sync.mint_url);
+                        println!("  Checked: {}", sync.checked);
+                        println!(
+                            "  Spent (removed): {} ({} {})",
+                            sync.spent_count, sync.spent_amount, sync.unit
+                        );
+                        if !sync.pending.is_empty() {
+                            println!(
+                                "  Pending: {} ({} {})",
+                                sync.pending.len(),
+                                sync.pending_amount(),
+                                sync.unit
+                            );
+                        }
+                    }
+
+                    match interval {
+                        Some(seconds) => {
+                            tokio::time::sleep(std::time::Duration::from_secs(seconds)).await
+                        }
+                        None => break,
+                    }
+                }
+            } else {
+                println!("No wallet found");
+            }
+        }
+
+        Commands::Restore {
+            enter_mnemonic,
+            passphrase,
+            mints,
+            db_name,
+            gap_limit,
+            batch_size,
+        } => {
+            let local_config = LocalConfig::load().unwrap_or_default();
+            let mnemonic = match &local_config.encrypted_mnemonic {
+                Some(encrypted) if !enter_mnemonic => {
+                    let password = read_password("Keystore password: ")?;
+                    keystore::decrypt_mnemonic(encrypted, &password)?
+                }
+                _ => read_mnemonic()?,
+            };
+            let mints = if mints.is_empty() {
+                local_config
+                    .mints
+                    .into_iter()
+                    .map(|mint| mint.url)
+                    .collect()
+            } else {
+                mints
+            };
+            if mints.is_empty() {
+                return Err("No mints given and none configured".into());
+            }
+
+            let defaults = RestoreOptions::default();
+            let options = RestoreOptions {
+                batch_size: batch_size.unwrap_or(defaults.batch_size),
+                gap_limit: gap_limit.unwrap_or(defaults.gap_limit),
+            };
+            let restores =
+                restore::restore(&mnemonic, &passphrase, &mints, &db_name, &options).await?;
+
+            println!("=== Restore ===");
+            for mint in &restores {
+                println!("• {}", mint.mint_url);
+                for keyset in &mint.keysets {
+                    if keyset.signatures_found == 0 {
+                        continue;
+                    }
+                    println!("  Keyset {} ({})", keyset.keyset_id, keyset.unit);
+                    println!("    Signatures found: {}", keyset.signatures_found);
+                    println!(
+                        "    Restored: {} unspent, {} pending ({} {})",
+                        keyset.unspent_proofs, keyset.pending_proofs, keyset.amount, keyset.unit
+                    );
+                    if keyset.verification.missing_dleq > 0 {
+                        println!(
+                            "    ⚠️  {} signatures had no DLEQ proof",
+                            keyset.verification.missing_dleq
+                        );
+                    }
+                }
+                println!("  Total: {} sat", mint.amount(&CurrencyUnit::Sat));
+            }
+            println!("Wallet database: {}", db_name);
+        }
+
         Commands::GetStats {} => {
             let local_config = LocalConfig::load().unwrap_or_default();
-            let keys = Keys::from_str(&local_config.default_private_key.unwrap())?;
+            let keys = local_config.keys()?;
             let relay_refs: Vec<&str> = local_config.relays.iter().map(|s| s.as_str()).collect();
 
             if let Some(wallet) = Nip60Wallet::load_from_nostr(keys, relay_refs.clone()).await? {
@@ -606,6 +942,12 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                     "Total Balance: {} sat ({} msat)",
                     stats.balance.total_sats, stats.balance.total_msats
                 );
+                if !stats.balance.unpriced_units.is_empty() {
+                    println!(
+                        "No exchange rate for {}, the total is incomplete",
+                        stats.balance.unpriced_units.join(", ")
+                    );
+                }
                 println!("Token Events: {}", stats.token_events);
 
                 println!("\n=== Mints ===");
@@ -644,7 +986,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
         Commands::ParseCashuToken { token } => {
             let local_config = LocalConfig::load().unwrap_or_default();
-            let keys = Keys::from_str(&local_config.default_private_key.unwrap())?;
+            let keys = local_config.keys()?;
             let relay_refs: Vec<&str> = local_config.relays.iter().map(|s| s.as_str()).collect();
 
             if let Some(wallet) = Nip60Wallet::load_from_nostr(keys, relay_refs.clone()).await? {
@@ -669,7 +1011,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
         Commands::CalculateTokenAmount { token } => {
             let local_config = LocalConfig::load().unwrap_or_default();
-            let keys = Keys::from_str(&local_config.default_private_key.unwrap())?;
+            let keys = local_config.keys()?;
             let relay_refs: Vec<&str> = local_config.relays.iter().map(|s| s.as_str()).collect();
 
             if let Some(wallet) = Nip60Wallet::load_from_nostr(keys, relay_refs.clone()).await? {
@@ -689,14 +1031,53 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
         }
 
+        Commands::VerifyToken { token } => {
+            let local_config = LocalConfig::load().unwrap_or_default();
+            let keys = local_config.keys()?;
+            let relay_refs: Vec<&str> = local_config.relays.iter().map(|s| s.as_str()).collect();
+
+            if let Some(wallet) = Nip60Wallet::load_from_nostr(keys, relay_refs.clone()).await? {
+                match wallet.verify_token(&token).await {
+                    Ok(verification) => {
+                        println!("=== Token Verification ===");
+                        println!("Verified proofs: {}", verification.verified);
+                        println!("Proofs without DLEQ: {}", verification.missing_dleq);
+                        if verification.is_fully_verified() {
+                            println!("✅ All proofs carry a valid DLEQ proof");
+                        } else {
+                            println!("⚠️  Some proofs cannot be verified offline");
+                        }
+                    }
+                    Err(e) => {
+                        println!("❌ Token verification failed: {}", e);
+                    }
+                }
+            } else {
+                println!("No wallet found");
+            }
+        }
+
         Commands::RecordReceive { token } => {
             let local_config = LocalConfig::load().unwrap_or_default();
-            let keys = Keys::from_str(&local_config.default_private_key.unwrap())?;
+            let keys = local_config.keys()?;
             let relay_refs: Vec<&str> = local_config.relays.iter().map(|s| s.as_str()).collect();
 
             if let Some(wallet) = Nip60Wallet::load_from_nostr(keys, relay_refs.clone()).await? {
-                wallet.record_receive(&token).await?;
+                let received = wallet.record_receive(&token).await?;
                 println!("Receive recorded successfully");
+                let verification = &received.verification;
+                if verification.unchecked > 0 {
+                    println!(
+                        "⚠️  Mint keys unavailable or keyset unknown, {} proofs recorded unverified",
+                        verification.unchecked
+                    );
+                } else if !verification.is_fully_verified() {
+                    println!(
+                        "⚠️  {} of {} proofs in received token have no DLEQ proof",
+                        verification.missing_dleq,
+                        verification.missing_dleq + verification.verified
+                    );
+                }
             } else {
                 println!("No wallet found");
             }
@@ -704,7 +1085,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
         Commands::RecordSpend { amount, token_ids } => {
             let local_config = LocalConfig::load().unwrap_or_default();
-            let keys = Keys::from_str(&local_config.default_private_key.unwrap())?;
+            let keys = local_config.keys()?;
             let relay_refs: Vec<&str> = local_config.relays.iter().map(|s| s.as_str()).collect();
 
             if let Some(wallet) = Nip60Wallet::load_from_nostr(keys, relay_refs.clone()).await? {
@@ -725,7 +1106,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             memo,
         } => {
             let local_config = LocalConfig::load().unwrap_or_default();
-            let keys = Keys::from_str(&local_config.default_private_key.unwrap())?;
+            let keys = local_config.keys()?;
             let relay_refs: Vec<&str> = local_config.relays.iter().map(|s| s.as_str()).collect();
             let recipient_pk = PublicKey::from_str(&recipient)?;
 
@@ -739,7 +1120,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
         Commands::SendToSelf { amount, memo } => {
             let local_config = LocalConfig::load().unwrap_or_default();
-            let keys = Keys::from_str(&local_config.default_private_key.unwrap())?;
+            let keys = local_config.keys()?;
             let relay_refs: Vec<&str> = local_config.relays.iter().map(|s| s.as_str()).collect();
 
             if let Some(wallet) = Nip60Wallet::load_from_nostr(keys, relay_refs.clone()).await? {
@@ -752,7 +1133,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
         Commands::SendTokenStringToSelf { token } => {
             let local_config = LocalConfig::load().unwrap_or_default();
-            let keys = Keys::from_str(&local_config.default_private_key.unwrap())?;
+            let keys = local_config.keys()?;
             let relay_refs: Vec<&str> = local_config.relays.iter().map(|s| s.as_str()).collect();
 
             if let Some(wallet) = Nip60Wallet::load_from_nostr(keys, relay_refs.clone()).await? {
@@ -763,19 +1144,43 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
         }
 
-        Commands::Send { amount, memo } => {
+        Commands::Send {
+            amount,
+            memo,
+            include_fees,
+            dry_run,
+        } => {
             let local_config = LocalConfig::load().unwrap_or_default();
-            let keys = Keys::from_str(&local_config.default_private_key.unwrap())?;
+            let keys = local_config.keys()?;
             let relay_refs: Vec<&str> = local_config.relays.iter().map(|s| s.as_str()).collect();
 
             if let Some(wallet) = Nip60Wallet::load_from_nostr(keys, relay_refs.clone()).await? {
+                let fee_options = SendFeeOptions { include_fees };
+
+                if dry_run {
+                    let preview = wallet.preview_send(amount, None, fee_options).await?;
+                    println!("=== Send Preview ===");
+                    println!("Mint: {}", preview.mint_url);
+                    println!("Amount: {} {}", preview.amount, preview.unit);
+                    println!("Proofs: {}", preview.proofs.len());
+                    println!("Token amount: {}", preview.token_amount);
+                    println!("Swap fee: {}", preview.swap_fee);
+                    println!("Redeem fee: {}", preview.redeem_fee);
+                    println!("Receiver gets: {}", preview.received);
+                    println!("Change: {}", preview.change);
+                    return Ok(());
+                }
+
                 println!("=== Creating Token ===");
                 println!("Amount: {} sat", amount);
                 if let Some(memo_text) = &memo {
                     println!("Memo: {}", memo_text);
                 }
 
-                match wallet.send(amount, memo).await {
+                match wallet
+                    .send_with_options(amount, None, memo, fee_options)
+                    .await
+                {
                     Ok(token) => {
                         println!("✅ Token created successfully!");
                         println!("Token: {}", token);
@@ -812,7 +1217,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             memo,
         } => {
             let local_config = LocalConfig::load().unwrap_or_default();
-            let keys = Keys::from_str(&local_config.default_private_key.unwrap())?;
+            let keys = local_config.keys()?;
             let relay_refs: Vec<&str> = local_config.relays.iter().map(|s| s.as_str()).collect();
 
             if let Some(wallet) = Nip60Wallet::load_from_nostr(keys, relay_refs.clone()).await? {
@@ -827,7 +1232,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
         Commands::Redeem { token } => {
             let local_config = LocalConfig::load().unwrap_or_default();
-            let keys = Keys::from_str(&local_config.default_private_key.unwrap())?;
+            let keys = local_config.keys()?;
             let relay_refs: Vec<&str> = local_config.relays.iter().map(|s| s.as_str()).collect();
 
             if let Some(wallet) = Nip60Wallet::load_from_nostr(keys, relay_refs.clone()).await? {
@@ -867,7 +1272,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
         Commands::CheckIncomingTokens {} => {
             let local_config = LocalConfig::load().unwrap_or_default();
-            let keys = Keys::from_str(&local_config.default_private_key.unwrap())?;
+            let keys = local_config.keys()?;
             let relay_refs: Vec<&str> = local_config.relays.iter().map(|s| s.as_str()).collect();
 
             if let Some(wallet) = Nip60Wallet::load_from_nostr(keys, relay_refs.clone()).await? {
@@ -883,7 +1288,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
         Commands::GetConfig {} => {
             let local_config = LocalConfig::load().unwrap_or_default();
-            let keys = Keys::from_str(&local_config.default_private_key.unwrap())?;
+            let keys = local_config.keys()?;
             let relay_refs: Vec<&str> = local_config.relays.iter().map(|s| s.as_str()).collect();
 
             if let Some(wallet) = Nip60Wallet::load_from_nostr(keys, relay_refs.clone()).await? {
@@ -905,7 +1310,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             let (final_relays, final_mints, final_private_key) =
                 local_config.merge_with_args(relays, mints.unwrap_or_default(), Some(private_key));
 
-            let keys = Keys::from_str(&final_private_key.unwrap())?;
+            let keys = parse_private_key(&final_private_key.unwrap())?;
             let relay_refs: Vec<&str> = final_relays.iter().map(|s| s.as_str()).collect();
 
             if let Some(mut wallet) = Nip60Wallet::load_from_nostr(keys, relay_refs.clone()).await?
@@ -935,6 +1340,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 }
                 if config.default_private_key.is_some() {
                     println!("\nDefault private key: [SET]");
+                } else if config.encrypted_private_key.is_some() {
+                    println!("\nDefault private key: [ENCRYPTED]");
                 } else {
                     println!("\nDefault private key: [NOT SET]");
                 }
@@ -947,18 +1354,39 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         Commands::SetLocalConfig {
             relays,
             mints,
-            default_private_key,
+            set_private_key,
         } => {
-            let mut config = LocalConfig::default();
-            config.relays = relays;
-            config.mints = mints
-                .into_iter()
-                .map(|url| MintInfo {
-                    url,
-                    unit: "sat".to_string(),
-                })
-                .collect();
-            config.default_private_key = default_private_key;
+            let existing = LocalConfig::load().unwrap_or_default();
+            let mut default_private_key = None;
+            let mut encrypted_private_key = existing.encrypted_private_key.clone();
+            if set_private_key {
+                let keys = read_private_key()?;
+                // Keep the key encrypted once the keystore has a password
+                if existing.has_keystore() {
+                    let password = read_keystore_password(&existing)?;
+                    encrypted_private_key = Some(keystore::encrypt_keys(
+                        &keys,
+                        &password,
+                        nip49::KeySecurity::Weak,
+                    )?);
+                } else {
+                    default_private_key = Some(keys.secret_key().to_bech32()?);
+                    encrypted_private_key = None;
+                }
+            }
+            let config = LocalConfig {
+                relays,
+                mints: mints
+                    .into_iter()
+                    .map(|url| MintInfo {
+                        url,
+                        unit: "sat".to_string(),
+                    })
+                    .collect(),
+                default_private_key,
+                encrypted_private_key,
+                encrypted_mnemonic: existing.encrypted_mnemonic,
+            };
             config.save()?;
         }
 
@@ -1021,8 +1449,11 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 }
 
                 if clear_key {
-                    if config.default_private_key.is_some() {
+                    if config.default_private_key.is_some()
+                        || config.encrypted_private_key.is_some()
+                    {
                         config.default_private_key = None;
+                        config.encrypted_private_key = None;
                         println!("Cleared default private key");
                         updated = true;
                     } else {
@@ -1043,9 +1474,44 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
         },
 
+        Commands::Encrypt { store_mnemonic } => {
+            let mut config = LocalConfig::load().unwrap_or_default();
+            if config.default_private_key.is_none() && !store_mnemonic {
+                println!("No plaintext private key or mnemonic to encrypt");
+                return Ok(());
+            }
+
+            let mnemonic = if store_mnemonic {
+                Some(read_mnemonic()?)
+            } else {
+                None
+            };
+            // Everything in the keystore shares one password
+            let password = if config.has_keystore() {
+                read_keystore_password(&config)?
+            } else {
+                read_new_password()?
+            };
+            if let Some(key) = config.default_private_key.take().map(Zeroizing::new) {
+                let keys = Keys::from_str(&key)?;
+                // It was stored unencrypted until now
+                config.encrypted_private_key = Some(keystore::encrypt_keys(
+                    &keys,
+                    &password,
+                    nip49::KeySecurity::Weak,
+                )?);
+                println!("Encrypted default private key");
+            }
+            if let Some(mnemonic) = mnemonic {
+                config.encrypted_mnemonic = Some(keystore::encrypt_mnemonic(&mnemonic, &password)?);
+                println!("Stored encrypted mnemonic");
+            }
+            config.save()?;
+        }
+
         Commands::GetEventHistoryByMint { mint } => {
             let local_config = LocalConfig::load().unwrap_or_default();
-            let keys = Keys::from_str(&local_config.default_private_key.unwrap())?;
+            let keys = local_config.keys()?;
             let relay_refs: Vec<&str> = local_config.relays.iter().map(|s| s.as_str()).collect();
 
             if let Some(wallet) = Nip60Wallet::load_from_nostr(keys, relay_refs.clone()).await? {
@@ -1083,11 +1549,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                     println!(
                         "  Net: {}",
                         WalletOperations::display_amount_with_conversion(
-                            if *total_in > *total_out {
-                                *total_in - *total_out
-                            } else {
-                                *total_out - *total_in
-                            },
+                            total_in.abs_diff(*total_out),
                             unit,
                             true
                         )
@@ -1126,7 +1588,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
         Commands::GetProofBreakdown => {
             let local_config = LocalConfig::load().unwrap_or_default();
-            let keys = Keys::from_str(&local_config.default_private_key.unwrap())?;
+            let keys = local_config.keys()?;
             let relay_refs: Vec<&str> = local_config.relays.iter().map(|s| s.as_str()).collect();
 
             if let Some(wallet) = Nip60Wallet::load_from_nostr(keys, relay_refs.clone()).await? {
@@ -1139,7 +1601,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
         Commands::ShowMintInfo { mint } => {
             let local_config = LocalConfig::load().unwrap_or_default();
-            let keys = Keys::from_str(&local_config.default_private_key.unwrap())?;
+            let keys = local_config.keys()?;
             let relay_refs: Vec<&str> = local_config.relays.iter().map(|s| s.as_str()).collect();
 
             if let Some(wallet) = Nip60Wallet::load_from_nostr(keys, relay_refs.clone()).await? {
@@ -11,13 +11,8 @@ pub struct App {
 
 impl App {
     pub async fn new() -> Result<Self> {
-        let mut state = AppState::new().await?;
-
-        if let Some(active_wallet_name) = state.config.active_wallet.clone() {
-            if let Err(e) = state.load_wallet(&active_wallet_name).await {
-                state.set_error(format!("Failed to load active wallet: {}", e));
-            }
-        }
+        // Wallets load once the keystore is unlocked
+        let state = AppState::new().await?;
 
         Ok(Self { state })
     }
@@ -55,10 +50,15 @@ impl App {
             ActiveView::AddWallet => {
                 AddWalletWidget::render(f, &self.state, area);
             }
+            ActiveView::Unlock => {
+                UnlockWidget::render(f, &self.state, area);
+            }
         }
     }
 
     pub async fn handle_key_event(&mut self, key: KeyEvent) -> Result<()> {
+        self.state.lock_if_idle();
+        self.state.session.touch();
         EventHandler::handle_key_event(&mut self.state, key).await?;
         Ok(())
     }
@@ -103,6 +103,11 @@ impl App {
         self.state.active_view
     }
 
+    /// Lock the keystore if it has been idle too long
+    pub fn check_idle(&mut self) {
+        self.state.lock_if_idle();
+    }
+
     pub fn has_error(&self) -> bool {
         self.state.error_message.is_some()
     }
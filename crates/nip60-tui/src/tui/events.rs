@@ -1,6 +1,8 @@
 use crate::error::Result;
 use crate::tui::state::{ActiveView, AppState, HistoryFilter};
 use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use nostr_sdk::prelude::*;
+use zeroize::Zeroizing;
 
 pub struct EventHandler;
 
@@ -14,9 +16,26 @@ impl EventHandler {
             ActiveView::Lightning => Self::handle_lightning_view(state, key).await,
             ActiveView::WalletManager => Self::handle_wallet_manager_view(state, key).await,
             ActiveView::AddWallet => Self::handle_add_wallet_view(state, key).await,
+            ActiveView::Unlock => Self::handle_unlock_view(state, key).await,
         }
     }
 
+    async fn handle_unlock_view(state: &mut AppState, key: KeyEvent) -> Result<()> {
+        match key.code {
+            KeyCode::Enter => state.unlock().await?,
+            KeyCode::Char(c) => {
+                state.unlock_state.error = None;
+                state.unlock_state.password_input.push(c);
+            }
+            KeyCode::Backspace => {
+                state.unlock_state.password_input.pop();
+            }
+            KeyCode::Esc => state.unlock_state.password_input.clear(),
+            _ => {}
+        }
+        Ok(())
+    }
+
     async fn handle_main_view(state: &mut AppState, key: KeyEvent) -> Result<()> {
         // Ensure mint index is valid for the current wallet
         state.ensure_mint_index_valid();
@@ -66,25 +85,23 @@ impl EventHandler {
                 }
                 state.loading = false;
             }
-            KeyCode::Tab => {
-                if !state.config.wallets.is_empty() {
-                    state.selected_wallet_index =
-                        (state.selected_wallet_index + 1) % state.config.wallets.len();
-                    state.selected_mint_index = 0; // Reset mint selection when switching wallets
-                    if let Some(wallet_config) = state
-                        .config
-                        .wallets
-                        .get(state.selected_wallet_index)
-                        .cloned()
-                    {
-                        state.config.set_active_wallet(&wallet_config.name);
-                        if let Err(e) = state.load_wallet(&wallet_config.name).await {
-                            state.set_error(format!("Failed to load wallet: {}", e));
-                        } else {
-                            // Refresh mint breakdowns for the new wallet
-                            if let Err(e) = state.refresh_mint_breakdowns().await {
-                                state.set_error(format!("Failed to refresh mint data: {}", e));
-                            }
+            KeyCode::Tab if !state.config.wallets.is_empty() => {
+                state.selected_wallet_index =
+                    (state.selected_wallet_index + 1) % state.config.wallets.len();
+                state.selected_mint_index = 0; // Reset mint selection when switching wallets
+                if let Some(wallet_config) = state
+                    .config
+                    .wallets
+                    .get(state.selected_wallet_index)
+                    .cloned()
+                {
+                    state.config.set_active_wallet(&wallet_config.name);
+                    if let Err(e) = state.load_wallet(&wallet_config.name).await {
+                        state.set_error(format!("Failed to load wallet: {}", e));
+                    } else {
+                        // Refresh mint breakdowns for the new wallet
+                        if let Err(e) = state.refresh_mint_breakdowns().await {
+                            state.set_error(format!("Failed to refresh mint data: {}", e));
                         }
                     }
                 }
@@ -160,30 +177,27 @@ impl EventHandler {
                     state.send_state.memo_input.pop();
                 }
             }
-            KeyCode::Tab => {
-                if !state.send_state.amount_input.is_empty() {
-                    if let Ok(amount) = state.send_state.amount_input.parse::<u64>() {
-                        if let Some(wallet_instance) = state.get_active_wallet() {
-                            if let Some(ref wallet) = wallet_instance.wallet {
-                                match wallet
-                                    .send(
-                                        amount,
-                                        if state.send_state.memo_input.is_empty() {
-                                            None
-                                        } else {
-                                            Some(state.send_state.memo_input.clone())
-                                        },
-                                    )
-                                    .await
-                                {
-                                    Ok(token) => {
-                                        state.send_state.generated_token = Some(token);
-                                        state.send_state.error = None;
-                                    }
-                                    Err(e) => {
-                                        state.send_state.error =
-                                            Some(format!("Failed to send: {}", e));
-                                    }
+            KeyCode::Tab if !state.send_state.amount_input.is_empty() => {
+                if let Ok(amount) = state.send_state.amount_input.parse::<u64>() {
+                    if let Some(wallet_instance) = state.get_active_wallet() {
+                        if let Some(ref wallet) = wallet_instance.wallet {
+                            match wallet
+                                .send(
+                                    amount,
+                                    if state.send_state.memo_input.is_empty() {
+                                        None
+                                    } else {
+                                        Some(state.send_state.memo_input.clone())
+                                    },
+                                )
+                                .await
+                            {
+                                Ok(token) => {
+                                    state.send_state.generated_token = Some(token);
+                                    state.send_state.error = None;
+                                }
+                                Err(e) => {
+                                    state.send_state.error = Some(format!("Failed to send: {}", e));
                                 }
                             }
                         }
@@ -217,21 +231,18 @@ impl EventHandler {
             KeyCode::Backspace => {
                 state.redeem_state.token_input.pop();
             }
-            KeyCode::Enter => {
-                if !state.redeem_state.token_input.is_empty() {
-                    if let Some(wallet_instance) = state.get_active_wallet() {
-                        if let Some(ref wallet) = wallet_instance.wallet {
-                            match wallet.redeem(&state.redeem_state.token_input).await {
-                                Ok(amount) => {
-                                    state.redeem_state.result =
-                                        Some(format!("Successfully redeemed {} sats", amount));
-                                    state.redeem_state.error = None;
-                                    state.redeem_state.token_input.clear();
-                                }
-                                Err(e) => {
-                                    state.redeem_state.error =
-                                        Some(format!("Failed to redeem: {}", e));
-                                }
+            KeyCode::Enter if !state.redeem_state.token_input.is_empty() => {
+                if let Some(wallet_instance) = state.get_active_wallet() {
+                    if let Some(ref wallet) = wallet_instance.wallet {
+                        match wallet.redeem(&state.redeem_state.token_input).await {
+                            Ok(amount) => {
+                                state.redeem_state.result =
+                                    Some(format!("Successfully redeemed {} sats", amount));
+                                state.redeem_state.error = None;
+                                state.redeem_state.token_input.clear();
+                            }
+                            Err(e) => {
+                                state.redeem_state.error = Some(format!("Failed to redeem: {}", e));
                             }
                         }
                     }
@@ -281,20 +292,16 @@ impl EventHandler {
         match key.code {
             KeyCode::Char('q') | KeyCode::Esc => state.switch_view(ActiveView::Main),
             KeyCode::Char('a') => state.switch_view(ActiveView::AddWallet),
-            KeyCode::Char('j') | KeyCode::Down => {
-                if !state.config.wallets.is_empty() {
-                    state.selected_wallet_index =
-                        (state.selected_wallet_index + 1) % state.config.wallets.len();
-                }
+            KeyCode::Char('j') | KeyCode::Down if !state.config.wallets.is_empty() => {
+                state.selected_wallet_index =
+                    (state.selected_wallet_index + 1) % state.config.wallets.len();
             }
-            KeyCode::Char('k') | KeyCode::Up => {
-                if !state.config.wallets.is_empty() {
-                    state.selected_wallet_index = if state.selected_wallet_index == 0 {
-                        state.config.wallets.len() - 1
-                    } else {
-                        state.selected_wallet_index - 1
-                    };
-                }
+            KeyCode::Char('k') | KeyCode::Up if !state.config.wallets.is_empty() => {
+                state.selected_wallet_index = if state.selected_wallet_index == 0 {
+                    state.config.wallets.len() - 1
+                } else {
+                    state.selected_wallet_index - 1
+                };
             }
             KeyCode::Enter => {
                 if let Some(wallet_config) = state.config.wallets.get(state.selected_wallet_index) {
@@ -348,62 +355,78 @@ impl EventHandler {
                     state.add_wallet_state.step - 1
                 };
             }
-            KeyCode::Enter => {
+            KeyCode::Enter
                 if !state.add_wallet_state.name_input.is_empty()
-                    && !state.add_wallet_state.nsec_input.is_empty()
-                {
-                    let mut wallet_config = crate::tui::config::WalletConfig::new(
-                        state.add_wallet_state.name_input.clone(),
-                        state.add_wallet_state.nsec_input.clone(),
-                    );
-
-                    if !state.add_wallet_state.mints_input.is_empty() {
-                        wallet_config.mints = state
-                            .add_wallet_state
-                            .mints_input
-                            .split(',')
-                            .map(|s| s.trim().to_string())
-                            .filter(|s| !s.is_empty())
-                            .collect();
+                    && !state.add_wallet_state.nsec_input.is_empty() =>
+            {
+                let keys = match Keys::parse(&state.add_wallet_state.nsec_input) {
+                    Ok(keys) => keys,
+                    Err(e) => {
+                        state.add_wallet_state.error = Some(format!("Invalid private key: {}", e));
+                        return Ok(());
                     }
-
-                    if !state.add_wallet_state.relays_input.is_empty() {
-                        wallet_config.relays = state
-                            .add_wallet_state
-                            .relays_input
-                            .split(',')
-                            .map(|s| s.trim().to_string())
-                            .filter(|s| !s.is_empty())
-                            .collect();
+                };
+                // The key was typed or pasted in plaintext
+                let ncryptsec = match state.session.encrypt_keys(&keys, nip49::KeySecurity::Weak) {
+                    Ok(ncryptsec) => ncryptsec,
+                    Err(e) => {
+                        state.add_wallet_state.error =
+                            Some(format!("Failed to encrypt key: {}", e));
+                        return Ok(());
                     }
+                };
+                let mut wallet_config = crate::tui::config::WalletConfig::new(
+                    state.add_wallet_state.name_input.clone(),
+                    ncryptsec,
+                );
 
-                    state.config.add_wallet(wallet_config.clone());
-                    state.wallets.insert(
-                        wallet_config.name.clone(),
-                        crate::tui::state::WalletInstance {
-                            config: wallet_config,
-                            wallet: None,
-                            state: None,
-                            balance: 0,
-                            history: Vec::new(),
-                            mint_breakdowns: Vec::new(),
-                            last_update: std::time::SystemTime::now(),
-                            error: None,
-                        },
-                    );
+                if !state.add_wallet_state.mints_input.is_empty() {
+                    wallet_config.mints = state
+                        .add_wallet_state
+                        .mints_input
+                        .split(',')
+                        .map(|s| s.trim().to_string())
+                        .filter(|s| !s.is_empty())
+                        .collect();
+                }
 
-                    if let Err(e) = state.config.save() {
-                        state.add_wallet_state.error =
-                            Some(format!("Failed to save config: {}", e));
-                    } else {
-                        state.switch_view(ActiveView::WalletManager);
-                        state.add_wallet_state = Default::default();
-                    }
+                if !state.add_wallet_state.relays_input.is_empty() {
+                    wallet_config.relays = state
+                        .add_wallet_state
+                        .relays_input
+                        .split(',')
+                        .map(|s| s.trim().to_string())
+                        .filter(|s| !s.is_empty())
+                        .collect();
+                }
+
+                state.config.add_wallet(wallet_config.clone());
+                state.wallets.insert(
+                    wallet_config.name.clone(),
+                    crate::tui::state::WalletInstance {
+                        config: wallet_config,
+                        wallet: None,
+                        state: None,
+                        balance: 0,
+                        history: Vec::new(),
+                        mint_breakdowns: Vec::new(),
+                        last_update: std::time::SystemTime::now(),
+                        error: None,
+                    },
+                );
+
+                if let Err(e) = state.config.save() {
+                    state.add_wallet_state.error = Some(format!("Failed to save config: {}", e));
+                } else {
+                    state.switch_view(ActiveView::WalletManager);
+                    state.add_wallet_state = Default::default();
                 }
             }
             KeyCode::Char('v') if key.modifiers.contains(KeyModifiers::CONTROL) => {
                 if let Ok(mut clipboard) = arboard::Clipboard::new() {
                     if let Ok(text) = clipboard.get_text() {
+                        // The clipboard may hold a private key
+                        let text = Zeroizing::new(text);
                         match state.add_wallet_state.step {
                             0 => state.add_wallet_state.name_input.push_str(&text),
                             1 => state.add_wallet_state.nsec_input.push_str(&text),
@@ -1,6 +1,7 @@
 pub mod app;
 pub mod config;
 pub mod events;
+pub mod secret_input;
 pub mod state;
 pub mod widgets;
 
@@ -17,6 +18,10 @@ use crossterm::{
 };
 use ratatui::{Terminal, backend::CrosstermBackend};
 use std::io;
+use std::time::Duration;
+
+/// How often the event loop wakes up to check for an idle keystore
+const IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(1);
 
 pub async fn run() -> Result<()> {
     enable_raw_mode()?;
@@ -31,6 +36,11 @@ pub async fn run() -> Result<()> {
     while !should_quit {
         terminal.draw(|f| app.draw(f))?;
 
+        if !event::poll(IDLE_CHECK_INTERVAL)? {
+            app.check_idle();
+            continue;
+        }
+
         if let Event::Key(key) = event::read()? {
             match key.code {
                 KeyCode::Char('q') if key.modifiers.contains(KeyModifiers::CONTROL) => {
@@ -0,0 +1,64 @@
+use std::fmt;
+use std::ops::Deref;
+use zeroize::{Zeroize, Zeroizing};
+
+/// Text typed into a password or key field.
+///
+/// Every buffer the text has been in is zeroized: growing copies it into a
+/// larger buffer and zeroizes the old one, and removing characters zeroizes
+/// what they occupied. It is never printed by `Debug`.
+#[derive(Clone, Default)]
+pub struct SecretInput(Zeroizing<String>);
+
+impl SecretInput {
+    pub fn push(&mut self, c: char) {
+        self.reserve(c.len_utf8());
+        self.0.push(c);
+    }
+
+    pub fn push_str(&mut self, text: &str) {
+        self.reserve(text.len());
+        self.0.push_str(text);
+    }
+
+    pub fn pop(&mut self) {
+        if let Some(c) = self.0.chars().next_back() {
+            let kept = self.0.len() - c.len_utf8();
+            self.replace(kept, self.0.capacity());
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.0.zeroize();
+    }
+
+    /// Make room for `additional` bytes without leaving a copy behind
+    fn reserve(&mut self, additional: usize) {
+        let needed = self.0.len() + additional;
+        if needed > self.0.capacity() {
+            self.replace(self.0.len(), needed.max(64).next_power_of_two());
+        }
+    }
+
+    /// Keep the first `len` bytes in a new buffer of `capacity`, zeroizing the old one
+    fn replace(&mut self, len: usize, capacity: usize) {
+        let mut text = String::with_capacity(capacity);
+        text.push_str(&self.0[..len]);
+        // Dropping the old buffer zeroizes it
+        self.0 = Zeroizing::new(text);
+    }
+}
+
+impl Deref for SecretInput {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for SecretInput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SecretInput(..)")
+    }
+}
@@ -1,9 +1,14 @@
-use crate::error::Result;
+use crate::error::{Error, Result};
 use crate::tui::config::{Config, WalletConfig};
+use crate::tui::secret_input::SecretInput;
+use nip60::keystore::{self, UnlockSession};
 use nip60::nip60::{Nip60Wallet, ProofBreakdown, SpendingHistory, WalletState};
 use nostr_sdk::prelude::*;
 use std::collections::HashMap;
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime};
+
+/// Inactivity after which the keystore locks and loaded keys are dropped
+pub const IDLE_TIMEOUT: Duration = Duration::from_secs(5 * 60);
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ActiveView {
@@ -14,6 +19,7 @@ pub enum ActiveView {
     Lightning,
     WalletManager,
     AddWallet,
+    Unlock,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -62,15 +68,23 @@ pub struct LightningState {
 #[derive(Debug, Clone, Default)]
 pub struct AddWalletState {
     pub name_input: String,
-    pub nsec_input: String,
+    pub nsec_input: SecretInput,
     pub mints_input: String,
     pub relays_input: String,
     pub error: Option<String>,
     pub step: u8,
 }
 
+#[derive(Debug, Clone, Default)]
+pub struct UnlockState {
+    pub password_input: SecretInput,
+    pub error: Option<String>,
+}
+
 pub struct AppState {
     pub config: Config,
+    pub session: UnlockSession,
+    pub unlock_state: UnlockState,
     pub wallets: HashMap<String, WalletInstance>,
     pub active_view: ActiveView,
     pub history_filter: HistoryFilter,
@@ -109,8 +123,10 @@ impl AppState {
 
         Ok(Self {
             config,
+            session: UnlockSession::new(IDLE_TIMEOUT),
+            unlock_state: UnlockState::default(),
             wallets,
-            active_view: ActiveView::Main,
+            active_view: ActiveView::Unlock,
             history_filter: HistoryFilter::All,
             send_state: SendState::default(),
             redeem_state: RedeemState::default(),
@@ -143,7 +159,10 @@ impl AppState {
     pub async fn load_wallet(&mut self, name: &str) -> Result<()> {
         if let Some(wallet_instance) = self.wallets.get_mut(name) {
             if wallet_instance.wallet.is_none() {
-                let keys = Keys::parse(&wallet_instance.config.nsec)?;
+                let keys = self
+                    .session
+                    .parse_keys(&wallet_instance.config.nsec)
+                    .map_err(|e| Error::custom(&e.to_string()))?;
                 let relays: Vec<&str> = wallet_instance
                     .config
                     .relays
@@ -169,6 +188,100 @@ impl AppState {
         Ok(())
     }
 
+    /// Unlock the keystore with the entered password, encrypt any keys
+    /// still stored in plaintext and load the active wallet
+    pub async fn unlock(&mut self) -> Result<()> {
+        let password = std::mem::take(&mut self.unlock_state.password_input);
+        if password.is_empty() {
+            self.unlock_state.error = Some("Enter a password".to_string());
+            return Ok(());
+        }
+
+        if let Some(encrypted) = self
+            .config
+            .wallets
+            .iter()
+            .find(|wallet| keystore::is_ncryptsec(&wallet.nsec))
+        {
+            if keystore::decrypt_keys(&encrypted.nsec, &password).is_err() {
+                self.unlock_state.error = Some("Wrong password".to_string());
+                return Ok(());
+            }
+        }
+
+        self.session.unlock(&password);
+        self.unlock_state = UnlockState::default();
+        if let Err(e) = self.encrypt_plaintext_keys() {
+            // Stay locked until the config is fixed rather than leave keys in plaintext
+            self.session.lock();
+            self.unlock_state.error = Some(format!("Failed to encrypt keys: {}", e));
+            return Ok(());
+        }
+        self.switch_view(ActiveView::Main);
+
+        if let Some(active_wallet_name) = self.config.active_wallet.clone() {
+            if let Err(e) = self.load_wallet(&active_wallet_name).await {
+                self.set_error(format!("Failed to load active wallet: {}", e));
+            }
+        }
+        Ok(())
+    }
+
+    /// Lock the keystore and drop the loaded wallets with their keys
+    pub fn lock(&mut self) {
+        self.session.lock();
+        for wallet_instance in self.wallets.values_mut() {
+            wallet_instance.wallet = None;
+        }
+        self.add_wallet_state = AddWalletState::default();
+        self.switch_view(ActiveView::Unlock);
+    }
+
+    /// Lock once the keystore has been idle for [`IDLE_TIMEOUT`]
+    pub fn lock_if_idle(&mut self) {
+        if self.session.lock_if_idle() {
+            self.lock();
+            self.unlock_state.error = Some("Locked after inactivity".to_string());
+        }
+    }
+
+    /// Replace the plaintext nsecs of older configs with NIP-49 ncryptsecs.
+    ///
+    /// Keys that do not parse are left as they are and named in the error,
+    /// after the others have been encrypted and saved.
+    fn encrypt_plaintext_keys(&mut self) -> Result<()> {
+        let mut changed = false;
+        let mut invalid = Vec::new();
+        for wallet_config in self.config.wallets.iter_mut() {
+            if keystore::is_ncryptsec(&wallet_config.nsec) {
+                continue;
+            }
+            let Ok(keys) = Keys::parse(&wallet_config.nsec) else {
+                invalid.push(wallet_config.name.clone());
+                continue;
+            };
+            wallet_config.nsec = self
+                .session
+                .encrypt_keys(&keys, nip49::KeySecurity::Weak)
+                .map_err(|e| Error::custom(&e.to_string()))?;
+            if let Some(wallet_instance) = self.wallets.get_mut(&wallet_config.name) {
+                wallet_instance.config.nsec = wallet_config.nsec.clone();
+            }
+            changed = true;
+        }
+
+        if changed {
+            self.config.save()?;
+        }
+        if !invalid.is_empty() {
+            return Err(Error::custom(&format!(
+                "invalid private key in wallet {}",
+                invalid.join(", ")
+            )));
+        }
+        Ok(())
+    }
+
     pub async fn refresh_wallet_data(&mut self, name: &str) -> Result<()> {
         if let Some(wallet_instance) = self.wallets.get_mut(name) {
             if let Some(ref wallet) = wallet_instance.wallet {
@@ -4,6 +4,7 @@ pub mod lightning;
 pub mod main;
 pub mod redeem;
 pub mod send;
+pub mod unlock;
 pub mod wallet_manager;
 
 pub use add_wallet::AddWalletWidget;
@@ -12,6 +13,7 @@ pub use lightning::LightningWidget;
 pub use main::MainWidget;
 pub use redeem::RedeemWidget;
 pub use send::SendWidget;
+pub use unlock::UnlockWidget;
 pub use wallet_manager::WalletManagerWidget;
 
 use ratatui::{
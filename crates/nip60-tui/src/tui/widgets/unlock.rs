@@ -0,0 +1,78 @@
+use crate::tui::state::AppState;
+use crate::tui::widgets::{
+    centered_rect, create_error_style, create_normal_style, create_selected_style,
+    create_title_style,
+};
+use ratatui::{
+    prelude::*,
+    widgets::{Block, Borders, Paragraph, Wrap},
+};
+
+pub struct UnlockWidget;
+
+impl UnlockWidget {
+    pub fn render(f: &mut Frame, state: &AppState, area: Rect) {
+        let area = centered_rect(60, 50, area);
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(3),
+                Constraint::Length(3),
+                Constraint::Min(4),
+                Constraint::Length(3),
+            ])
+            .split(area);
+
+        Self::render_header(f, chunks[0]);
+        Self::render_password(f, state, chunks[1]);
+        Self::render_info(f, state, chunks[2]);
+        Self::render_help(f, chunks[3]);
+    }
+
+    fn render_header(f: &mut Frame, area: Rect) {
+        let header = Paragraph::new("Unlock Keystore")
+            .style(create_title_style())
+            .alignment(Alignment::Center)
+            .block(Block::default().borders(Borders::ALL));
+
+        f.render_widget(header, area);
+    }
+
+    fn render_password(f: &mut Frame, state: &AppState, area: Rect) {
+        let masked = "*".repeat(state.unlock_state.password_input.chars().count());
+        let password_input = Paragraph::new(format!("Password: {}", masked))
+            .style(create_selected_style())
+            .block(Block::default().borders(Borders::ALL));
+
+        f.render_widget(password_input, area);
+    }
+
+    fn render_info(f: &mut Frame, state: &AppState, area: Rect) {
+        let (text, style) = if let Some(ref error) = state.unlock_state.error {
+            (error.clone(), create_error_style())
+        } else {
+            (
+                "Wallet keys are stored encrypted (NIP-49) with this password. \
+                 Keys still in plaintext are encrypted on unlock, and the \
+                 keystore locks again after a period of inactivity."
+                    .to_string(),
+                create_normal_style(),
+            )
+        };
+
+        let info = Paragraph::new(text)
+            .style(style)
+            .wrap(Wrap { trim: true })
+            .block(Block::default().borders(Borders::ALL));
+
+        f.render_widget(info, area);
+    }
+
+    fn render_help(f: &mut Frame, area: Rect) {
+        let help_paragraph = Paragraph::new("Enter: Unlock • Ctrl+Q: Quit")
+            .style(Style::default().fg(Color::Gray))
+            .alignment(Alignment::Center);
+
+        f.render_widget(help_paragraph, area);
+    }
+}
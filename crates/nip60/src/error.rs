@@ -1,4 +1,5 @@
 use derive_more::From;
+use ecash_402_wallet::error::MintError;
 use std::fmt;
 
 pub type Result<T> = core::result::Result<T, Error>;
@@ -27,12 +28,33 @@ pub enum Error {
 
     #[from]
     KeyError(nostr_sdk::key::Error),
+
+    #[from]
+    MintError(MintError),
 }
 
 impl Error {
     pub fn custom(msg: &str) -> Self {
         Error::WalletError(cdk::Error::Custom(msg.to_string()))
     }
+
+    /// The mint's error response, if this error came from one
+    pub fn mint_error(&self) -> Option<&MintError> {
+        match self {
+            Error::MintError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<ecash_402_wallet::error::Error> for Error {
+    fn from(e: ecash_402_wallet::error::Error) -> Self {
+        match e {
+            ecash_402_wallet::error::Error::MintError(e) => Error::MintError(e),
+            ecash_402_wallet::error::Error::WalletError(e) => Error::WalletError(e),
+            e => Error::custom(&e.to_string()),
+        }
+    }
 }
 
 impl fmt::Display for Error {
@@ -46,6 +68,7 @@ impl fmt::Display for Error {
             Error::SerializationError(e) => write!(f, "Serialization error: {}", e),
             Error::YamlError(e) => write!(f, "YAML error: {}", e),
             Error::KeyError(e) => write!(f, "Key error: {}", e),
+            Error::MintError(e) => write!(f, "Mint error: {}", e),
         }
     }
 }
@@ -61,6 +84,7 @@ impl std::error::Error for Error {
             Error::SerializationError(e) => Some(e),
             Error::YamlError(e) => Some(e),
             Error::KeyError(e) => Some(e),
+            Error::MintError(e) => Some(e),
         }
     }
 }
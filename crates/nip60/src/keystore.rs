@@ -0,0 +1,268 @@
+use crate::error::{Error, Result};
+use bip39::Mnemonic;
+use chacha20poly1305::{
+    aead::{rand_core::RngCore, Aead, AeadCore, KeyInit, OsRng},
+    XChaCha20Poly1305, XNonce,
+};
+use nostr_sdk::nips::nip49::{EncryptedSecretKey, KeySecurity};
+use nostr_sdk::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+use unicode_normalization::UnicodeNormalization;
+use zeroize::Zeroizing;
+
+/// scrypt cost (`N = 2^log_n`) of new encryptions, the NIP-49 minimum
+/// recommendation
+pub const DEFAULT_LOG_N: u8 = 16;
+
+const SALT_SIZE: usize = 16;
+const KEY_SIZE: usize = 32;
+const NCRYPTSEC_PREFIX: &str = "ncryptsec1";
+
+/// A secret encrypted with a password the way NIP-49 encrypts nostr keys:
+/// scrypt (`r = 8`, `p = 1`) over the NFKC-normalized password derives an
+/// XChaCha20-Poly1305 key. Byte fields are hex encoded.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EncryptedSecret {
+    pub log_n: u8,
+    pub salt: String,
+    pub nonce: String,
+    pub ciphertext: String,
+}
+
+impl EncryptedSecret {
+    pub fn encrypt(secret: &[u8], password: &str, log_n: u8) -> Result<Self> {
+        let mut salt = [0u8; SALT_SIZE];
+        OsRng.fill_bytes(&mut salt);
+        let key = derive_key(password, &salt, log_n)?;
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = XChaCha20Poly1305::new((&*key).into())
+            .encrypt(&nonce, secret)
+            .map_err(|_| Error::custom("Failed to encrypt secret"))?;
+
+        Ok(Self {
+            log_n,
+            salt: hex::encode(salt),
+            nonce: hex::encode(nonce),
+            ciphertext: hex::encode(ciphertext),
+        })
+    }
+
+    pub fn decrypt(&self, password: &str) -> Result<Zeroizing<Vec<u8>>> {
+        let salt = decode_hex(&self.salt)?;
+        let nonce = decode_hex(&self.nonce)?;
+        if nonce.len() != 24 {
+            return Err(Error::custom("Invalid nonce in encrypted secret"));
+        }
+        let ciphertext = decode_hex(&self.ciphertext)?;
+
+        let key = derive_key(password, &salt, self.log_n)?;
+        let secret = XChaCha20Poly1305::new((&*key).into())
+            .decrypt(XNonce::from_slice(&nonce), ciphertext.as_slice())
+            .map_err(|_| Error::custom("Wrong password or corrupted secret"))?;
+        Ok(Zeroizing::new(secret))
+    }
+}
+
+fn derive_key(password: &str, salt: &[u8], log_n: u8) -> Result<Zeroizing<[u8; KEY_SIZE]>> {
+    let password: Zeroizing<String> = Zeroizing::new(password.nfkc().collect());
+    let params = scrypt::Params::new(log_n, 8, 1, KEY_SIZE)
+        .map_err(|e| Error::custom(&format!("Invalid scrypt parameters: {}", e)))?;
+
+    let mut key = Zeroizing::new([0u8; KEY_SIZE]);
+    scrypt::scrypt(password.as_bytes(), salt, &params, key.as_mut())
+        .map_err(|e| Error::custom(&format!("Key derivation failed: {}", e)))?;
+    Ok(key)
+}
+
+fn decode_hex(value: &str) -> Result<Vec<u8>> {
+    hex::decode(value).map_err(|e| Error::custom(&format!("Invalid encrypted secret: {}", e)))
+}
+
+/// Encrypt a BIP39 mnemonic, rejecting anything that is not one
+pub fn encrypt_mnemonic(mnemonic: &str, password: &str) -> Result<EncryptedSecret> {
+    Mnemonic::from_str(mnemonic).map_err(|_| Error::custom("Invalid mnemonic"))?;
+    EncryptedSecret::encrypt(mnemonic.as_bytes(), password, DEFAULT_LOG_N)
+}
+
+pub fn decrypt_mnemonic(encrypted: &EncryptedSecret, password: &str) -> Result<Zeroizing<String>> {
+    let bytes = encrypted.decrypt(password)?;
+    let mnemonic = std::str::from_utf8(&bytes)
+        .map_err(|_| Error::custom("Encrypted mnemonic is not valid UTF-8"))?;
+    Ok(Zeroizing::new(mnemonic.to_string()))
+}
+
+/// Whether `key` is a NIP-49 encrypted key rather than an nsec or hex key
+pub fn is_ncryptsec(key: &str) -> bool {
+    key.starts_with(NCRYPTSEC_PREFIX)
+}
+
+/// Encrypt `keys` as a NIP-49 `ncryptsec`. `key_security` records whether
+/// the key was ever handled unencrypted, e.g. stored in a config file.
+pub fn encrypt_keys(keys: &Keys, password: &str, key_security: KeySecurity) -> Result<String> {
+    let encrypted =
+        EncryptedSecretKey::new(keys.secret_key(), password, DEFAULT_LOG_N, key_security)
+            .map_err(|e| Error::custom(&format!("Failed to encrypt key: {}", e)))?;
+    encrypted
+        .to_bech32()
+        .map_err(|e| Error::custom(&format!("Failed to encode key: {}", e)))
+}
+
+pub fn decrypt_keys(ncryptsec: &str, password: &str) -> Result<Keys> {
+    let encrypted = EncryptedSecretKey::from_bech32(ncryptsec)
+        .map_err(|e| Error::custom(&format!("Invalid ncryptsec: {}", e)))?;
+    let secret_key = encrypted
+        .decrypt(password)
+        .map_err(|_| Error::custom("Wrong password or corrupted key"))?;
+    Ok(Keys::new(secret_key))
+}
+
+/// The keystore password, held until the session is locked or sits unused
+/// for `idle_timeout`
+pub struct UnlockSession {
+    password: Option<Zeroizing<String>>,
+    idle_timeout: Duration,
+    last_used: Instant,
+}
+
+impl UnlockSession {
+    /// A locked session
+    pub fn new(idle_timeout: Duration) -> Self {
+        Self {
+            password: None,
+            idle_timeout,
+            last_used: Instant::now(),
+        }
+    }
+
+    pub fn unlock(&mut self, password: &str) {
+        self.password = Some(Zeroizing::new(password.to_string()));
+        self.last_used = Instant::now();
+    }
+
+    pub fn lock(&mut self) {
+        self.password = None;
+    }
+
+    pub fn is_unlocked(&self) -> bool {
+        self.password.is_some() && self.last_used.elapsed() < self.idle_timeout
+    }
+
+    /// Lock a session idle past its timeout, returning whether it was
+    /// locked just now
+    pub fn lock_if_idle(&mut self) -> bool {
+        if self.password.is_some() && !self.is_unlocked() {
+            self.lock();
+            return true;
+        }
+        false
+    }
+
+    /// Count as activity, restarting the idle timeout
+    pub fn touch(&mut self) {
+        if self.is_unlocked() {
+            self.last_used = Instant::now();
+        }
+    }
+
+    fn password(&mut self) -> Result<&str> {
+        self.lock_if_idle();
+        self.touch();
+        self.password
+            .as_deref()
+            .map(String::as_str)
+            .ok_or_else(|| Error::custom("Keystore is locked"))
+    }
+
+    pub fn encrypt_keys(&mut self, keys: &Keys, key_security: KeySecurity) -> Result<String> {
+        encrypt_keys(keys, self.password()?, key_security)
+    }
+
+    /// Keys from an `ncryptsec`, or from an nsec or hex key as they are
+    pub fn parse_keys(&mut self, key: &str) -> Result<Keys> {
+        if is_ncryptsec(key) {
+            decrypt_keys(key, self.password()?)
+        } else {
+            Ok(Keys::parse(key)?)
+        }
+    }
+
+    pub fn encrypt_mnemonic(&mut self, mnemonic: &str) -> Result<EncryptedSecret> {
+        encrypt_mnemonic(mnemonic, self.password()?)
+    }
+
+    pub fn decrypt_mnemonic(&mut self, encrypted: &EncryptedSecret) -> Result<Zeroizing<String>> {
+        decrypt_mnemonic(encrypted, self.password()?)
+    }
+}
+
+impl fmt::Debug for UnlockSession {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UnlockSession")
+            .field("unlocked", &self.is_unlocked())
+            .field("idle_timeout", &self.idle_timeout)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Cheap scrypt cost for tests; decryption reads it from the secret
+    const TEST_LOG_N: u8 = 4;
+
+    #[test]
+    fn encrypted_secret_round_trips_and_rejects_wrong_password() {
+        let encrypted = EncryptedSecret::encrypt(b"secret bytes", "hunter2", TEST_LOG_N).unwrap();
+        assert_eq!(
+            encrypted.decrypt("hunter2").unwrap().as_slice(),
+            b"secret bytes"
+        );
+        assert!(encrypted.decrypt("hunter3").is_err());
+
+        // Survives the config file and normalizes the password like NIP-49
+        let json = serde_json::to_string(&encrypted).unwrap();
+        let parsed: EncryptedSecret = serde_json::from_str(&json).unwrap();
+        let normalized = EncryptedSecret::encrypt(b"x", "\u{212b}", TEST_LOG_N).unwrap();
+        assert_eq!(parsed, encrypted);
+        assert_eq!(normalized.decrypt("\u{00c5}").unwrap().as_slice(), b"x");
+    }
+
+    #[test]
+    fn tampered_secret_fails_to_decrypt() {
+        let mut encrypted = EncryptedSecret::encrypt(b"secret", "pw", TEST_LOG_N).unwrap();
+        let mut ciphertext = hex::decode(&encrypted.ciphertext).unwrap();
+        ciphertext[0] ^= 1;
+        encrypted.ciphertext = hex::encode(ciphertext);
+        assert!(encrypted.decrypt("pw").is_err());
+    }
+
+    #[test]
+    fn mnemonic_round_trips() {
+        let mnemonic = Mnemonic::generate(12).unwrap().to_string();
+        let encrypted = EncryptedSecret::encrypt(mnemonic.as_bytes(), "pw", TEST_LOG_N).unwrap();
+        assert_eq!(*decrypt_mnemonic(&encrypted, "pw").unwrap(), mnemonic);
+        assert!(decrypt_mnemonic(&encrypted, "other").is_err());
+        assert!(encrypt_mnemonic("not a mnemonic", "pw").is_err());
+    }
+
+    #[test]
+    fn keys_round_trip_and_reject_wrong_password() {
+        let keys = Keys::generate();
+        let ncryptsec =
+            EncryptedSecretKey::new(keys.secret_key(), "pw", TEST_LOG_N, KeySecurity::Weak)
+                .unwrap()
+                .to_bech32()
+                .unwrap();
+        assert!(is_ncryptsec(&ncryptsec));
+        assert_eq!(
+            decrypt_keys(&ncryptsec, "pw").unwrap().secret_key(),
+            keys.secret_key()
+        );
+        assert!(decrypt_keys(&ncryptsec, "wrong").is_err());
+        assert!(decrypt_keys("ncryptsec1invalid", "pw").is_err());
+    }
+}
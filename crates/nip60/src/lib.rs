@@ -1,3 +1,4 @@
 pub mod error;
+pub mod keystore;
 pub mod nip60;
 pub mod wallet_operations;
@@ -1,11 +1,16 @@
 use crate::error::Result;
-use ecash_402_wallet::mint::{KeysetInfo, MintClient};
+use ecash_402_wallet::dleq::{self, DleqPolicy, TokenVerification};
+use ecash_402_wallet::inventory::{KeysetMigration, ProofStateSync};
+use ecash_402_wallet::mint::{
+    KeysetInfo, KeysetResponse, MintClient, MintClientConfig, MintTransport,
+};
+use ecash_402_wallet::models::{SendFeeOptions, SendPreview};
 use ecash_402_wallet::wallet::CashuWalletClient;
 
 use bip39::Mnemonic;
 use cdk::mint_url::MintUrl;
 use cdk::nuts::CurrencyUnit;
-use cdk::nuts::Proofs;
+use cdk::nuts::{Proofs, State};
 
 use nostr_sdk::prelude::*;
 use serde::{Deserialize, Serialize};
@@ -29,6 +34,47 @@ pub mod kinds {
     pub const QUOTE: Kind = Kind::Custom(7374);
 }
 
+/// Attempts at selecting proofs that cover a send plus its own redeem fee
+const MAX_FEE_SELECTION_ROUNDS: usize = 8;
+
+/// Attempts at publishing proofs a swap returned before giving up
+const PUBLISH_SWAPPED_PROOFS_ATTEMPTS: usize = 3;
+
+/// Proofs a send draws from one mint
+#[derive(Debug, Clone)]
+struct SendSelection {
+    /// Proofs spent, sent as they are unless they must be swapped
+    inputs: Proofs,
+    /// Value of the token sent
+    token_amount: u64,
+    /// Input fee for swapping `inputs` into the token and change
+    swap_fee: u64,
+    /// Returned to the wallet by the swap
+    change: u64,
+    /// Fee the receiver pays to redeem the token
+    redeem_fee: u64,
+    /// Token events holding `inputs`
+    spent_event_ids: Vec<EventId>,
+}
+
+impl SendSelection {
+    fn needs_swap(&self) -> bool {
+        let input_total: u64 = self.inputs.iter().map(|p| u64::from(p.amount)).sum();
+        input_total != self.token_amount
+    }
+}
+
+/// Token events to replace when moving a mint's proofs off inactive keysets
+#[derive(Debug, Clone)]
+struct KeysetMigrationPlan {
+    /// Proofs on inactive keysets, to swap
+    stale_proofs: Proofs,
+    /// Other proofs of the replaced events, kept as they are
+    kept_proofs: Proofs,
+    spent_event_ids: Vec<EventId>,
+    inactive_keysets: Vec<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WalletStats {
     pub balance: u64,
@@ -100,6 +146,13 @@ pub struct EventDetail {
     pub memo: Option<String>,
 }
 
+/// A token recorded by [`Nip60Wallet::record_receive`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReceivedToken {
+    pub amount: u64,
+    pub verification: TokenVerification,
+}
+
 #[derive(Debug, Clone)]
 pub struct ProofBreakdown {
     pub mint_url: String,
@@ -156,6 +209,8 @@ pub struct Nip60Wallet {
     client: Client,
     mints: Vec<String>,
     mint_infos: HashMap<String, MintInfo>,
+    dleq_policy: DleqPolicy,
+    transport: MintTransport,
 }
 
 impl std::fmt::Debug for Nip60Wallet {
@@ -163,13 +218,19 @@ impl std::fmt::Debug for Nip60Wallet {
         f.debug_struct("Nip60Wallet")
             .field("mints", &self.mints)
             .field("mint_infos", &self.mint_infos)
+            .field("dleq_policy", &self.dleq_policy)
+            .field("mint_client_config", self.transport.config())
             .finish_non_exhaustive()
     }
 }
 
 impl MintInfo {
     pub async fn from_url(url: String) -> Result<Self> {
-        let client = MintClient::new(&url).unwrap();
+        Self::from_client(&MintClient::new(&url)?).await
+    }
+
+    pub async fn from_client(client: &MintClient) -> Result<Self> {
+        let url = client.url_string();
 
         let keysets = match client.get_keysets().await {
             Ok(response) => response.keysets,
@@ -215,6 +276,8 @@ impl Nip60Wallet {
             client,
             mints,
             mint_infos,
+            dleq_policy: DleqPolicy::default(),
+            transport: MintTransport::shared(),
         };
         wallet.initialize_mint_infos().await?;
 
@@ -240,6 +303,8 @@ impl Nip60Wallet {
             client,
             mints,
             mint_infos,
+            dleq_policy: DleqPolicy::default(),
+            transport: MintTransport::shared(),
         };
 
         wallet.publish_wallet_config().await?;
@@ -252,7 +317,7 @@ impl Nip60Wallet {
     pub async fn initialize_mint_infos(&mut self) -> Result<()> {
         for mint_url in &self.mints {
             if !self.mint_infos.contains_key(mint_url) {
-                match MintInfo::from_url(mint_url.clone()).await {
+                match self.fetch_mint_info(mint_url).await {
                     Ok(mint_info) => {
                         self.mint_infos.insert(mint_url.clone(), mint_info);
                     }
@@ -272,6 +337,10 @@ impl Nip60Wallet {
         Ok(())
     }
 
+    async fn fetch_mint_info(&self, mint_url: &str) -> Result<MintInfo> {
+        MintInfo::from_client(&self.transport.client_for(mint_url)?).await
+    }
+
     pub fn get_mint_info(&self, mint_url: &str) -> Option<&MintInfo> {
         self.mint_infos.get(mint_url)
     }
@@ -312,8 +381,231 @@ impl Nip60Wallet {
             .collect()
     }
 
+    /// Swap proofs on keysets a mint has rotated out into its active keyset,
+    /// before the mint stops honoring them.
+    ///
+    /// Refreshes the keysets of every mint first, then replaces the token
+    /// events holding such proofs. Returns one entry per mint that had any.
+    pub async fn migrate_inactive_keysets(&mut self) -> Result<Vec<KeysetMigration>> {
+        for mint_url in self.mints.clone() {
+            self.refresh_mint_info(&mint_url).await?;
+        }
+
+        let state = self.fetch_wallet_state().await?;
+        let mut migrations = Vec::new();
+
+        for mint_url in &self.mints {
+            let Some(plan) = Self::plan_keyset_migration(&state, &self.get_mint_keysets(mint_url))
+            else {
+                continue;
+            };
+            let unit = self.determine_unit_from_proofs(mint_url, &plan.stale_proofs)?;
+
+            let client = self
+                .transport
+                .client_for(mint_url)?
+                .with_dleq_policy(self.dleq_policy);
+            let proofs_moved = plan.stale_proofs.len();
+            let swap = client.swap(plan.stale_proofs, &[]).await?;
+            let amount_moved: u64 = swap.change.iter().map(|p| u64::from(p.amount)).sum();
+
+            let mut new_proofs = plan.kept_proofs;
+            new_proofs.extend(swap.change);
+            self.publish_swapped_proofs(mint_url, new_proofs, &plan.spent_event_ids)
+                .await?;
+            for event_id in &plan.spent_event_ids {
+                self.delete_token_event(event_id).await?;
+            }
+
+            migrations.push(KeysetMigration {
+                mint_url: mint_url.clone(),
+                unit,
+                inactive_keysets: plan.inactive_keysets,
+                proofs_moved,
+                amount_moved,
+                fee: swap.fee,
+            });
+        }
+
+        Ok(migrations)
+    }
+
+    /// The proofs of a mint's inactive `keysets` to swap, and the token
+    /// events to replace along with the other proofs they hold
+    fn plan_keyset_migration(
+        state: &WalletState,
+        keysets: &[KeysetInfo],
+    ) -> Option<KeysetMigrationPlan> {
+        let stale_proofs: Proofs = state
+            .proofs
+            .iter()
+            .filter(|proof| {
+                let keyset_id = proof.keyset_id.to_string();
+                keysets.iter().any(|k| !k.active && k.id == keyset_id)
+            })
+            .cloned()
+            .collect();
+        if stale_proofs.is_empty() {
+            return None;
+        }
+
+        let mut inactive_keysets: Vec<String> = stale_proofs
+            .iter()
+            .map(|proof| proof.keyset_id.to_string())
+            .collect();
+        inactive_keysets.sort();
+        inactive_keysets.dedup();
+
+        let spent_event_ids = Self::event_ids_of(state, &stale_proofs);
+        // Proofs sharing an event with stale ones move to the new event as they are
+        let kept_proofs: Proofs = state
+            .proofs
+            .iter()
+            .filter(|proof| {
+                state
+                    .proof_to_event_id
+                    .get(&proof.c.to_string())
+                    .and_then(|event_id| EventId::from_hex(event_id).ok())
+                    .is_some_and(|event_id| spent_event_ids.contains(&event_id))
+                    && !stale_proofs.iter().any(|stale| stale.c == proof.c)
+            })
+            .cloned()
+            .collect();
+
+        Some(KeysetMigrationPlan {
+            stale_proofs,
+            kept_proofs,
+            spent_event_ids,
+            inactive_keysets,
+        })
+    }
+
+    /// Check every proof against its mint's NUT-07 state.
+    ///
+    /// Spent proofs are dropped by rolling their token events over to the
+    /// remaining proofs, and the correction is recorded in the spending
+    /// history. Pending proofs are kept and reported. Proofs whose keyset
+    /// belongs to none of the wallet's mints are not checked.
+    pub async fn sync_proof_states(&self) -> Result<Vec<ProofStateSync>> {
+        let state = self.fetch_wallet_state().await?;
+        let mut syncs = Vec::new();
+
+        for mint_url in &self.mints {
+            let proofs: Proofs = state
+                .proofs
+                .iter()
+                .filter(|proof| {
+                    self.get_mint_url_by_keyset_id(&proof.keyset_id.to_string())
+                        .as_deref()
+                        == Some(mint_url.as_str())
+                })
+                .cloned()
+                .collect();
+            if proofs.is_empty() {
+                continue;
+            }
+
+            let client = self.transport.client_for(mint_url)?;
+            let states = client.check_proof_states(&proofs).await?;
+
+            let mut spent_proofs = Vec::new();
+            let mut pending = Vec::new();
+            for (proof, proof_state) in proofs.iter().zip(&states) {
+                match proof_state {
+                    State::Spent => spent_proofs.push(proof.clone()),
+                    State::Pending => pending.push(proof.clone()),
+                    _ => {}
+                }
+            }
+
+            let spent_amount: u64 = spent_proofs.iter().map(|p| u64::from(p.amount)).sum();
+            if !spent_proofs.is_empty() {
+                self.remove_spent_proofs(mint_url, &state, &spent_proofs, spent_amount)
+                    .await?;
+            }
+
+            syncs.push(ProofStateSync {
+                mint_url: mint_url.clone(),
+                unit: self.determine_unit_from_proofs(mint_url, &proofs)?,
+                checked: proofs.len(),
+                spent_count: spent_proofs.len(),
+                spent_amount,
+                pending,
+            });
+        }
+
+        Ok(syncs)
+    }
+
+    /// Replace the token events holding `spent_proofs` with one holding
+    /// their other proofs, and record the spent amount as outgoing
+    async fn remove_spent_proofs(
+        &self,
+        mint_url: &str,
+        state: &WalletState,
+        spent_proofs: &Proofs,
+        spent_amount: u64,
+    ) -> Result<()> {
+        let affected_events: HashSet<String> = spent_proofs
+            .iter()
+            .filter_map(|proof| state.proof_to_event_id.get(&proof.c.to_string()))
+            .cloned()
+            .collect();
+        let remaining_proofs: Proofs = state
+            .proofs
+            .iter()
+            .filter(|proof| {
+                state
+                    .proof_to_event_id
+                    .get(&proof.c.to_string())
+                    .is_some_and(|event_id| affected_events.contains(event_id))
+                    && !spent_proofs.iter().any(|spent| spent.c == proof.c)
+            })
+            .cloned()
+            .collect();
+        let spent_event_ids: Vec<EventId> = affected_events
+            .iter()
+            .filter_map(|id| EventId::from_hex(id).ok())
+            .collect();
+
+        let mut event_refs = Vec::new();
+        for event_id in &spent_event_ids {
+            event_refs.push((
+                "e".to_string(),
+                event_id.to_hex(),
+                "".to_string(),
+                "destroyed".to_string(),
+            ));
+        }
+
+        if !remaining_proofs.is_empty() {
+            let new_token_event_id = self
+                .create_token_event(
+                    mint_url,
+                    remaining_proofs,
+                    spent_event_ids.iter().map(|id| id.to_hex()).collect(),
+                )
+                .await?;
+            event_refs.push((
+                "e".to_string(),
+                new_token_event_id.to_hex(),
+                "".to_string(),
+                "created".to_string(),
+            ));
+        }
+
+        self.create_spending_history("out", spent_amount, event_refs)
+            .await?;
+
+        for event_id in &spent_event_ids {
+            self.delete_token_event(event_id).await?;
+        }
+
+        Ok(())
+    }
+
     pub async fn refresh_mint_info(&mut self, mint_url: &str) -> Result<()> {
-        match MintInfo::from_url(mint_url.to_string()).await {
+        match self.fetch_mint_info(mint_url).await {
             Ok(mint_info) => {
                 self.mint_infos.insert(mint_url.to_string(), mint_info);
                 Ok(())
@@ -385,6 +677,8 @@ impl Nip60Wallet {
                 client,
                 mints: config.mints,
                 mint_infos: HashMap::new(),
+                dleq_policy: DleqPolicy::default(),
+                transport: MintTransport::shared(),
             };
             wallet.initialize_mint_infos().await?;
             return Ok(Some(wallet));
@@ -474,7 +768,10 @@ impl Nip60Wallet {
         Ok(())
     }
 
-    pub async fn record_receive(&self, token_string: &str) -> Result<u64> {
+    /// Record a received token. Under [`DleqPolicy::Flag`] a token whose
+    /// mint keys cannot be fetched is still recorded, with its proofs
+    /// counted as unchecked in the returned verification.
+    pub async fn record_receive(&self, token_string: &str) -> Result<ReceivedToken> {
         let parsed_token = self.parse_cashu_token(token_string)?;
         let total_amount = self.calculate_token_amount(&parsed_token)?;
         let mut created_event_ids = Vec::new();
@@ -486,6 +783,13 @@ impl Nip60Wallet {
         let proofs = parsed_token.proofs(&empty_keysets).map_err(|e| {
             crate::error::Error::custom(&format!("Failed to get proofs from token: {}", e))
         })?;
+        let verification = match self.token_keysets(&mint_url, &proofs).await {
+            Ok(keysets) => dleq::verify_token(&parsed_token, &keysets, self.dleq_policy)
+                .map_err(|e| Error::custom(&format!("Token verification failed: {}", e)))?,
+            Err(e) if self.dleq_policy == DleqPolicy::Reject => return Err(e),
+            Err(_) => TokenVerification::unchecked(proofs.len()),
+        };
+
         let token_event_id = self.create_token_event(&mint_url, proofs, vec![]).await?;
         created_event_ids.push(token_event_id);
 
@@ -504,7 +808,76 @@ impl Nip60Wallet {
         self.create_spending_history("in", total_amount, event_refs)
             .await?;
 
-        Ok(total_amount)
+        Ok(ReceivedToken {
+            amount: total_amount,
+            verification,
+        })
+    }
+
+    /// Check the DLEQ proofs of a token against its mint's keysets (NUT-12)
+    pub async fn verify_token(&self, token_string: &str) -> Result<TokenVerification> {
+        let parsed_token = self.parse_cashu_token(token_string)?;
+        let mint_url = parsed_token
+            .mint_url()
+            .map_err(|e| Error::custom(&format!("Failed to get mint URL: {}", e)))?
+            .to_string();
+        let proofs = parsed_token.proofs(&[]).unwrap_or_default();
+        let keysets = self.token_keysets(&mint_url, &proofs).await?;
+
+        dleq::verify_token(&parsed_token, &keysets, self.dleq_policy)
+            .map_err(|e| Error::custom(&format!("Token verification failed: {}", e)))
+    }
+
+    /// Keys of every known keyset of `mint_url`, for checking the DLEQ
+    /// proofs of `proofs`. The mint is asked for its keysets when the cache
+    /// lacks one of theirs, as it may have rotated in a keyset since.
+    async fn token_keysets(&self, mint_url: &str, proofs: &Proofs) -> Result<Vec<KeysetResponse>> {
+        let client = self
+            .transport
+            .client_for(mint_url)
+            .map_err(|e| Error::custom(&format!("Invalid mint URL: {}", e)))?;
+
+        let mut keyset_ids: Vec<String> = self
+            .get_mint_keysets(mint_url)
+            .into_iter()
+            .map(|k| k.id)
+            .collect();
+        let uncached = proofs
+            .iter()
+            .any(|proof| !keyset_ids.contains(&proof.keyset_id.to_string()));
+        if keyset_ids.is_empty() || uncached {
+            keyset_ids = client
+                .get_keysets()
+                .await?
+                .keysets
+                .into_iter()
+                .map(|k| k.id)
+                .collect();
+        }
+
+        let mut keysets = Vec::new();
+        for keyset_id in keyset_ids {
+            keysets.extend(client.get_keyset_keys(&keyset_id).await?.keysets);
+        }
+        Ok(keysets)
+    }
+
+    pub fn set_dleq_policy(&mut self, policy: DleqPolicy) {
+        self.dleq_policy = policy;
+    }
+
+    pub fn dleq_policy(&self) -> DleqPolicy {
+        self.dleq_policy
+    }
+
+    /// Use `config` for all further requests to mints
+    pub fn set_mint_client_config(&mut self, config: MintClientConfig) -> Result<()> {
+        self.transport = MintTransport::new(config)?;
+        Ok(())
+    }
+
+    pub fn mint_client_config(&self) -> &MintClientConfig {
+        self.transport.config()
     }
 
     pub fn parse_cashu_token(&self, token_string: &str) -> Result<Token> {
@@ -817,6 +1190,40 @@ impl Nip60Wallet {
         }
     }
 
+    /// Publish `proofs` received from a mint swap in a token event replacing
+    /// `spent_event_ids`, retrying since the proofs swapped for them are
+    /// already spent.
+    ///
+    /// If every attempt fails, the error carries the proofs as a cashu token
+    /// so they can still be received.
+    async fn publish_swapped_proofs(
+        &self,
+        mint_url: &str,
+        proofs: Proofs,
+        spent_event_ids: &[EventId],
+    ) -> Result<EventId> {
+        let del: Vec<String> = spent_event_ids.iter().map(|id| id.to_hex()).collect();
+
+        let mut last_error = None;
+        for _ in 0..PUBLISH_SWAPPED_PROOFS_ATTEMPTS {
+            match self
+                .create_token_event(mint_url, proofs.clone(), del.clone())
+                .await
+            {
+                Ok(event_id) => return Ok(event_id),
+                Err(e) => last_error = Some(e),
+            }
+        }
+
+        let token = self.create_cashu_token_string(mint_url, proofs, None, None)?;
+        Err(crate::error::Error::custom(&format!(
+            "Failed to publish proofs from a swap at {} ({}). Receive this token to recover them: {}",
+            mint_url,
+            last_error.map(|e| e.to_string()).unwrap_or_default(),
+            token
+        )))
+    }
+
     async fn delete_token_event(&self, token_id: &EventId) -> Result<()> {
         // Create a NIP-09 delete event (kind 5)
         let delete_builder = EventBuilder::new(Kind::EventDeletion, "").tags([
@@ -976,15 +1383,13 @@ impl Nip60Wallet {
                 match item[0].as_str() {
                     "direction" => direction = item[1].clone(),
                     "amount" => amount = item[1].clone(),
-                    "e" => {
-                        if item.len() >= 4 {
-                            events.push((
-                                item[0].clone(), // "e"
-                                item[1].clone(), // event_id
-                                item[2].clone(), // relay (usually empty)
-                                item[3].clone(), // marker
-                            ));
-                        }
+                    "e" if item.len() >= 4 => {
+                        events.push((
+                            item[0].clone(), // "e"
+                            item[1].clone(), // event_id
+                            item[2].clone(), // relay (usually empty)
+                            item[3].clone(), // marker
+                        ));
                     }
                     _ => {}
                 }
@@ -1171,7 +1576,7 @@ impl Nip60Wallet {
         let mut current_amount = 0u64;
 
         let mut available_proofs = state.proofs.clone();
-        available_proofs.sort_by(|a, b| b.amount.cmp(&a.amount));
+        available_proofs.sort_by_key(|p| std::cmp::Reverse(p.amount));
 
         for proof in available_proofs {
             if current_amount < amount {
@@ -1265,6 +1670,49 @@ impl Nip60Wallet {
         amount: u64,
         target_mint: Option<String>,
         memo: Option<String>,
+    ) -> Result<String> {
+        self.send_with_options(amount, target_mint, memo, SendFeeOptions::default())
+            .await
+    }
+
+    /// The proofs and fees a send of `amount` would use, without sending
+    pub async fn preview_send(
+        &self,
+        amount: u64,
+        target_mint: Option<String>,
+        options: SendFeeOptions,
+    ) -> Result<SendPreview> {
+        let mint_url =
+            target_mint.unwrap_or_else(|| self.mints.first().cloned().unwrap_or_default());
+        let state = self.fetch_wallet_state().await?;
+        let selection = Self::select_proofs_for_send(
+            &state,
+            amount,
+            &mint_url,
+            &self.get_mint_keysets(&mint_url),
+            options,
+        )?;
+        let unit = self.determine_unit_from_proofs(&mint_url, &selection.inputs)?;
+
+        Ok(SendPreview {
+            mint_url,
+            unit,
+            amount,
+            proofs: selection.inputs,
+            swap_fee: selection.swap_fee,
+            redeem_fee: selection.redeem_fee,
+            token_amount: selection.token_amount,
+            received: selection.token_amount.saturating_sub(selection.redeem_fee),
+            change: selection.change,
+        })
+    }
+
+    pub async fn send_with_options(
+        &self,
+        amount: u64,
+        target_mint: Option<String>,
+        memo: Option<String>,
+        options: SendFeeOptions,
     ) -> Result<String> {
         let state = self.fetch_wallet_state().await?;
 
@@ -1278,20 +1726,47 @@ impl Nip60Wallet {
         let mint_url =
             target_mint.unwrap_or_else(|| self.mints.first().cloned().unwrap_or_default());
 
-        let (selected_proofs, all_consumed_proofs, spent_event_ids) = self
-            .select_proofs_for_exact_amount(amount, &mint_url)
-            .await?;
+        let selection = Self::select_proofs_for_send(
+            &state,
+            amount,
+            &mint_url,
+            &self.get_mint_keysets(&mint_url),
+            options,
+        )?;
+
+        // Proofs of the spent token events that stay in the wallet
+        let mut kept_proofs: Proofs = state
+            .proofs
+            .iter()
+            .filter(|proof| {
+                state
+                    .proof_to_event_id
+                    .get(&proof.c.to_string())
+                    .and_then(|event_id| EventId::from_hex(event_id).ok())
+                    .is_some_and(|event_id| selection.spent_event_ids.contains(&event_id))
+                    && !selection.inputs.iter().any(|input| input.c == proof.c)
+            })
+            .cloned()
+            .collect();
 
-        let token_string =
-            self.create_cashu_token_string(&mint_url, selected_proofs.clone(), memo, None)?;
+        let send_proofs = if selection.needs_swap() {
+            let client = self
+                .transport
+                .client_for(&mint_url)?
+                .with_dleq_policy(self.dleq_policy);
+            let swap = client
+                .swap(selection.inputs.clone(), &[selection.token_amount])
+                .await?;
+            kept_proofs.extend(swap.change);
+            swap.proofs
+        } else {
+            selection.inputs.clone()
+        };
 
-        let remaining_proofs: Vec<_> = all_consumed_proofs
-            .into_iter()
-            .filter(|proof| !selected_proofs.iter().any(|sp| sp.c == proof.c))
-            .collect();
+        let token_string = self.create_cashu_token_string(&mint_url, send_proofs, memo, None)?;
 
         let mut event_refs = Vec::new();
-        for event_id in &spent_event_ids {
+        for event_id in &selection.spent_event_ids {
             event_refs.push((
                 "e".to_string(),
                 event_id.to_hex(),
@@ -1300,10 +1775,28 @@ impl Nip60Wallet {
             ));
         }
 
-        if !remaining_proofs.is_empty() {
-            let new_token_event_id = self
-                .create_rollover_token_event(remaining_proofs, &spent_event_ids)
-                .await?;
+        if !kept_proofs.is_empty() {
+            let new_token_event_id = if selection.needs_swap() {
+                self.publish_swapped_proofs(&mint_url, kept_proofs, &selection.spent_event_ids)
+                    .await
+                    .map_err(|e| {
+                        crate::error::Error::custom(&format!(
+                            "{}. The token sent is {}",
+                            e, token_string
+                        ))
+                    })?
+            } else {
+                self.create_token_event(
+                    &mint_url,
+                    kept_proofs,
+                    selection
+                        .spent_event_ids
+                        .iter()
+                        .map(|id| id.to_hex())
+                        .collect(),
+                )
+                .await?
+            };
             event_refs.push((
                 "e".to_string(),
                 new_token_event_id.to_hex(),
@@ -1312,15 +1805,10 @@ impl Nip60Wallet {
             ));
         }
 
-        let total_amount: u64 = selected_proofs
-            .iter()
-            .map(|p| p.amount.to_string().parse::<u64>().unwrap_or(0))
-            .sum();
-
-        self.create_spending_history("out", total_amount, event_refs)
+        self.create_spending_history("out", selection.token_amount, event_refs)
             .await?;
 
-        for event_id in &spent_event_ids {
+        for event_id in &selection.spent_event_ids {
             self.delete_token_event(event_id).await?;
         }
 
@@ -1376,12 +1864,17 @@ impl Nip60Wallet {
             ecash_402_wallet::crypto::generate_random_secret()
         );
 
-        let temp_wallet =
-            CashuWalletClient::from_seed_with_unit(&mint_url, &temp_seed, &temp_db_name, mint_unit)
-                .await
-                .map_err(|e| {
-                    crate::error::Error::custom(&format!("Failed to create temp wallet: {}", e))
-                })?;
+        let temp_wallet = CashuWalletClient::from_seed_with_transport(
+            &mint_url,
+            &temp_seed,
+            &temp_db_name,
+            mint_unit,
+            self.transport.clone(),
+        )
+        .await
+        .map_err(|e| {
+            crate::error::Error::custom(&format!("Failed to create temp wallet: {}", e))
+        })?;
 
         temp_wallet
             .receive(token_string)
@@ -1423,130 +1916,148 @@ impl Nip60Wallet {
         Ok(redeemed_amount)
     }
 
-    async fn select_proofs_for_exact_amount(
-        &self,
+    /// Select proofs of `mint_url` for a send of `amount`, adding the fee the
+    /// receiver pays to redeem them when `options.include_fees` is set.
+    fn select_proofs_for_send(
+        state: &WalletState,
         amount: u64,
-        target_mint: &str,
-    ) -> Result<(Proofs, Proofs, Vec<EventId>)> {
-        let state = self.fetch_wallet_state().await?;
-        let available_proofs: Vec<_> = state.proofs.iter().filter(|_p| true).cloned().collect();
+        mint_url: &str,
+        keysets: &[KeysetInfo],
+        options: SendFeeOptions,
+    ) -> Result<SendSelection> {
+        // The fee depends on the proofs selected, so select until the
+        // selection covers the amount plus its own fee
+        let mut target = amount;
+        for _ in 0..MAX_FEE_SELECTION_ROUNDS {
+            let selection = Self::select_proofs(state, target, mint_url, keysets)?;
+
+            if !options.include_fees || target == amount + selection.redeem_fee {
+                return Ok(selection);
+            }
+            target = amount + selection.redeem_fee;
+        }
+
+        Err(crate::error::Error::custom(&format!(
+            "Could not select proofs covering {} plus the fee to redeem them",
+            amount
+        )))
+    }
 
-        let available_amount: u64 = available_proofs
+    /// Select proofs of `mint_url` for a token of exactly `amount`.
+    ///
+    /// Proofs adding up to `amount` are sent as they are. Otherwise the
+    /// largest proofs covering `amount` plus their input fee are selected,
+    /// to be swapped for the token and change.
+    fn select_proofs(
+        state: &WalletState,
+        amount: u64,
+        mint_url: &str,
+        keysets: &[KeysetInfo],
+    ) -> Result<SendSelection> {
+        let mut available_proofs: Proofs = state
+            .proofs
             .iter()
-            .map(|p| p.amount.to_string().parse::<u64>().unwrap_or(0))
-            .sum();
+            .filter(|proof| {
+                let keyset_id = proof.keyset_id.to_string();
+                keysets.iter().any(|keyset| keyset.id == keyset_id)
+            })
+            .cloned()
+            .collect();
+        available_proofs.sort_by_key(|p| std::cmp::Reverse(p.amount)); // largest → smallest
 
+        let available_amount: u64 = available_proofs.iter().map(|p| u64::from(p.amount)).sum();
         if available_amount < amount {
             return Err(crate::error::Error::custom(&format!(
                 "Insufficient balance in target mint {}: need {}, have {}",
-                target_mint, amount, available_amount
+                mint_url, amount, available_amount
             )));
         }
 
-        let mut selected_proofs = Vec::new();
-        let mut selected_total = 0u64;
-        let mut spent_event_ids = HashSet::new();
-
-        let mut sorted_proofs = available_proofs.clone();
-        sorted_proofs.sort_by(|a, b| b.amount.cmp(&a.amount)); // largest → smallest
-
+        let mut exact_proofs = Vec::new();
         let mut remaining = amount;
-        for proof in &sorted_proofs {
+        for proof in &available_proofs {
             if remaining == 0 {
                 break;
             }
 
-            let value = proof.amount.to_string().parse::<u64>().unwrap_or(0);
-            if value == 0 || value > remaining {
-                continue; // skip anything that would overshoot
+            let value = u64::from(proof.amount);
+            if value <= remaining {
+                exact_proofs.push(proof.clone());
+                remaining -= value;
             }
-
-            selected_proofs.push(proof.clone());
-            selected_total += value;
-            remaining -= value;
-
-            if let Some(event_id_str) = state.proof_to_event_id.get(&proof.c.to_string()) {
-                if let Ok(event_id) = EventId::from_hex(event_id_str) {
-                    spent_event_ids.insert(event_id);
-                }
-            }
-        }
-
-        if remaining != 0 {
-            return Err(crate::error::Error::custom(&format!(
-                "Could not find an exact combination of proofs to cover {} sats ({} sats missing)",
-                amount, remaining
-            )));
-        }
-
-        if selected_total < amount {
-            return Err(crate::error::Error::custom(&format!(
-                "Could not select sufficient proofs: need {}, selected {}",
-                amount, selected_total
-            )));
         }
 
-        if selected_total == amount {
-            let spent_event_ids: Vec<EventId> = spent_event_ids.into_iter().collect();
-            return Ok((selected_proofs.clone(), selected_proofs, spent_event_ids));
+        if remaining == 0 {
+            return Ok(SendSelection {
+                redeem_fee: MintClient::calculate_input_fee(&exact_proofs, keysets),
+                spent_event_ids: Self::event_ids_of(state, &exact_proofs),
+                inputs: exact_proofs,
+                token_amount: amount,
+                swap_fee: 0,
+                change: 0,
+            });
         }
 
-        let change_amount = selected_total - amount;
-        let (send_proofs, change_proofs) = self
-            .split_proofs_for_amounts(selected_proofs, amount, change_amount, target_mint)
-            .await?;
-
-        if !change_proofs.is_empty() {
-            self.create_token_event(target_mint, change_proofs, vec![])
-                .await?;
+        let mut inputs = Vec::new();
+        let mut input_total = 0u64;
+        for proof in &available_proofs {
+            inputs.push(proof.clone());
+            input_total += u64::from(proof.amount);
+
+            let swap_fee = MintClient::calculate_input_fee(&inputs, keysets);
+            if input_total >= amount + swap_fee {
+                return Ok(SendSelection {
+                    redeem_fee: Self::swapped_token_fee(amount, &inputs, keysets),
+                    spent_event_ids: Self::event_ids_of(state, &inputs),
+                    inputs,
+                    token_amount: amount,
+                    swap_fee,
+                    change: input_total - amount - swap_fee,
+                });
+            }
         }
 
-        let spent_event_ids: Vec<EventId> = spent_event_ids.into_iter().collect();
-        Ok((send_proofs, available_proofs, spent_event_ids))
+        Err(crate::error::Error::custom(&format!(
+            "Insufficient balance in target mint {} to cover {} plus the swap fee: have {}",
+            mint_url, amount, available_amount
+        )))
     }
 
-    async fn split_proofs_for_amounts(
-        &self,
-        input_proofs: Proofs,
-        send_amount: u64,
-        change_amount: u64,
-        _mint_url: &str,
-    ) -> Result<(Proofs, Proofs)> {
-        let total_input: u64 = input_proofs
+    /// Fee to redeem a token of `amount` swapped from `inputs`, whose proofs
+    /// are split into powers of two on the active keyset of their unit
+    fn swapped_token_fee(amount: u64, inputs: &Proofs, keysets: &[KeysetInfo]) -> u64 {
+        let unit = inputs.first().and_then(|proof| {
+            let keyset_id = proof.keyset_id.to_string();
+            keysets
+                .iter()
+                .find(|keyset| keyset.id == keyset_id)
+                .map(|keyset| keyset.unit.clone())
+        });
+        let fee_ppk = keysets
             .iter()
-            .map(|p| p.amount.to_string().parse::<u64>().unwrap_or(0))
-            .sum();
-
-        if total_input != send_amount + change_amount {
-            return Err(crate::error::Error::custom(&format!(
-                "Amount mismatch: input={}, send={}, change={}",
-                total_input, send_amount, change_amount
-            )));
-        }
-
-        let _send_denoms = self.calculate_optimal_denominations(send_amount);
-        let _change_denoms = self.calculate_optimal_denominations(change_amount);
+            .filter(|keyset| keyset.active && Some(&keyset.unit) == unit.as_ref())
+            .map(|keyset| keyset.input_fee_ppk)
+            .min()
+            .unwrap_or(0);
 
-        Ok((input_proofs, Vec::new()))
+        (u64::from(amount.count_ones()) * fee_ppk).div_ceil(1000)
     }
 
-    fn calculate_optimal_denominations(&self, amount: u64) -> std::collections::HashMap<u64, u32> {
-        let mut denominations = std::collections::HashMap::new();
-        let mut remaining = amount;
-
-        let denoms = [
-            16384, 8192, 4096, 2048, 1024, 512, 256, 128, 64, 32, 16, 8, 4, 2, 1,
-        ];
-
-        for &denom in &denoms {
-            if remaining >= denom {
-                let count = remaining / denom;
-                denominations.insert(denom, count as u32);
-                remaining -= denom * count;
+    /// The token events holding `proofs`
+    fn event_ids_of(state: &WalletState, proofs: &Proofs) -> Vec<EventId> {
+        let mut event_ids = Vec::new();
+        for proof in proofs {
+            if let Some(event_id) = state
+                .proof_to_event_id
+                .get(&proof.c.to_string())
+                .and_then(|event_id| EventId::from_hex(event_id).ok())
+            {
+                if !event_ids.contains(&event_id) {
+                    event_ids.push(event_id);
+                }
             }
         }
-
-        denominations
+        event_ids
     }
 
     pub async fn get_event_history_by_mint(
@@ -1600,7 +2111,9 @@ impl Nip60Wallet {
         }
 
         for history in history_by_mint.values_mut() {
-            history.events.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+            history
+                .events
+                .sort_by_key(|e| std::cmp::Reverse(e.timestamp));
         }
 
         Ok(history_by_mint.into_values().collect())
@@ -1625,7 +2138,7 @@ impl Nip60Wallet {
         }
 
         let mut result: Vec<_> = breakdowns.into_values().collect();
-        result.sort_by(|a, b| b.total_balance.cmp(&a.total_balance));
+        result.sort_by_key(|b| std::cmp::Reverse(b.total_balance));
         result
     }
 
@@ -1689,3 +2202,184 @@ impl Nip60Wallet {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cdk::nuts::{Id, Proof, SecretKey};
+    use cdk::secret::Secret;
+    use cdk::Amount;
+
+    const MINT_A: &str = "https://mint-a.example";
+    const KEYSET_A: &str = "009a1f293253e41e";
+    const KEYSET_B: &str = "00ad268c4d1f5826";
+
+    fn keyset(id: &str, input_fee_ppk: u64) -> KeysetInfo {
+        KeysetInfo {
+            id: id.to_string(),
+            unit: "sat".to_string(),
+            active: true,
+            input_fee_ppk,
+        }
+    }
+
+    fn proof(keyset_id: &str, amount: u64) -> Proof {
+        Proof {
+            amount: Amount::from(amount),
+            keyset_id: Id::from_str(keyset_id).unwrap(),
+            secret: Secret::generate(),
+            c: SecretKey::generate().public_key(),
+            witness: None,
+            dleq: None,
+        }
+    }
+
+    /// A wallet state with each group of proofs in its own token event
+    fn state(events: Vec<Proofs>) -> WalletState {
+        let mut state = WalletState {
+            balance: 0,
+            proofs: Vec::new(),
+            proof_to_event_id: HashMap::new(),
+            mint_keysets: HashMap::new(),
+        };
+        for (i, proofs) in events.into_iter().enumerate() {
+            let event_id = EventId::from_slice(&[i as u8 + 1; 32]).unwrap().to_hex();
+            for proof in proofs {
+                state.balance += u64::from(proof.amount);
+                state
+                    .proof_to_event_id
+                    .insert(proof.c.to_string(), event_id.clone());
+                state.proofs.push(proof);
+            }
+        }
+        state
+    }
+
+    fn total(proofs: &Proofs) -> u64 {
+        proofs.iter().map(|p| u64::from(p.amount)).sum()
+    }
+
+    #[test]
+    fn selects_only_proofs_of_the_target_mint() {
+        let state = state(vec![
+            vec![proof(KEYSET_B, 8), proof(KEYSET_B, 4)],
+            vec![proof(KEYSET_A, 2), proof(KEYSET_A, 1)],
+        ]);
+
+        let selection =
+            Nip60Wallet::select_proofs(&state, 3, MINT_A, &[keyset(KEYSET_A, 0)]).unwrap();
+        assert_eq!(total(&selection.inputs), 3);
+        assert!(selection
+            .inputs
+            .iter()
+            .all(|p| p.keyset_id.to_string() == KEYSET_A));
+        assert_eq!(selection.spent_event_ids.len(), 1);
+
+        assert!(Nip60Wallet::select_proofs(&state, 4, MINT_A, &[keyset(KEYSET_A, 0)]).is_err());
+    }
+
+    #[test]
+    fn exact_selection_needs_no_swap() {
+        let state = state(vec![vec![proof(KEYSET_A, 8), proof(KEYSET_A, 2)]]);
+
+        let selection =
+            Nip60Wallet::select_proofs(&state, 10, MINT_A, &[keyset(KEYSET_A, 0)]).unwrap();
+        assert!(!selection.needs_swap());
+        assert_eq!(selection.swap_fee, 0);
+        assert_eq!(selection.change, 0);
+    }
+
+    #[test]
+    fn inexact_selection_is_swapped_with_fee_and_change() {
+        let state = state(vec![vec![proof(KEYSET_A, 16), proof(KEYSET_A, 4)]]);
+
+        let selection =
+            Nip60Wallet::select_proofs(&state, 10, MINT_A, &[keyset(KEYSET_A, 1000)]).unwrap();
+        assert!(selection.needs_swap());
+        assert_eq!(total(&selection.inputs), 16);
+        assert_eq!(selection.swap_fee, 1);
+        assert_eq!(selection.change, 5);
+        // 10 is sent as 8 + 2
+        assert_eq!(selection.redeem_fee, 2);
+    }
+
+    #[test]
+    fn swap_fails_when_the_fee_cannot_be_covered() {
+        let state = state(vec![vec![proof(KEYSET_A, 8), proof(KEYSET_A, 8)]]);
+
+        assert!(Nip60Wallet::select_proofs(&state, 15, MINT_A, &[keyset(KEYSET_A, 1000)]).is_err());
+    }
+
+    #[test]
+    fn include_fees_adds_the_redeem_fee_to_the_token() {
+        let state = state(vec![vec![
+            proof(KEYSET_A, 8),
+            proof(KEYSET_A, 2),
+            proof(KEYSET_A, 1),
+        ]]);
+        let options = SendFeeOptions { include_fees: true };
+
+        let selection = Nip60Wallet::select_proofs_for_send(
+            &state,
+            9,
+            MINT_A,
+            &[keyset(KEYSET_A, 500)],
+            options,
+        )
+        .unwrap();
+        assert_eq!(selection.token_amount, 10);
+        assert_eq!(selection.redeem_fee, 1);
+        assert!(!selection.needs_swap());
+    }
+
+    fn inactive(id: &str) -> KeysetInfo {
+        KeysetInfo {
+            active: false,
+            ..keyset(id, 0)
+        }
+    }
+
+    #[test]
+    fn migration_keeps_proofs_sharing_an_event_with_stale_ones() {
+        let mixed = vec![proof(KEYSET_B, 8), proof(KEYSET_A, 4), proof(KEYSET_A, 2)];
+        let untouched = vec![proof(KEYSET_A, 1)];
+        let state = state(vec![mixed.clone(), untouched.clone()]);
+
+        let plan =
+            Nip60Wallet::plan_keyset_migration(&state, &[keyset(KEYSET_A, 0), inactive(KEYSET_B)])
+                .unwrap();
+
+        assert_eq!(plan.stale_proofs, vec![mixed[0].clone()]);
+        assert_eq!(plan.kept_proofs, vec![mixed[1].clone(), mixed[2].clone()]);
+        assert_eq!(plan.inactive_keysets, vec![KEYSET_B.to_string()]);
+        assert_eq!(
+            plan.spent_event_ids,
+            vec![EventId::from_slice(&[1; 32]).unwrap()]
+        );
+        assert!(!plan.kept_proofs.contains(&untouched[0]));
+    }
+
+    #[test]
+    fn migration_replaces_every_event_holding_stale_proofs() {
+        let state = state(vec![
+            vec![proof(KEYSET_B, 8)],
+            vec![proof(KEYSET_B, 4), proof(KEYSET_A, 2)],
+            vec![proof(KEYSET_A, 1)],
+        ]);
+
+        let plan =
+            Nip60Wallet::plan_keyset_migration(&state, &[keyset(KEYSET_A, 0), inactive(KEYSET_B)])
+                .unwrap();
+
+        assert_eq!(total(&plan.stale_proofs), 12);
+        assert_eq!(total(&plan.kept_proofs), 2);
+        assert_eq!(plan.spent_event_ids.len(), 2);
+    }
+
+    #[test]
+    fn migration_is_skipped_without_stale_proofs() {
+        let state = state(vec![vec![proof(KEYSET_A, 4)], vec![proof(KEYSET_B, 2)]]);
+
+        assert!(Nip60Wallet::plan_keyset_migration(&state, &[keyset(KEYSET_A, 0)]).is_none());
+    }
+}
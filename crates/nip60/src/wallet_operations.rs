@@ -1,7 +1,10 @@
-use crate::error::Result;
+use crate::error::{Error, Result};
 use crate::nip60::Nip60Wallet;
+use cdk::nuts::CurrencyUnit;
+use ecash_402_wallet::price::{self, PriceOracle, StaticPriceOracle};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::str::FromStr;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MintInfo {
@@ -24,6 +27,14 @@ pub struct KeysetDisplayInfo {
 pub struct WalletBalance {
     pub total_sats: u64,
     pub total_msats: u64,
+    /// Unit `display_total` is reported in
+    pub display_unit: String,
+    pub display_total: u64,
+    pub display_total_formatted: String,
+    /// Units whose price was missing, leaving some balances out of the totals
+    #[serde(default)]
+    pub unpriced_units: Vec<String>,
+    /// One entry per mint and unit, in the mint's own unit
     pub by_mint: Vec<MintBalance>,
 }
 
@@ -80,34 +91,37 @@ impl WalletOperations {
 
     /// Get wallet balance with proper unit handling
     pub async fn get_detailed_balance(wallet: &Nip60Wallet) -> Result<WalletBalance> {
+        Self::get_detailed_balance_in(wallet, "sat", None).await
+    }
+
+    /// Get wallet balance with totals in `display_unit`.
+    ///
+    /// Balances in units other than sat and msat, such as usd or eur, are
+    /// converted with `oracle`. Without a price for their unit they are still
+    /// listed per mint but left out of the totals and their unit is named in
+    /// [`WalletBalance::unpriced_units`]. Without a price for a fiat
+    /// `display_unit`, sat and msat balances still count towards `total_sats`
+    /// and `display_unit` itself is named instead.
+    pub async fn get_detailed_balance_in(
+        wallet: &Nip60Wallet,
+        display_unit: &str,
+        oracle: Option<&dyn PriceOracle>,
+    ) -> Result<WalletBalance> {
         let proofs = wallet.get_unspent_proofs().await?;
-        let mut by_mint: HashMap<String, MintBalance> = HashMap::new();
-        let mut total_sats = 0u64;
-        let mut total_msats = 0u64;
+        let mut by_mint: HashMap<(String, String), MintBalance> = HashMap::new();
 
         for proof in &proofs {
-            let mint_url = &proof.keyset_id.to_string();
+            let keyset_id = proof.keyset_id.to_string();
 
-            let actual_mint_url =
-                Self::resolve_mint_url_from_keyset(wallet, &proof.keyset_id.to_string())
-                    .unwrap_or_else(|| mint_url.clone());
+            let actual_mint_url = Self::resolve_mint_url_from_keyset(wallet, &keyset_id)
+                .unwrap_or_else(|| keyset_id.clone());
 
-            let unit = Self::get_unit_for_mint(wallet, &actual_mint_url);
+            let unit = Self::get_unit_for_keyset(wallet, &keyset_id)
+                .unwrap_or_else(|| Self::get_unit_for_mint(wallet, &actual_mint_url));
             let amount = proof.amount.to_string().parse::<u64>().unwrap_or(0);
 
-            match unit.as_str() {
-                "msat" => {
-                    total_msats += amount;
-                    total_sats += Self::msat_to_sat(amount);
-                }
-                _ => {
-                    total_sats += amount;
-                    total_msats += Self::sat_to_msat(amount);
-                }
-            }
-
             let entry = by_mint
-                .entry(actual_mint_url.clone())
+                .entry((actual_mint_url.clone(), unit.clone()))
                 .or_insert_with(|| MintBalance {
                     mint_url: actual_mint_url.clone(),
                     unit: unit.clone(),
@@ -120,16 +134,78 @@ impl WalletOperations {
             entry.proof_count += 1;
         }
 
-        for mint_balance in by_mint.values_mut() {
+        Ok(Self::total_balances(
+            by_mint.into_values().collect(),
+            display_unit,
+            oracle,
+        ))
+    }
+
+    /// Total per-mint balances in msat and `display_unit`
+    fn total_balances(
+        mut by_mint: Vec<MintBalance>,
+        display_unit: &str,
+        oracle: Option<&dyn PriceOracle>,
+    ) -> WalletBalance {
+        let mut total_msats = 0u64;
+        let mut display_total = 0u64;
+        let mut unpriced_units = Vec::new();
+
+        for mint_balance in by_mint.iter_mut() {
             mint_balance.amount_display =
                 Self::format_amount(mint_balance.amount, &mint_balance.unit);
+
+            let amount = mint_balance.amount;
+            let unit = &mint_balance.unit;
+            // sat and msat convert to each other exactly, so a missing fiat
+            // price only drops the balance from the total it is needed for
+            let msats = Self::convert_amount(amount, unit, "msat", oracle);
+            let display = Self::convert_amount(amount, unit, display_unit, oracle);
+            let unpriced = match (&msats, &display) {
+                (Err(_), _) => Some(unit.as_str()),
+                (Ok(_), Err(_)) => Some(display_unit),
+                (Ok(_), Ok(_)) => None,
+            };
+            total_msats += msats.unwrap_or(0);
+            display_total += display.unwrap_or(0);
+            if let Some(unit) = unpriced {
+                if !unpriced_units.iter().any(|listed| listed == unit) {
+                    unpriced_units.push(unit.to_string());
+                }
+            }
         }
 
-        Ok(WalletBalance {
-            total_sats,
+        WalletBalance {
+            total_sats: Self::msat_to_sat(total_msats),
             total_msats,
-            by_mint: by_mint.into_values().collect(),
-        })
+            display_unit: display_unit.to_string(),
+            display_total,
+            display_total_formatted: Self::format_amount(display_total, display_unit),
+            unpriced_units,
+            by_mint,
+        }
+    }
+
+    /// Convert `amount` between units, using `oracle` for fiat units
+    pub fn convert_amount(
+        amount: u64,
+        from: &str,
+        to: &str,
+        oracle: Option<&dyn PriceOracle>,
+    ) -> Result<u64> {
+        let from = Self::parse_unit(from)?;
+        let to = Self::parse_unit(to)?;
+        match oracle {
+            Some(oracle) => Ok(price::convert(oracle, amount, &from, &to)?),
+            None => price::convert(&StaticPriceOracle::new(), amount, &from, &to).map_err(|_| {
+                Error::custom(&format!("No price oracle to convert {} to {}", from, to))
+            }),
+        }
+    }
+
+    fn parse_unit(unit: &str) -> Result<CurrencyUnit> {
+        CurrencyUnit::from_str(unit)
+            .map_err(|e| Error::custom(&format!("Invalid unit {}: {}", unit, e)))
     }
 
     pub fn get_mint_info_display(wallet: &Nip60Wallet) -> Vec<MintInfo> {
@@ -233,6 +309,15 @@ impl WalletOperations {
         "sat".to_string()
     }
 
+    fn get_unit_for_keyset(wallet: &Nip60Wallet, keyset_id: &str) -> Option<String> {
+        wallet
+            .get_all_mint_infos()
+            .into_iter()
+            .flat_map(|mint_info| mint_info.keysets.iter())
+            .find(|keyset| keyset.id == keyset_id)
+            .map(|keyset| keyset.unit.clone())
+    }
+
     fn resolve_mint_url_from_keyset(wallet: &Nip60Wallet, keyset_id: &str) -> Option<String> {
         for mint_info in wallet.get_all_mint_infos() {
             for keyset in &mint_info.keysets {
@@ -287,3 +372,64 @@ pub struct DetailedWalletStats {
     pub token_events: usize,
     pub mint_infos: Vec<MintInfo>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn balance(unit: &str, amount: u64) -> MintBalance {
+        MintBalance {
+            mint_url: format!("https://{}.example", unit),
+            unit: unit.to_string(),
+            amount,
+            amount_display: String::new(),
+            proof_count: 1,
+        }
+    }
+
+    #[test]
+    fn sat_and_msat_are_totalled_without_a_price_for_the_display_unit() {
+        let total = WalletOperations::total_balances(
+            vec![balance("sat", 21), balance("msat", 2_000)],
+            "usd",
+            None,
+        );
+
+        assert_eq!(total.total_sats, 23);
+        assert_eq!(total.total_msats, 23_000);
+        assert_eq!(total.display_total, 0);
+        assert_eq!(total.unpriced_units, vec!["usd".to_string()]);
+    }
+
+    #[test]
+    fn fiat_balances_are_left_out_of_the_totals_without_a_price() {
+        let total = WalletOperations::total_balances(
+            vec![balance("sat", 100_000), balance("usd", 650)],
+            "sat",
+            None,
+        );
+
+        assert_eq!(total.total_sats, 100_000);
+        assert_eq!(total.display_total, 100_000);
+        assert_eq!(total.unpriced_units, vec!["usd".to_string()]);
+        assert_eq!(total.by_mint.len(), 2);
+    }
+
+    #[test]
+    fn fiat_balances_are_converted_and_rounded_with_a_price() {
+        // 65,000 usd per bitcoin, in cents
+        let oracle = StaticPriceOracle::new().with_price(CurrencyUnit::Usd, 6_500_000.0);
+        let total = WalletOperations::total_balances(
+            vec![balance("msat", 1_500), balance("usd", 650)],
+            "usd",
+            Some(&oracle),
+        );
+
+        assert_eq!(total.total_msats, 10_001_500);
+        assert_eq!(total.total_sats, 10_001);
+        // 1,500 msat is 0.0975 cents
+        assert_eq!(total.display_total, 650);
+        assert_eq!(total.display_total_formatted, "$6.50");
+        assert!(total.unpriced_units.is_empty());
+    }
+}
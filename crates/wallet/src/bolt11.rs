@@ -0,0 +1,241 @@
+use crate::error::{Error, Result};
+use cdk::lightning_invoice::Bolt11InvoiceDescriptionRef;
+use cdk::nuts::CurrencyUnit;
+use cdk::Bolt11Invoice;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The fields of a BOLT11 invoice the wallet cares about
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DecodedInvoice {
+    pub payment_request: String,
+    /// `None` for amountless invoices
+    pub amount_msat: Option<u64>,
+    pub description: Option<String>,
+    pub description_hash: Option<String>,
+    /// Hex-encoded node public key of the payee
+    pub payee: String,
+    pub payment_hash: String,
+    /// Unix time the invoice was created
+    pub timestamp: u64,
+    /// Seconds after `timestamp` until the invoice expires
+    pub expiry_secs: u64,
+    /// `bitcoin`, `testnet`, `regtest`, `signet` or `simnet`
+    pub network: String,
+}
+
+impl DecodedInvoice {
+    /// Decode and check the signature of a BOLT11 invoice, with or without a
+    /// `lightning:` prefix
+    pub fn parse(payment_request: &str) -> Result<Self> {
+        let trimmed = payment_request.trim();
+        let trimmed = trimmed
+            .strip_prefix("lightning:")
+            .or_else(|| trimmed.strip_prefix("LIGHTNING:"))
+            .unwrap_or(trimmed);
+
+        let invoice = Bolt11Invoice::from_str(trimmed)
+            .map_err(|e| Error::custom(&format!("Invalid BOLT11 invoice: {}", e)))?;
+
+        let (description, description_hash) = match invoice.description() {
+            Bolt11InvoiceDescriptionRef::Direct(description) => {
+                (Some(description.to_string()), None)
+            }
+            Bolt11InvoiceDescriptionRef::Hash(hash) => (None, Some(hash.0.to_string())),
+        };
+
+        let network = match invoice.currency() {
+            cdk::lightning_invoice::Currency::Bitcoin => "bitcoin",
+            cdk::lightning_invoice::Currency::BitcoinTestnet => "testnet",
+            cdk::lightning_invoice::Currency::Regtest => "regtest",
+            cdk::lightning_invoice::Currency::Simnet => "simnet",
+            cdk::lightning_invoice::Currency::Signet => "signet",
+        };
+
+        Ok(Self {
+            payment_request: trimmed.to_string(),
+            amount_msat: invoice.amount_milli_satoshis(),
+            description,
+            description_hash,
+            payee: invoice.get_payee_pub_key().to_string(),
+            payment_hash: invoice.payment_hash().to_string(),
+            timestamp: invoice.duration_since_epoch().as_secs(),
+            expiry_secs: invoice.expiry_time().as_secs(),
+            network: network.to_string(),
+        })
+    }
+
+    /// Unix time the invoice expires
+    pub fn expires_at(&self) -> u64 {
+        self.timestamp.saturating_add(self.expiry_secs)
+    }
+
+    pub fn is_expired(&self) -> bool {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        now >= self.expires_at()
+    }
+
+    /// The invoice amount in `unit`, rounded up to whole sats.
+    ///
+    /// `None` for amountless invoices and units other than sat and msat.
+    pub fn amount_in(&self, unit: &CurrencyUnit) -> Option<u64> {
+        let amount_msat = self.amount_msat?;
+        match unit {
+            CurrencyUnit::Msat => Some(amount_msat),
+            CurrencyUnit::Sat => Some(amount_msat.div_ceil(1000)),
+            _ => None,
+        }
+    }
+
+    /// The amount to pay in msat: the invoice amount, or `explicit_msat` for
+    /// amountless invoices. An explicit amount must match the invoice amount
+    /// when the invoice has one.
+    pub fn payable_amount_msat(&self, explicit_msat: Option<u64>) -> Result<u64> {
+        match (self.amount_msat, explicit_msat) {
+            (Some(amount), None) => Ok(amount),
+            (Some(amount), Some(explicit)) if amount == explicit => Ok(amount),
+            (Some(amount), Some(explicit)) => Err(Error::custom(&format!(
+                "Invoice is for {} msat, not {} msat",
+                amount, explicit
+            ))),
+            (None, Some(explicit)) if explicit > 0 => Ok(explicit),
+            (None, _) => Err(Error::custom(
+                "Invoice has no amount; an explicit amount is required",
+            )),
+        }
+    }
+
+    /// Check that an invoice issued for a topup of `amount` `unit` is for that amount
+    pub fn ensure_amount(&self, amount: u64, unit: &CurrencyUnit) -> Result<()> {
+        let expected_msat = match unit {
+            CurrencyUnit::Msat => amount,
+            CurrencyUnit::Sat => amount.saturating_mul(1000),
+            // Fiat quotes are priced by the mint, nothing to compare against
+            _ => return Ok(()),
+        };
+
+        match self.amount_msat {
+            Some(amount_msat) if amount_msat == expected_msat => Ok(()),
+            Some(amount_msat) => Err(Error::custom(&format!(
+                "Mint invoice is for {} msat but {} {} was requested",
+                amount_msat, amount, unit
+            ))),
+            None => Err(Error::custom("Mint returned an invoice without an amount")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::hashes::{sha256, Hash};
+    use bitcoin::secp256k1::{Secp256k1, SecretKey};
+    use cdk::lightning_invoice::{Currency, InvoiceBuilder, PaymentSecret};
+    use std::time::Duration;
+
+    /// 2023-11-14, long enough ago for any expiry used here
+    const CREATED_AT: u64 = 1_700_000_000;
+
+    /// A signed invoice with a fixed payee key, creation time and expiry
+    fn invoice(amount_msat: Option<u64>, created_at: u64, expiry_secs: u64) -> String {
+        let key = SecretKey::from_slice(&[0x11; 32]).unwrap();
+        let builder = InvoiceBuilder::new(Currency::Regtest)
+            .description("coffee".to_string())
+            .payment_hash(sha256::Hash::hash(b"preimage"))
+            .payment_secret(PaymentSecret([7; 32]))
+            .duration_since_epoch(Duration::from_secs(created_at))
+            .expiry_time(Duration::from_secs(expiry_secs))
+            .min_final_cltv_expiry_delta(144);
+        match amount_msat {
+            Some(amount_msat) => builder
+                .amount_milli_satoshis(amount_msat)
+                .build_signed(|hash| Secp256k1::new().sign_ecdsa_recoverable(hash, &key)),
+            None => {
+                builder.build_signed(|hash| Secp256k1::new().sign_ecdsa_recoverable(hash, &key))
+            }
+        }
+        .unwrap()
+        .to_string()
+    }
+
+    fn now() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    }
+
+    #[test]
+    fn parses_fields_with_or_without_prefix() {
+        let payment_request = invoice(Some(21_000), CREATED_AT, 600);
+        let decoded = DecodedInvoice::parse(&format!("lightning:{}", payment_request)).unwrap();
+
+        assert_eq!(decoded.payment_request, payment_request);
+        assert_eq!(decoded.amount_msat, Some(21_000));
+        assert_eq!(decoded.description.as_deref(), Some("coffee"));
+        assert_eq!(decoded.description_hash, None);
+        assert_eq!(decoded.network, "regtest");
+        assert_eq!(decoded.timestamp, CREATED_AT);
+        assert_eq!(decoded.expires_at(), CREATED_AT + 600);
+        assert_eq!(
+            decoded.payment_hash,
+            sha256::Hash::hash(b"preimage").to_string()
+        );
+        assert_eq!(DecodedInvoice::parse(&payment_request).unwrap(), decoded);
+
+        assert!(DecodedInvoice::parse("lnbcrt1notaninvoice").is_err());
+    }
+
+    #[test]
+    fn expiry_is_measured_from_creation() {
+        let expired = DecodedInvoice::parse(&invoice(Some(1_000), CREATED_AT, 3600)).unwrap();
+        assert!(expired.is_expired());
+
+        let fresh = DecodedInvoice::parse(&invoice(Some(1_000), now(), 3600)).unwrap();
+        assert!(!fresh.is_expired());
+    }
+
+    #[test]
+    fn sat_amounts_round_up_partial_sats() {
+        let decoded = DecodedInvoice::parse(&invoice(Some(1_500), CREATED_AT, 600)).unwrap();
+
+        assert_eq!(decoded.amount_in(&CurrencyUnit::Msat), Some(1_500));
+        assert_eq!(decoded.amount_in(&CurrencyUnit::Sat), Some(2));
+        assert_eq!(decoded.amount_in(&CurrencyUnit::Usd), None);
+
+        assert!(decoded.ensure_amount(1_500, &CurrencyUnit::Msat).is_ok());
+        // Rounded up, 2 sat is more than the invoice asks for
+        assert!(decoded.ensure_amount(2, &CurrencyUnit::Sat).is_err());
+    }
+
+    #[test]
+    fn amountless_invoices_need_an_explicit_amount() {
+        let decoded = DecodedInvoice::parse(&invoice(None, CREATED_AT, 600)).unwrap();
+
+        assert_eq!(decoded.amount_msat, None);
+        assert_eq!(decoded.amount_in(&CurrencyUnit::Sat), None);
+        assert!(decoded.payable_amount_msat(None).is_err());
+        assert!(decoded.payable_amount_msat(Some(0)).is_err());
+        assert_eq!(decoded.payable_amount_msat(Some(5_000)).unwrap(), 5_000);
+        assert!(decoded.ensure_amount(5, &CurrencyUnit::Sat).is_err());
+    }
+
+    #[test]
+    fn mismatched_amounts_are_rejected() {
+        let decoded = DecodedInvoice::parse(&invoice(Some(10_000), CREATED_AT, 600)).unwrap();
+
+        assert_eq!(decoded.payable_amount_msat(None).unwrap(), 10_000);
+        assert_eq!(decoded.payable_amount_msat(Some(10_000)).unwrap(), 10_000);
+        assert!(decoded.payable_amount_msat(Some(9_999)).is_err());
+
+        assert!(decoded.ensure_amount(10, &CurrencyUnit::Sat).is_ok());
+        assert!(decoded.ensure_amount(11, &CurrencyUnit::Sat).is_err());
+        assert!(decoded.ensure_amount(10, &CurrencyUnit::Msat).is_err());
+        // Fiat topups are priced by the mint
+        assert!(decoded.ensure_amount(1, &CurrencyUnit::Usd).is_ok());
+    }
+}
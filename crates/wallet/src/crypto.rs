@@ -1,82 +1,281 @@
 use crate::error::{Error, Result};
-use cdk::nuts::{BlindedMessage, Id};
+use cdk::dhke::{hash_to_curve, unblind_message};
+use cdk::nuts::nut12::ProofDleq;
+use cdk::nuts::{
+    BlindSignature, BlindedMessage, Id, Keys, PreMint, PreMintSecrets, Proof, Proofs, SecretKey,
+};
+use cdk::secret::Secret;
 use hex;
 use rand::RngCore;
-use secp256k1::{Secp256k1, SecretKey};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::str::FromStr;
 
-/// Calculate optimal denominations for a given amount
-pub fn calculate_optimal_denominations(amount: u64) -> HashMap<u64, u32> {
-    let mut denominations = HashMap::new();
-    let mut remaining = amount;
+/// The amounts a keyset has keys for, smallest first
+pub fn keyset_denominations(keys: &Keys) -> Vec<u64> {
+    keys.iter().map(|(amount, _)| u64::from(*amount)).collect()
+}
 
-    let denoms = [
-        16384, 8192, 4096, 2048, 1024, 512, 256, 128, 64, 32, 16, 8, 4, 2, 1,
-    ];
+/// Calculate optimal denominations for a given amount from the amounts a
+/// keyset signs
+pub fn calculate_optimal_denominations(
+    amount: u64,
+    denominations: &[u64],
+) -> Result<HashMap<u64, u32>> {
+    let mut sorted: Vec<u64> = denominations
+        .iter()
+        .copied()
+        .filter(|&denom| denom > 0)
+        .collect();
+    sorted.sort_unstable_by_key(|&denom| std::cmp::Reverse(denom));
+    sorted.dedup();
+
+    let mut result = HashMap::new();
+    let mut remaining = amount;
 
-    for &denom in &denoms {
+    for denom in sorted {
         if remaining >= denom {
             let count = remaining / denom;
-            denominations.insert(denom, count as u32);
+            result.insert(denom, count as u32);
             remaining -= denom * count;
         }
     }
 
-    denominations
+    if remaining > 0 {
+        return Err(Error::custom(&format!(
+            "Amount {} cannot be made from keyset amounts",
+            amount
+        )));
+    }
+
+    Ok(result)
 }
 
-/// Create blinded messages for a given amount using optimal denominations
+/// Create blinded outputs for a given amount using optimal denominations.
+///
+/// The returned premint secrets keep the secret and blinding factor of every
+/// output so the mint's signatures can later be unblinded with
+/// [`unblind_signatures`].
 pub fn create_blinded_messages_for_amount(
     amount: u64,
     keyset_id: &str,
-) -> Result<Vec<BlindedMessage>> {
-    let mut outputs = Vec::new();
+    denominations: &[u64],
+) -> Result<PreMintSecrets> {
+    let keyset_id =
+        Id::from_str(keyset_id).map_err(|e| Error::custom(&format!("Invalid keyset ID: {}", e)))?;
+
+    create_blinded_messages_for_amounts(&split_amount(amount, denominations)?, keyset_id)
+}
+
+/// Split an amount into its optimal denominations, smallest first
+pub fn split_amount(amount: u64, denominations: &[u64]) -> Result<Vec<u64>> {
+    let mut amounts = Vec::new();
+    for (denom, count) in calculate_optimal_denominations(amount, denominations)? {
+        amounts.extend(std::iter::repeat_n(denom, count as usize));
+    }
+    amounts.sort_unstable();
+    Ok(amounts)
+}
+
+/// Create one blinded output with a fresh random secret per requested amount
+pub fn create_blinded_messages_for_amounts(
+    amounts: &[u64],
+    keyset_id: Id,
+) -> Result<PreMintSecrets> {
+    let mut premint = PreMintSecrets::new(keyset_id);
+
+    for &amount in amounts {
+        premint
+            .secrets
+            .push(blind_secret(Secret::generate(), amount, keyset_id, None)?);
+    }
+
+    Ok(premint)
+}
+
+/// Blind a secret for the mint (NUT-00).
+///
+/// Computes `Y = hash_to_curve(secret)` and `B_ = Y + rG`. A random blinding
+/// factor `r` is generated when none is given.
+pub fn blind_secret(
+    secret: Secret,
+    amount: u64,
+    keyset_id: Id,
+    blinding_factor: Option<SecretKey>,
+) -> Result<PreMint> {
+    let y = hash_to_curve(&secret.to_bytes())
+        .map_err(|e| Error::custom(&format!("Failed to hash secret to curve: {}", e)))?;
+    let r = blinding_factor.unwrap_or_else(SecretKey::generate);
 
-    let denominations = calculate_optimal_denominations(amount);
+    let blinded_secret = y
+        .combine(&r.public_key())
+        .map_err(|e| Error::custom(&format!("Failed to blind secret: {}", e)))?;
 
-    for (&denom, &count) in &denominations {
-        for _ in 0..count {
-            let blinded_msg = create_simple_blinded_message(denom, keyset_id)?;
-            outputs.push(blinded_msg);
+    Ok(PreMint {
+        blinded_message: BlindedMessage::new(amount.into(), keyset_id, blinded_secret.into()),
+        secret,
+        r,
+        amount: amount.into(),
+    })
+}
+
+/// Unblind the mint's signatures into proofs (NUT-00).
+///
+/// Signatures must be in the same order as the outputs of `premint`. Every
+/// signature is checked against the amount and keyset of its output before
+/// `C = C_ - rK` is computed with the keyset key for that amount.
+pub fn unblind_signatures(
+    premint: &PreMintSecrets,
+    signatures: Vec<BlindSignature>,
+    keys: &Keys,
+) -> Result<Proofs> {
+    if signatures.len() != premint.len() {
+        return Err(Error::custom(&format!(
+            "Mint returned {} signatures for {} outputs",
+            signatures.len(),
+            premint.len()
+        )));
+    }
+
+    let mut proofs = Vec::with_capacity(signatures.len());
+
+    for (signature, output) in signatures.into_iter().zip(premint.iter()) {
+        if signature.amount != output.amount {
+            return Err(Error::custom(&format!(
+                "Signature amount {} does not match output amount {}",
+                signature.amount, output.amount
+            )));
         }
+
+        proofs.push(unblind_signature(signature, output, keys)?);
     }
 
-    Ok(outputs)
+    Ok(proofs)
 }
 
-/// Create a simple blinded message for the mint
-fn create_simple_blinded_message(amount: u64, keyset_id: &str) -> Result<BlindedMessage> {
-    let secp = Secp256k1::new();
+/// Create blank outputs for returning overpaid Lightning fees (NUT-08).
+///
+/// The mint assigns the amounts when it signs, so the outputs carry a zero
+/// amount. `max(ceil(log2(max_change)), 1)` outputs are enough to represent
+/// any change up to `max_change`.
+pub fn create_blank_outputs(max_change: u64, keyset_id: Id) -> Result<PreMintSecrets> {
+    let count = (u64::BITS - max_change.saturating_sub(1).leading_zeros()).max(1);
 
-    // Generate a proper secp256k1 private key
-    let mut rng = rand::rng();
-    let mut secret_bytes = [0u8; 32];
-    rng.fill_bytes(&mut secret_bytes);
+    let mut premint = PreMintSecrets::new(keyset_id);
+    for _ in 0..count {
+        premint
+            .secrets
+            .push(blind_secret(Secret::generate(), 0, keyset_id, None)?);
+    }
 
-    let secret_key = SecretKey::from_byte_array(secret_bytes)
-        .map_err(|e| Error::custom(&format!("Failed to create secret key: {}", e)))?;
+    Ok(premint)
+}
 
-    // Generate the corresponding public key
-    let public_key = secret_key.public_key(&secp);
+/// Unblind change signatures returned for blank outputs (NUT-08).
+///
+/// The mint may sign fewer outputs than were sent and sets the amount of each
+/// one, so the signature amounts are taken as they are.
+pub fn unblind_change_signatures(
+    premint: &PreMintSecrets,
+    signatures: Vec<BlindSignature>,
+    keys: &Keys,
+) -> Result<Proofs> {
+    if signatures.len() > premint.len() {
+        return Err(Error::custom(&format!(
+            "Mint returned {} change signatures for {} blank outputs",
+            signatures.len(),
+            premint.len()
+        )));
+    }
 
-    // Convert to compressed format
-    let compressed_pubkey = public_key.serialize();
+    signatures
+        .into_iter()
+        .zip(premint.iter())
+        .map(|(signature, output)| unblind_signature(signature, output, keys))
+        .collect()
+}
 
-    // Parse keyset ID
-    let keyset_id_parsed =
-        Id::from_str(keyset_id).map_err(|e| Error::custom(&format!("Invalid keyset ID: {}", e)))?;
+fn unblind_signature(signature: BlindSignature, output: &PreMint, keys: &Keys) -> Result<Proof> {
+    if signature.keyset_id != output.blinded_message.keyset_id {
+        return Err(Error::custom(&format!(
+            "Signature keyset {} does not match output keyset {}",
+            signature.keyset_id, output.blinded_message.keyset_id
+        )));
+    }
 
-    // Create blinded message
-    let blinded_msg = BlindedMessage {
-        amount: amount.into(),
-        keyset_id: keyset_id_parsed,
-        blinded_secret: cdk::nuts::PublicKey::from_hex(hex::encode(compressed_pubkey))
-            .map_err(|e| Error::custom(&format!("Failed to create blinded secret: {}", e)))?,
+    let mint_pubkey = keys.amount_key(signature.amount).ok_or_else(|| {
+        Error::custom(&format!(
+            "Keyset {} has no key for amount {}",
+            signature.keyset_id, signature.amount
+        ))
+    })?;
+
+    let c = unblind_message(&signature.c, &output.r, &mint_pubkey)
+        .map_err(|e| Error::custom(&format!("Failed to unblind signature: {}", e)))?;
+
+    Ok(Proof {
+        amount: signature.amount,
+        keyset_id: signature.keyset_id,
+        secret: output.secret.clone(),
+        c,
         witness: None,
-    };
+        dleq: signature
+            .dleq
+            .map(|dleq| ProofDleq::new(dleq.e, dleq.s, output.r.clone())),
+    })
+}
 
-    Ok(blinded_msg)
+/// The secrets and blinding factors of outputs sent to the mint, kept until
+/// their proofs are stored so the signatures can be restored (NUT-09) if the
+/// mint's response is lost
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StoredOutputs {
+    pub keyset_id: String,
+    pub outputs: Vec<StoredOutput>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StoredOutput {
+    pub amount: u64,
+    pub secret: String,
+    /// Hex blinding factor `r`
+    pub blinding_factor: String,
+}
+
+impl StoredOutputs {
+    pub fn from_premint(premint: &PreMintSecrets) -> Self {
+        Self {
+            keyset_id: premint.keyset_id.to_string(),
+            outputs: premint
+                .iter()
+                .map(|output| StoredOutput {
+                    amount: u64::from(output.amount),
+                    secret: output.secret.to_string(),
+                    blinding_factor: output.r.to_secret_hex(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Blind the stored secrets again, giving the same outputs
+    pub fn to_premint(&self) -> Result<PreMintSecrets> {
+        let keyset_id = Id::from_str(&self.keyset_id)
+            .map_err(|e| Error::custom(&format!("Invalid keyset ID: {}", e)))?;
+
+        let mut premint = PreMintSecrets::new(keyset_id);
+        for output in &self.outputs {
+            let r = SecretKey::from_hex(&output.blinding_factor)
+                .map_err(|e| Error::custom(&format!("Invalid blinding factor: {}", e)))?;
+            premint.secrets.push(blind_secret(
+                Secret::new(output.secret.clone()),
+                output.amount,
+                keyset_id,
+                Some(r),
+            )?);
+        }
+
+        Ok(premint)
+    }
 }
 
 /// Generate a random secret string for cashu proofs
@@ -86,3 +285,144 @@ pub fn generate_random_secret() -> String {
     rng.fill_bytes(&mut secret_bytes);
     hex::encode(secret_bytes)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cdk::dhke::{sign_message, verify_message};
+    use cdk::nuts::PublicKey;
+    use cdk::Amount;
+    use std::collections::BTreeMap;
+
+    // Test vectors from NUT-00 (https://github.com/cashubtc/nuts/blob/main/tests/00-tests.md)
+
+    fn keyset_id() -> Id {
+        Id::from_str("009a1f293253e41e").unwrap()
+    }
+
+    fn pubkey(hex: &str) -> PublicKey {
+        PublicKey::from_hex(hex).unwrap()
+    }
+
+    fn secret_key(hex: &str) -> SecretKey {
+        SecretKey::from_hex(hex).unwrap()
+    }
+
+    #[test]
+    fn blind_secret_matches_nut00_vectors() {
+        let r = secret_key("0000000000000000000000000000000000000000000000000000000000000001");
+        let premint =
+            blind_secret(Secret::new("test_message"), 1, keyset_id(), Some(r.clone())).unwrap();
+        let blinded = premint.blinded_message.blinded_secret;
+
+        assert_eq!(premint.r, r);
+        assert_eq!(
+            blinded,
+            pubkey("025cc16fe33b953e2ace39653efb3e7a7049711ae1d8a2f7a9108753f1cdea742b")
+        );
+
+        // The mint signs B_ as C_ = kB_
+        let mint_key =
+            secret_key("7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f");
+        assert_eq!(
+            sign_message(&mint_key, &blinded).unwrap(),
+            pubkey("027726f0e5757b4202a27198369a3477a17bc275b7529da518fc7cb4a1d927cc0d")
+        );
+    }
+
+    #[test]
+    fn unblind_signatures_matches_nut00_vector() {
+        let output = blind_secret(
+            Secret::new("test_message"),
+            1,
+            keyset_id(),
+            Some(secret_key(
+                "0000000000000000000000000000000000000000000000000000000000000001",
+            )),
+        )
+        .unwrap();
+        let mut premint = PreMintSecrets::new(keyset_id());
+        premint.secrets.push(output);
+
+        let keys = Keys::new(BTreeMap::from([(
+            Amount::from(1),
+            pubkey("020000000000000000000000000000000000000000000000000000000000000001"),
+        )]));
+        let signature = BlindSignature {
+            amount: Amount::from(1),
+            keyset_id: keyset_id(),
+            c: pubkey("02a9acc1e48c25eeeb9289b5031cc57da9fe72f3fe2861d264bdc074209b107ba2"),
+            dleq: None,
+        };
+
+        let proofs = unblind_signatures(&premint, vec![signature], &keys).unwrap();
+        assert_eq!(
+            proofs[0].c,
+            pubkey("03c724d7e6a5443b39ac8acf11f40420adc4f99a02e7cc1b57703d9391f6d129cd")
+        );
+        assert_eq!(proofs[0].secret, Secret::new("test_message"));
+    }
+
+    #[test]
+    fn blinded_outputs_unblind_to_valid_proofs() {
+        let mint_key =
+            secret_key("7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f");
+        let keys = Keys::new(
+            [1u64, 2, 4, 8]
+                .into_iter()
+                .map(|amount| (Amount::from(amount), mint_key.public_key()))
+                .collect(),
+        );
+
+        let premint =
+            create_blinded_messages_for_amount(13, "009a1f293253e41e", &[1, 2, 4, 8]).unwrap();
+        let signatures = premint
+            .iter()
+            .map(|output| BlindSignature {
+                amount: output.amount,
+                keyset_id: keyset_id(),
+                c: sign_message(&mint_key, &output.blinded_message.blinded_secret).unwrap(),
+                dleq: None,
+            })
+            .collect();
+
+        let proofs = unblind_signatures(&premint, signatures, &keys).unwrap();
+        assert_eq!(proofs.iter().map(|p| u64::from(p.amount)).sum::<u64>(), 13);
+        for proof in proofs {
+            verify_message(&mint_key, proof.c, &proof.secret.to_bytes()).unwrap();
+        }
+    }
+
+    #[test]
+    fn stored_outputs_blind_to_the_same_outputs() {
+        let premint = create_blinded_messages_for_amounts(&[1, 4, 8], keyset_id()).unwrap();
+        let stored = StoredOutputs::from_premint(&premint);
+        let json = serde_json::to_string(&stored).unwrap();
+
+        let restored: StoredOutputs = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.to_premint().unwrap(), premint);
+    }
+
+    #[test]
+    fn unblind_signatures_rejects_mismatched_amounts() {
+        let mint_key =
+            secret_key("0000000000000000000000000000000000000000000000000000000000000001");
+        let keys = Keys::new(BTreeMap::from([
+            (Amount::from(1), mint_key.public_key()),
+            (Amount::from(2), mint_key.public_key()),
+        ]));
+        let premint = create_blinded_messages_for_amounts(&[1], keyset_id()).unwrap();
+        let signature = BlindSignature {
+            amount: Amount::from(2),
+            keyset_id: keyset_id(),
+            c: sign_message(
+                &mint_key,
+                &premint.secrets[0].blinded_message.blinded_secret,
+            )
+            .unwrap(),
+            dleq: None,
+        };
+
+        assert!(unblind_signatures(&premint, vec![signature], &keys).is_err());
+    }
+}
@@ -0,0 +1,339 @@
+use crate::error::{Error, Result};
+use crate::mint::KeysetResponse;
+use cdk::nuts::nut12;
+use cdk::nuts::{BlindSignature, CurrencyUnit, Id, KeySetInfo, Keys, PreMintSecrets, Proof, Token};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// What to do with signatures and proofs that carry no DLEQ proof (NUT-12).
+///
+/// A DLEQ proof that is present but invalid is always rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum DleqPolicy {
+    /// Fail when any signature or proof lacks a DLEQ proof
+    Reject,
+    /// Accept them, but count them as unverified
+    #[default]
+    Flag,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TokenVerification {
+    pub verified: usize,
+    pub missing_dleq: usize,
+    /// Proofs not checked because the mint's keys could not be fetched, or
+    /// the mint does not know their keyset
+    #[serde(default)]
+    pub unchecked: usize,
+}
+
+impl TokenVerification {
+    /// `proofs` proofs that could not be checked at all
+    pub fn unchecked(proofs: usize) -> Self {
+        Self {
+            unchecked: proofs,
+            ..Self::default()
+        }
+    }
+
+    pub fn is_fully_verified(&self) -> bool {
+        self.missing_dleq == 0 && self.unchecked == 0
+    }
+
+    /// Add the counts of `other`, for proofs checked in batches
+    pub fn merge(&mut self, other: &TokenVerification) {
+        self.verified += other.verified;
+        self.missing_dleq += other.missing_dleq;
+        self.unchecked += other.unchecked;
+    }
+}
+
+/// Verify the DLEQ proofs of the mint's signatures against the outputs they sign
+pub fn verify_signatures(
+    premint: &PreMintSecrets,
+    signatures: &[BlindSignature],
+    keys: &Keys,
+    policy: DleqPolicy,
+) -> Result<TokenVerification> {
+    let mut verification = TokenVerification::default();
+
+    for (signature, output) in signatures.iter().zip(premint.iter()) {
+        let mint_pubkey = keys.amount_key(signature.amount).ok_or_else(|| {
+            Error::custom(&format!(
+                "Keyset {} has no key for amount {}",
+                signature.keyset_id, signature.amount
+            ))
+        })?;
+
+        match signature.verify_dleq(mint_pubkey, output.blinded_message.blinded_secret) {
+            Ok(()) => verification.verified += 1,
+            Err(nut12::Error::MissingDleqProof) => {
+                record_missing(&mut verification, policy, "signature")?
+            }
+            Err(e) => {
+                return Err(Error::custom(&format!(
+                    "Invalid DLEQ proof on signature for amount {}: {}",
+                    signature.amount, e
+                )))
+            }
+        }
+    }
+
+    Ok(verification)
+}
+
+/// Verify the DLEQ proofs carried by `proofs` without contacting the mint.
+///
+/// A proof on a keyset missing from `keysets` fails under
+/// [`DleqPolicy::Reject`] and is counted as unchecked under
+/// [`DleqPolicy::Flag`].
+pub fn verify_proofs(
+    proofs: &[Proof],
+    keysets: &[KeysetResponse],
+    policy: DleqPolicy,
+) -> Result<TokenVerification> {
+    let mut keys_by_id: HashMap<String, Keys> = HashMap::new();
+    for keyset in keysets {
+        keys_by_id.insert(keyset.id.clone(), keyset.to_keys()?);
+    }
+
+    let mut verification = TokenVerification::default();
+
+    for proof in proofs {
+        let keyset_id = proof.keyset_id.to_string();
+        let Some(keys) = keys_by_id.get(&keyset_id) else {
+            match policy {
+                DleqPolicy::Reject => {
+                    return Err(Error::custom(&format!("Unknown keyset {}", keyset_id)))
+                }
+                DleqPolicy::Flag => {
+                    verification.unchecked += 1;
+                    continue;
+                }
+            }
+        };
+        let mint_pubkey = keys.amount_key(proof.amount).ok_or_else(|| {
+            Error::custom(&format!(
+                "Keyset {} has no key for amount {}",
+                keyset_id, proof.amount
+            ))
+        })?;
+
+        match proof.verify_dleq(mint_pubkey) {
+            Ok(()) => verification.verified += 1,
+            Err(nut12::Error::MissingDleqProof) => {
+                record_missing(&mut verification, policy, "proof")?
+            }
+            Err(e) => {
+                return Err(Error::custom(&format!(
+                    "Invalid DLEQ proof on proof for amount {}: {}",
+                    proof.amount, e
+                )))
+            }
+        }
+    }
+
+    Ok(verification)
+}
+
+/// Verify every proof of a token against the keysets of its mint, offline
+pub fn verify_token(
+    token: &Token,
+    keysets: &[KeysetResponse],
+    policy: DleqPolicy,
+) -> Result<TokenVerification> {
+    let keyset_infos = keysets
+        .iter()
+        .map(|keyset| {
+            Ok(KeySetInfo {
+                id: Id::from_str(&keyset.id)
+                    .map_err(|e| Error::custom(&format!("Invalid keyset ID: {}", e)))?,
+                unit: CurrencyUnit::from_str(&keyset.unit)
+                    .map_err(|e| Error::custom(&format!("Invalid unit {}: {}", keyset.unit, e)))?,
+                active: true,
+                input_fee_ppk: 0,
+                final_expiry: None,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let proofs = token
+        .proofs(&keyset_infos)
+        .map_err(|e| Error::custom(&format!("Failed to get proofs from token: {}", e)))?;
+
+    verify_proofs(&proofs, keysets, policy)
+}
+
+fn record_missing(
+    verification: &mut TokenVerification,
+    policy: DleqPolicy,
+    what: &str,
+) -> Result<()> {
+    match policy {
+        DleqPolicy::Reject => Err(Error::custom(&format!("Missing DLEQ proof on {}", what))),
+        DleqPolicy::Flag => {
+            verification.missing_dleq += 1;
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cdk::nuts::{BlindedMessage, PreMint, PublicKey, SecretKey};
+    use cdk::secret::Secret;
+    use cdk::Amount;
+
+    // Test vectors from NUT-12 (https://github.com/cashubtc/nuts/blob/main/tests/12-tests.md)
+
+    const KEYSET_ID: &str = "00882760bfa2eb41";
+    /// Mint key `A` of private key 1, used for every amount
+    const MINT_KEY: &str = "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798";
+    const BLINDED_SECRET: &str =
+        "02a9acc1e48c25eeeb9289b5031cc57da9fe72f3fe2861d264bdc074209b107ba2";
+    const SIGNATURE_E: &str = "9818e061ee51d5c8edc3342369a554998ff7b4381c8652d724cdf46429be73d9";
+    const SIGNATURE_S: &str = "9818e061ee51d5c8edc3342369a554998ff7b4381c8652d724cdf46429be73da";
+    const PROOF_E: &str = "b31e58ac6527f34975ffab13e70a48b6d2b0d35abc4b03f0151f09ee1a9763d4";
+    const PROOF_S: &str = "8fbae004c59e754d71df67e392b6ae4e29293113ddc2ec86592a0431d16306d8";
+    const PROOF_R: &str = "a6d13fcd7a18442e6076f5e1e7c887ad5de40a019824bdfa9fe740d302e8d861";
+
+    fn keys() -> Keys {
+        let mint_key = PublicKey::from_hex(MINT_KEY).unwrap();
+        Keys::new([(Amount::from(1), mint_key), (Amount::from(8), mint_key)].into())
+    }
+
+    fn keysets() -> Vec<KeysetResponse> {
+        vec![KeysetResponse {
+            id: KEYSET_ID.to_string(),
+            unit: "sat".to_string(),
+            keys: [
+                ("1".to_string(), MINT_KEY.to_string()),
+                ("8".to_string(), MINT_KEY.to_string()),
+            ]
+            .into(),
+        }]
+    }
+
+    fn premint() -> PreMintSecrets {
+        let keyset_id = Id::from_str(KEYSET_ID).unwrap();
+        let blinded_secret = PublicKey::from_hex(BLINDED_SECRET).unwrap();
+        let mut premint = PreMintSecrets::new(keyset_id);
+        premint.secrets.push(PreMint {
+            blinded_message: BlindedMessage::new(Amount::from(8), keyset_id, blinded_secret),
+            secret: Secret::generate(),
+            r: SecretKey::generate(),
+            amount: Amount::from(8),
+        });
+        premint
+    }
+
+    fn signature(e: &str, s: &str) -> BlindSignature {
+        serde_json::from_str(&format!(
+            r#"{{"amount":8,"id":"{}","C_":"{}","dleq":{{"e":"{}","s":"{}"}}}}"#,
+            KEYSET_ID, BLINDED_SECRET, e, s
+        ))
+        .unwrap()
+    }
+
+    fn proof(keyset_id: &str, dleq: Option<(&str, &str, &str)>) -> Proof {
+        let dleq = dleq
+            .map(|(e, s, r)| format!(r#","dleq":{{"e":"{}","s":"{}","r":"{}"}}"#, e, s, r))
+            .unwrap_or_default();
+        serde_json::from_str(&format!(
+            r#"{{"amount":1,"id":"{}","secret":"daf4dd00a2b68a0858a80450f52c8a7d2ccf87d375e43e216e0c571f089f63e9","C":"024369d2d22a80ecf78f3937da9d5f30c1b9f74f0c32684d583cca0fa6a61cdcfc"{}}}"#,
+            keyset_id, dleq
+        ))
+        .unwrap()
+    }
+
+    /// `hex` with its last digit changed
+    fn tampered(hex: &str) -> String {
+        let (head, last) = hex.split_at(hex.len() - 1);
+        format!("{}{}", head, if last == "0" { "1" } else { "0" })
+    }
+
+    #[test]
+    fn valid_signature_dleq_is_verified() {
+        let verification = verify_signatures(
+            &premint(),
+            &[signature(SIGNATURE_E, SIGNATURE_S)],
+            &keys(),
+            DleqPolicy::Reject,
+        )
+        .unwrap();
+        assert_eq!(verification.verified, 1);
+        assert!(verification.is_fully_verified());
+    }
+
+    #[test]
+    fn tampered_signature_dleq_is_rejected_under_any_policy() {
+        for policy in [DleqPolicy::Reject, DleqPolicy::Flag] {
+            for signature in [
+                signature(&tampered(SIGNATURE_E), SIGNATURE_S),
+                signature(SIGNATURE_E, &tampered(SIGNATURE_S)),
+            ] {
+                assert!(verify_signatures(&premint(), &[signature], &keys(), policy).is_err());
+            }
+        }
+    }
+
+    #[test]
+    fn valid_proof_dleq_is_verified() {
+        let proofs = [proof(KEYSET_ID, Some((PROOF_E, PROOF_S, PROOF_R)))];
+        let verification = verify_proofs(&proofs, &keysets(), DleqPolicy::Reject).unwrap();
+        assert_eq!(verification.verified, 1);
+        assert!(verification.is_fully_verified());
+    }
+
+    #[test]
+    fn tampered_proof_dleq_is_rejected_under_any_policy() {
+        for policy in [DleqPolicy::Reject, DleqPolicy::Flag] {
+            for dleq in [
+                (tampered(PROOF_E), PROOF_S.to_string(), PROOF_R.to_string()),
+                (PROOF_E.to_string(), tampered(PROOF_S), PROOF_R.to_string()),
+                (PROOF_E.to_string(), PROOF_S.to_string(), tampered(PROOF_R)),
+            ] {
+                let proofs = [proof(KEYSET_ID, Some((&dleq.0, &dleq.1, &dleq.2)))];
+                assert!(verify_proofs(&proofs, &keysets(), policy).is_err());
+            }
+        }
+    }
+
+    #[test]
+    fn missing_dleq_fails_under_reject_and_is_counted_under_flag() {
+        let mut signature = signature(SIGNATURE_E, SIGNATURE_S);
+        signature.dleq = None;
+        assert!(verify_signatures(
+            &premint(),
+            &[signature.clone()],
+            &keys(),
+            DleqPolicy::Reject
+        )
+        .is_err());
+        let verification =
+            verify_signatures(&premint(), &[signature], &keys(), DleqPolicy::Flag).unwrap();
+        assert_eq!(verification.missing_dleq, 1);
+        assert!(!verification.is_fully_verified());
+
+        let proofs = [proof(KEYSET_ID, None)];
+        assert!(verify_proofs(&proofs, &keysets(), DleqPolicy::Reject).is_err());
+        let verification = verify_proofs(&proofs, &keysets(), DleqPolicy::Flag).unwrap();
+        assert_eq!(verification.missing_dleq, 1);
+    }
+
+    #[test]
+    fn unknown_keyset_fails_under_reject_and_is_unchecked_under_flag() {
+        let proofs = [
+            proof(KEYSET_ID, Some((PROOF_E, PROOF_S, PROOF_R))),
+            proof("009a1f293253e41e", Some((PROOF_E, PROOF_S, PROOF_R))),
+        ];
+        assert!(verify_proofs(&proofs, &keysets(), DleqPolicy::Reject).is_err());
+
+        let verification = verify_proofs(&proofs, &keysets(), DleqPolicy::Flag).unwrap();
+        assert_eq!(verification.verified, 1);
+        assert_eq!(verification.unchecked, 1);
+        assert!(!verification.is_fully_verified());
+    }
+}
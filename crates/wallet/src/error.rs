@@ -1,3 +1,4 @@
+use cdk::error::ErrorCode;
 use derive_more::From;
 use std::fmt;
 
@@ -27,12 +28,129 @@ pub enum Error {
 
     #[from]
     KeyError(nostr_sdk::key::Error),
+
+    #[from]
+    MintError(MintError),
+}
+
+/// Error response returned by a mint for a non-2xx request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MintError {
+    pub status: u16,
+    pub code: Option<ErrorCode>,
+    pub detail: String,
+}
+
+impl MintError {
+    /// Parse a mint error body of the form `{"detail": ..., "code": ...}`.
+    ///
+    /// Bodies that are not JSON, or that carry no code, keep their text as
+    /// the detail and have no code. An empty body gets the status's reason
+    /// phrase as its detail.
+    pub fn from_body(status: u16, body: &str) -> Self {
+        if body.trim().is_empty() {
+            let reason = reqwest::StatusCode::from_u16(status)
+                .ok()
+                .and_then(|status| status.canonical_reason())
+                .unwrap_or("Empty response");
+            return Self {
+                status,
+                code: None,
+                detail: reason.to_string(),
+            };
+        }
+
+        let value = match serde_json::from_str::<serde_json::Value>(body) {
+            Ok(value) => value,
+            Err(_) => {
+                return Self {
+                    status,
+                    code: None,
+                    detail: body.to_string(),
+                }
+            }
+        };
+
+        let code = value
+            .get("code")
+            .and_then(|code| code.as_u64())
+            .and_then(|code| u16::try_from(code).ok())
+            .map(ErrorCode::from_code);
+
+        let detail = match value.get("detail").or_else(|| value.get("error")) {
+            Some(serde_json::Value::String(detail)) => detail.clone(),
+            Some(detail) => detail.to_string(),
+            None => body.to_string(),
+        };
+
+        Self {
+            status,
+            code,
+            detail,
+        }
+    }
+
+    pub fn is_token_spent(&self) -> bool {
+        self.code == Some(ErrorCode::TokenAlreadySpent)
+    }
+
+    pub fn is_token_pending(&self) -> bool {
+        self.code == Some(ErrorCode::TokenPending)
+    }
+
+    pub fn is_quote_not_paid(&self) -> bool {
+        self.code == Some(ErrorCode::QuoteNotPaid)
+    }
+
+    pub fn is_quote_pending(&self) -> bool {
+        self.code == Some(ErrorCode::QuotePending)
+    }
+
+    pub fn is_tokens_already_issued(&self) -> bool {
+        self.code == Some(ErrorCode::TokensAlreadyIssued)
+    }
+
+    pub fn is_keyset_inactive(&self) -> bool {
+        matches!(
+            self.code,
+            Some(ErrorCode::KeysetInactive) | Some(ErrorCode::KeysetNotFound)
+        )
+    }
+
+    pub fn is_amount_outside_limits(&self) -> bool {
+        self.code == Some(ErrorCode::AmountOutofLimitRange)
+    }
+}
+
+impl fmt::Display for MintError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.code {
+            Some(code) => write!(
+                f,
+                "{} (code {}, status {})",
+                self.detail,
+                code.to_code(),
+                self.status
+            ),
+            None => write!(f, "{} (status {})", self.detail, self.status),
+        }
+    }
 }
 
+impl std::error::Error for MintError {}
+
 impl Error {
     pub fn custom(msg: &str) -> Self {
         Error::WalletError(cdk::Error::Custom(msg.to_string()))
     }
+
+    /// The mint's error response, if this error came from one
+    pub fn mint_error(&self) -> Option<&MintError> {
+        match self {
+            Error::MintError(e) => Some(e),
+            _ => None,
+        }
+    }
 }
 
 impl fmt::Display for Error {
@@ -46,6 +164,7 @@ impl fmt::Display for Error {
             Error::SerializationError(e) => write!(f, "Serialization error: {}", e),
             Error::YamlError(e) => write!(f, "YAML error: {}", e),
             Error::KeyError(e) => write!(f, "Key error: {}", e),
+            Error::MintError(e) => write!(f, "Mint error: {}", e),
         }
     }
 }
@@ -61,6 +180,75 @@ impl std::error::Error for Error {
             Error::SerializationError(e) => Some(e),
             Error::YamlError(e) => Some(e),
             Error::KeyError(e) => Some(e),
+            Error::MintError(e) => Some(e),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn known_codes_are_recognised() {
+        let error = MintError::from_body(400, r#"{"detail":"Token already spent","code":11001}"#);
+
+        assert_eq!(error.status, 400);
+        assert_eq!(error.code, Some(ErrorCode::TokenAlreadySpent));
+        assert_eq!(error.detail, "Token already spent");
+        assert!(error.is_token_spent());
+        assert!(!error.is_quote_not_paid());
+        assert_eq!(
+            error.to_string(),
+            "Token already spent (code 11001, status 400)"
+        );
+    }
+
+    #[test]
+    fn unknown_codes_are_kept() {
+        let error = MintError::from_body(400, r#"{"detail":"Something new","code":49999}"#);
+
+        assert_eq!(error.code, Some(ErrorCode::Unknown(49999)));
+        assert_eq!(error.detail, "Something new");
+        assert!(!error.is_token_spent());
+        assert_eq!(error.to_string(), "Something new (code 49999, status 400)");
+    }
+
+    #[test]
+    fn json_without_a_detail_falls_back_to_error_or_the_body() {
+        let error = MintError::from_body(500, r#"{"error":"database down"}"#);
+        assert_eq!(error.code, None);
+        assert_eq!(error.detail, "database down");
+
+        let error = MintError::from_body(400, r#"{"detail":{"loc":["body"]},"code":"x"}"#);
+        assert_eq!(error.code, None);
+        assert_eq!(error.detail, r#"{"loc":["body"]}"#);
+
+        let error = MintError::from_body(400, r#"{"message":"bad"}"#);
+        assert_eq!(error.detail, r#"{"message":"bad"}"#);
+    }
+
+    #[test]
+    fn non_json_bodies_are_kept_as_the_detail() {
+        let error = MintError::from_body(502, "<html>Bad Gateway</html>");
+
+        assert_eq!(error.status, 502);
+        assert_eq!(error.code, None);
+        assert_eq!(error.detail, "<html>Bad Gateway</html>");
+        assert_eq!(error.to_string(), "<html>Bad Gateway</html> (status 502)");
+    }
+
+    #[test]
+    fn empty_bodies_get_the_status_reason() {
+        let error = MintError::from_body(429, "");
+        assert_eq!(error.code, None);
+        assert_eq!(error.detail, "Too Many Requests");
+        assert_eq!(error.to_string(), "Too Many Requests (status 429)");
+
+        let error = MintError::from_body(503, " \n");
+        assert_eq!(error.detail, "Service Unavailable");
+
+        let error = MintError::from_body(599, "");
+        assert_eq!(error.detail, "Empty response");
+    }
+}
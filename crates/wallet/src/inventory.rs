@@ -0,0 +1,169 @@
+use crate::crypto;
+use crate::error::Result;
+use cdk::nuts::{CurrencyUnit, Proofs};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Proofs of one keyset, counted by denomination
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeysetInventory {
+    pub keyset_id: String,
+    pub proof_count: usize,
+    pub total: u64,
+    /// Number of proofs per amount
+    pub denominations: BTreeMap<u64, usize>,
+}
+
+/// Proofs held at a mint in one unit, by keyset and denomination
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProofInventory {
+    pub mint_url: String,
+    pub unit: CurrencyUnit,
+    pub keysets: Vec<KeysetInventory>,
+}
+
+impl ProofInventory {
+    pub fn from_proofs(mint_url: &str, unit: CurrencyUnit, proofs: &Proofs) -> Self {
+        let mut keysets: BTreeMap<String, KeysetInventory> = BTreeMap::new();
+
+        for proof in proofs {
+            let keyset_id = proof.keyset_id.to_string();
+            let amount = u64::from(proof.amount);
+            let keyset = keysets
+                .entry(keyset_id.clone())
+                .or_insert_with(|| KeysetInventory {
+                    keyset_id,
+                    proof_count: 0,
+                    total: 0,
+                    denominations: BTreeMap::new(),
+                });
+
+            keyset.proof_count += 1;
+            keyset.total += amount;
+            *keyset.denominations.entry(amount).or_default() += 1;
+        }
+
+        Self {
+            mint_url: mint_url.to_string(),
+            unit,
+            keysets: keysets.into_values().collect(),
+        }
+    }
+
+    pub fn proof_count(&self) -> usize {
+        self.keysets.iter().map(|keyset| keyset.proof_count).sum()
+    }
+
+    pub fn total(&self) -> u64 {
+        self.keysets.iter().map(|keyset| keyset.total).sum()
+    }
+
+    /// Number of proofs per amount across all keysets
+    pub fn denominations(&self) -> BTreeMap<u64, usize> {
+        let mut denominations = BTreeMap::new();
+        for keyset in &self.keysets {
+            for (&amount, &count) in &keyset.denominations {
+                *denominations.entry(amount).or_default() += count;
+            }
+        }
+        denominations
+    }
+}
+
+/// How [`crate::multimint::MultimintWallet::consolidate`] reshapes a mint's proofs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConsolidationOptions {
+    /// Proofs of each denomination up to `reserve_max_denomination` to keep
+    /// for exact sends
+    pub reserve_per_denomination: usize,
+    pub reserve_max_denomination: u64,
+    /// Leave mints holding fewer proofs than this alone
+    pub min_proofs: usize,
+}
+
+impl Default for ConsolidationOptions {
+    fn default() -> Self {
+        Self {
+            reserve_per_denomination: 2,
+            reserve_max_denomination: 64,
+            min_proofs: 8,
+        }
+    }
+}
+
+impl ConsolidationOptions {
+    /// The amounts to hold `amount` in: the small-denomination reserve first,
+    /// then the fewest proofs for the rest. Smallest first.
+    pub fn target_amounts(&self, amount: u64, denominations: &[u64]) -> Result<Vec<u64>> {
+        let mut sorted = denominations.to_vec();
+        sorted.sort_unstable();
+        sorted.dedup();
+
+        let mut amounts = Vec::new();
+        let mut remaining = amount;
+
+        for &denom in sorted
+            .iter()
+            .filter(|&&denom| denom > 0 && denom <= self.reserve_max_denomination)
+        {
+            for _ in 0..self.reserve_per_denomination {
+                if remaining < denom {
+                    break;
+                }
+                amounts.push(denom);
+                remaining -= denom;
+            }
+        }
+
+        amounts.extend(crypto::split_amount(remaining, &sorted)?);
+        amounts.sort_unstable();
+        Ok(amounts)
+    }
+}
+
+/// What consolidating one mint did
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConsolidationResult {
+    pub mint_url: String,
+    pub unit: CurrencyUnit,
+    pub proofs_before: usize,
+    pub proofs_after: usize,
+    /// Input fee paid for the swap
+    pub fee: u64,
+}
+
+/// Proofs moved off a mint's inactive keysets into its active keyset
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeysetMigration {
+    pub mint_url: String,
+    pub unit: CurrencyUnit,
+    /// Inactive keysets the moved proofs came from
+    pub inactive_keysets: Vec<String>,
+    pub proofs_moved: usize,
+    /// Value of the new proofs, after the fee
+    pub amount_moved: u64,
+    /// Input fee paid for the swap
+    pub fee: u64,
+}
+
+/// Outcome of checking a mint's proofs against its NUT-07 states
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProofStateSync {
+    pub mint_url: String,
+    pub unit: CurrencyUnit,
+    pub checked: usize,
+    /// Proofs the mint reported spent, now removed from the wallet
+    pub spent_count: usize,
+    pub spent_amount: u64,
+    /// Proofs in an unfinished payment at the mint, kept in the wallet
+    pub pending: Proofs,
+}
+
+impl ProofStateSync {
+    pub fn pending_amount(&self) -> u64 {
+        self.pending
+            .iter()
+            .map(|proof| u64::from(proof.amount))
+            .sum()
+    }
+}
@@ -1,7 +1,20 @@
+pub mod bolt11;
 pub mod crypto;
+pub mod dleq;
 pub mod error;
+pub mod inventory;
 pub mod lightning;
+pub mod lnurl;
 pub mod mint;
+pub mod mint_registry;
+pub mod mint_selection;
 pub mod models;
 pub mod multimint;
+pub mod price;
+pub mod quote_store;
+pub mod restore;
+pub mod seed;
+pub mod storage;
+pub mod subscription;
+pub mod token_bundle;
 pub mod wallet;
@@ -1,15 +1,25 @@
 use crate::{
+    bolt11::DecodedInvoice,
+    crypto::{self, StoredOutputs},
+    dleq::TokenVerification,
     error::{Error, Result},
-    mint::MintClient,
+    lnurl::LnurlClient,
+    mint::{MintClient, MintTransport, PaymentMethod, PostMeltQuoteResponse, SignedProofs},
     multimint::MultimintWallet,
+    quote_store::{QuoteStore, StoredQuotes},
+    subscription::{StateStream, StateUpdate, SubscriptionKind},
     wallet::CashuWalletClient,
 };
-use cdk::nuts::CurrencyUnit;
+use cdk::nuts::{CurrencyUnit, Id, MeltOptions, Proofs, SecretKey, State};
+use cdk::types::ProofInfo;
+use cdk::wallet::Wallet;
+use cdk::Amount;
+use futures::StreamExt;
 use serde::{Deserialize, Serialize};
 use std::{
     collections::HashMap,
     fmt,
-    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -22,8 +32,18 @@ pub struct LightningInvoice {
     pub expiry: u64,
     pub state: InvoiceState,
     pub fee_reserve: Option<u64>,
-    #[serde(skip, default = "Instant::now")]
-    pub created_at: Instant,
+    /// Unix time the invoice was requested
+    #[serde(default)]
+    pub created_at: u64,
+    /// `Bolt12` when `payment_request` is a reusable offer
+    #[serde(default)]
+    pub method: PaymentMethod,
+    /// Hex secret key signing the mint requests of a bolt12 quote (NUT-20)
+    #[serde(default)]
+    pub signing_key: Option<String>,
+    /// Outputs of a mint request whose proofs are not stored yet
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub outputs: Option<StoredOutputs>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -31,6 +51,7 @@ pub enum InvoiceState {
     Unpaid,
     Pending,
     Paid,
+    Issued,
     Failed,
     Expired,
 }
@@ -41,6 +62,7 @@ impl fmt::Display for InvoiceState {
             InvoiceState::Unpaid => write!(f, "unpaid"),
             InvoiceState::Pending => write!(f, "pending"),
             InvoiceState::Paid => write!(f, "paid"),
+            InvoiceState::Issued => write!(f, "issued"),
             InvoiceState::Failed => write!(f, "failed"),
             InvoiceState::Expired => write!(f, "expired"),
         }
@@ -59,10 +81,13 @@ pub struct PaymentStatus {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TopupRequest {
+    /// 0 requests a bolt12 offer that accepts any amount
     pub amount: u64,
     pub unit: CurrencyUnit,
     pub mint_url: Option<String>,
     pub description: Option<String>,
+    #[serde(default)]
+    pub method: PaymentMethod,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -78,6 +103,31 @@ pub struct TopupResult {
     pub fee_paid: u64,
     pub mint_url: String,
     pub message: String,
+    /// DLEQ check of the minted proofs
+    #[serde(default)]
+    pub verification: TokenVerification,
+}
+
+/// The quotes settled by [`LightningManager::resume_pending`]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ResumeReport {
+    pub topups: Vec<TopupResult>,
+    /// Quotes that could not be settled, kept for the next run
+    pub failed: Vec<ResumeFailure>,
+}
+
+impl ResumeReport {
+    pub fn extend(&mut self, other: ResumeReport) {
+        self.topups.extend(other.topups);
+        self.failed.extend(other.failed);
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResumeFailure {
+    pub quote_id: String,
+    pub mint_url: String,
+    pub error: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -86,28 +136,236 @@ pub struct MeltRequest {
     pub payment_request: String,
     pub amount: u64,
     pub unit: CurrencyUnit,
+    /// Secrets of the proofs to spend. When empty, proofs are selected from the wallet.
     pub proofs: Vec<String>,
+    #[serde(default)]
+    pub method: PaymentMethod,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MeltResponse {
     pub success: bool,
     pub payment_preimage: Option<String>,
-    pub change_proofs: Option<Vec<String>>,
+    pub change_proofs: Option<Proofs>,
     pub fee_paid: u64,
     pub message: String,
+    /// DLEQ check of the change proofs
+    #[serde(default)]
+    pub verification: TokenVerification,
+}
+
+/// What paying an invoice through a mint will cost, before any proofs are spent
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MeltPreview {
+    /// The decoded invoice, `None` when paying a bolt12 offer
+    pub invoice: Option<DecodedInvoice>,
+    pub payment_request: String,
+    pub method: PaymentMethod,
+    pub quote_id: String,
+    /// Amount the mint will pay, in `unit`
+    pub amount: u64,
+    pub fee_reserve: u64,
+    pub unit: CurrencyUnit,
+    /// Unix time the melt quote expires
+    pub quote_expiry: u64,
+}
+
+/// A melt whose inputs are locked until the mint settles the payment
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingMelt {
+    pub mint_url: String,
+    pub quote_id: String,
+    pub payment_request: String,
+    pub amount: u64,
+    pub fee_reserve: u64,
+    pub unit: CurrencyUnit,
+    pub proofs: Proofs,
+    pub created_at: u64,
+    #[serde(default)]
+    pub method: PaymentMethod,
+    /// Blank outputs sent for the fee change (NUT-08), kept to unblind the
+    /// change of a melt settled after its response was lost
+    #[serde(default)]
+    pub outputs: Option<StoredOutputs>,
 }
 
 #[derive(Debug, Clone)]
 pub struct LightningManager {
     pending_invoices: HashMap<String, LightningInvoice>,
+    pending_melts: HashMap<String, PendingMelt>,
+    transport: MintTransport,
+    store: Option<QuoteStore>,
+    lnurl: LnurlClient,
 }
 
 impl LightningManager {
     pub fn new() -> Self {
+        Self::with_transport(MintTransport::shared())
+    }
+
+    pub fn with_transport(transport: MintTransport) -> Self {
         Self {
             pending_invoices: HashMap::new(),
+            pending_melts: HashMap::new(),
+            lnurl: LnurlClient::new(transport.clone()),
+            transport,
+            store: None,
+        }
+    }
+
+    /// Resolve Lightning addresses and LNURL-pay links with `lnurl`
+    pub fn with_lnurl_client(mut self, lnurl: LnurlClient) -> Self {
+        self.lnurl = lnurl;
+        self
+    }
+
+    /// Create a manager that keeps its pending invoices and melts in `store`,
+    /// loading the ones left from a previous run.
+    ///
+    /// Call [`Self::resume_pending`] afterwards to settle them.
+    pub fn with_store(transport: MintTransport, store: QuoteStore) -> Result<Self> {
+        let stored = store.load()?;
+        let mut manager = Self::with_transport(transport);
+
+        manager.pending_invoices = stored
+            .invoices
+            .into_iter()
+            .map(|invoice| (invoice.quote_id.clone(), invoice))
+            .collect();
+        manager.pending_melts = stored
+            .melts
+            .into_iter()
+            .map(|melt| (melt.quote_id.clone(), melt))
+            .collect();
+        manager.store = Some(store);
+
+        Ok(manager)
+    }
+
+    fn persist(&self) -> Result<()> {
+        match &self.store {
+            Some(store) => store.save(&StoredQuotes {
+                invoices: self.pending_invoices.values().cloned().collect(),
+                melts: self.pending_melts.values().cloned().collect(),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Settle the invoices and melts of `wallet`'s mint and unit left from an
+    /// earlier run: mint invoices that got paid while offline, resolve
+    /// pending melts and forget invoices that are minted or expired.
+    ///
+    /// A quote that cannot be settled, e.g. because its mint is unreachable,
+    /// is reported in [`ResumeReport::failed`] and kept for the next run.
+    pub async fn resume_pending(&mut self, wallet: &Wallet) -> Result<ResumeReport> {
+        let mint_url = wallet.mint_url.to_string();
+        let mut report = ResumeReport::default();
+
+        let quote_ids: Vec<String> = self
+            .pending_invoices
+            .values()
+            .filter(|invoice| invoice.mint_url == mint_url && invoice.unit == wallet.unit)
+            .map(|invoice| invoice.quote_id.clone())
+            .collect();
+        for quote_id in quote_ids {
+            match self.resume_invoice(&quote_id, wallet).await {
+                Ok(Some(topup)) => report.topups.push(topup),
+                Ok(None) => {}
+                Err(e) => report.failed.push(ResumeFailure {
+                    quote_id,
+                    mint_url: mint_url.clone(),
+                    error: e.to_string(),
+                }),
+            }
+        }
+
+        let melt_ids: Vec<String> = self
+            .pending_melts
+            .values()
+            .filter(|melt| melt.mint_url == mint_url && melt.unit == wallet.unit)
+            .map(|melt| melt.quote_id.clone())
+            .collect();
+        for quote_id in melt_ids {
+            if let Err(e) = self.resolve_pending_melt(wallet, &quote_id).await {
+                report.failed.push(ResumeFailure {
+                    quote_id,
+                    mint_url: mint_url.clone(),
+                    error: e.to_string(),
+                });
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Mint a pending invoice if it got paid, and drop it once it is minted
+    /// or expired
+    async fn resume_invoice(
+        &mut self,
+        quote_id: &str,
+        wallet: &Wallet,
+    ) -> Result<Option<TopupResult>> {
+        let (issued, interrupted) = self
+            .pending_invoices
+            .get(quote_id)
+            .map(|invoice| {
+                (
+                    invoice.state == InvoiceState::Issued,
+                    invoice.outputs.is_some(),
+                )
+            })
+            .ok_or_else(|| Error::custom("Invoice not found"))?;
+
+        // A topup interrupted after its mint request is completed from its outputs
+        let state = if interrupted {
+            InvoiceState::Paid
+        } else if issued {
+            InvoiceState::Issued
+        } else {
+            self.check_payment_status(quote_id).await?.state
+        };
+
+        let topup = match state {
+            InvoiceState::Paid => Some(self.complete_topup_into(quote_id, wallet).await?),
+            _ => None,
+        };
+
+        let finished = self
+            .pending_invoices
+            .get(quote_id)
+            .is_some_and(|invoice| invoice.outputs.is_none())
+            && matches!(
+                self.get_invoice_state(quote_id),
+                Some(InvoiceState::Issued | InvoiceState::Expired)
+            );
+        if finished {
+            self.remove_invoice(quote_id)?;
+        }
+
+        Ok(topup)
+    }
+
+    pub async fn resume_pending_for_wallet(
+        &mut self,
+        wallet: &CashuWalletClient,
+    ) -> Result<ResumeReport> {
+        self.resume_pending(&wallet.wallet).await
+    }
+
+    pub async fn resume_pending_for_multimint(
+        &mut self,
+        multimint: &MultimintWallet,
+    ) -> Result<ResumeReport> {
+        let mut report = ResumeReport::default();
+        for wallet in multimint.cdk_wallet().get_wallets().await {
+            report.extend(self.resume_pending(&wallet).await?);
         }
+        Ok(report)
+    }
+
+    fn mint_client(&self, mint_url: &str) -> Result<MintClient> {
+        self.transport.client_for(mint_url)
     }
 
     pub async fn create_invoice_for_wallet(
@@ -116,28 +374,19 @@ impl LightningManager {
         mint_url: &str,
         amount: u64,
         unit: CurrencyUnit,
+        method: PaymentMethod,
     ) -> Result<TopupResponse> {
-        let mint_client = MintClient::new(mint_url)?;
-        let quote_response = mint_client.request_mint_quote(amount, unit.clone()).await?;
-
-        let invoice = LightningInvoice {
-            mint_url: mint_url.to_string(),
-            quote_id: quote_response.quote.clone(),
-            payment_request: quote_response.request.clone(),
-            amount,
-            unit,
-            expiry: quote_response.expiry,
-            state: InvoiceState::Unpaid,
-            fee_reserve: None,
-            created_at: Instant::now(),
-        };
-
-        self.pending_invoices
-            .insert(quote_response.quote.clone(), invoice.clone());
+        let invoice = self
+            .create_invoice(mint_url, amount, unit, method, None)
+            .await?;
 
         Ok(TopupResponse {
+            message: format!(
+                "Lightning {} created for {} sats",
+                invoice_kind(method),
+                amount
+            ),
             invoice,
-            message: format!("Lightning invoice created for {} sats", amount),
         })
     }
 
@@ -158,57 +407,150 @@ impl LightningManager {
                 mint_url, available_mints
             )));
         }
+        multimint.ensure_mint_active(&mint_url)?;
 
-        let mint_client = MintClient::new(&mint_url)?;
-        let quote_response = mint_client
-            .request_mint_quote(request.amount, request.unit.clone())
+        let invoice = self
+            .create_invoice(
+                &mint_url,
+                request.amount,
+                request.unit,
+                request.method,
+                request.description,
+            )
             .await?;
 
-        let invoice = LightningInvoice {
-            mint_url: mint_url.clone(),
-            quote_id: quote_response.quote.clone(),
-            payment_request: quote_response.request.clone(),
-            amount: request.amount,
-            unit: request.unit,
-            expiry: quote_response.expiry,
-            state: InvoiceState::Unpaid,
-            fee_reserve: None,
-            created_at: Instant::now(),
-        };
-
-        self.pending_invoices
-            .insert(quote_response.quote.clone(), invoice.clone());
-
         Ok(TopupResponse {
-            invoice,
             message: format!(
-                "Lightning invoice created for {} sats at mint {}",
-                request.amount, mint_url
+                "Lightning {} created for {} sats at mint {}",
+                invoice_kind(request.method),
+                request.amount,
+                mint_url
             ),
+            invoice,
         })
     }
 
+    /// Request a mint quote with `method` and track it until it is minted.
+    ///
+    /// The mint must advertise `method` for `unit`. Bolt12 quotes are
+    /// reusable offers, locked to a fresh key (NUT-20), that accept any
+    /// amount when `amount` is 0.
+    async fn create_invoice(
+        &mut self,
+        mint_url: &str,
+        amount: u64,
+        unit: CurrencyUnit,
+        method: PaymentMethod,
+        description: Option<String>,
+    ) -> Result<LightningInvoice> {
+        let mint_client = self.mint_client(mint_url)?;
+        let advertised = mint_client.get_info().await?.mint_methods(&unit);
+        ensure_method(&advertised, method, "minting", &unit, mint_url)?;
+
+        let invoice = match method {
+            PaymentMethod::Bolt11 => {
+                let quote_response = mint_client.request_mint_quote(amount, unit.clone()).await?;
+                DecodedInvoice::parse(&quote_response.request)?.ensure_amount(amount, &unit)?;
+
+                LightningInvoice {
+                    mint_url: mint_url.to_string(),
+                    quote_id: quote_response.quote,
+                    payment_request: quote_response.request,
+                    amount,
+                    unit,
+                    expiry: quote_response.expiry,
+                    state: InvoiceState::Unpaid,
+                    fee_reserve: None,
+                    created_at: unix_now(),
+                    method,
+                    signing_key: None,
+                    outputs: None,
+                }
+            }
+            PaymentMethod::Bolt12 => {
+                let signing_key = SecretKey::generate();
+                let quote_response = mint_client
+                    .request_bolt12_mint_quote(
+                        (amount > 0).then_some(amount),
+                        unit.clone(),
+                        description,
+                        &signing_key.public_key(),
+                    )
+                    .await?;
+
+                LightningInvoice {
+                    mint_url: mint_url.to_string(),
+                    quote_id: quote_response.quote,
+                    payment_request: quote_response.request,
+                    amount,
+                    unit,
+                    expiry: quote_response.expiry.unwrap_or(u64::MAX),
+                    state: InvoiceState::Unpaid,
+                    fee_reserve: None,
+                    created_at: unix_now(),
+                    method,
+                    signing_key: Some(signing_key.to_secret_hex()),
+                    outputs: None,
+                }
+            }
+        };
+
+        self.pending_invoices
+            .insert(invoice.quote_id.clone(), invoice.clone());
+        self.persist()?;
+
+        Ok(invoice)
+    }
+
+    /// Refresh the state of a tracked invoice from the mint.
+    ///
+    /// A bolt12 offer is `Paid` while it holds payments that are not minted
+    /// yet, and only becomes `Issued` once its full amount has been minted.
     pub async fn check_payment_status(&mut self, quote_id: &str) -> Result<PaymentStatus> {
         if self.pending_invoices.contains_key(quote_id) {
-            let (mint_url, amount, expiry) = {
+            let (mint_url, amount, expiry, issued, method) = {
                 let invoice = self.pending_invoices.get(quote_id).unwrap();
-                (invoice.mint_url.clone(), invoice.amount, invoice.expiry)
+                (
+                    invoice.mint_url.clone(),
+                    invoice.amount,
+                    invoice.expiry,
+                    invoice.state == InvoiceState::Issued,
+                    invoice.method,
+                )
             };
 
-            let mint_client = MintClient::new(&mint_url)?;
-            let quote_response = mint_client.get_mint_quote(quote_id).await?;
-
-            let new_state = if quote_response.paid {
-                InvoiceState::Paid
-            } else if self.is_expired(expiry) {
-                InvoiceState::Expired
-            } else {
-                InvoiceState::Unpaid
+            let mint_client = self.mint_client(&mint_url)?;
+            let new_state = match method {
+                PaymentMethod::Bolt11 => {
+                    let quote_response = mint_client.get_mint_quote(quote_id).await?;
+                    if issued || quote_response.state.as_deref() == Some("ISSUED") {
+                        InvoiceState::Issued
+                    } else if quote_response.is_paid() {
+                        InvoiceState::Paid
+                    } else if self.is_expired(expiry) {
+                        InvoiceState::Expired
+                    } else {
+                        InvoiceState::Unpaid
+                    }
+                }
+                PaymentMethod::Bolt12 => {
+                    let quote_response = mint_client.get_bolt12_mint_quote(quote_id).await?;
+                    if quote_response.mintable_amount() > 0 {
+                        InvoiceState::Paid
+                    } else if amount > 0 && quote_response.amount_issued >= amount {
+                        InvoiceState::Issued
+                    } else if self.is_expired(expiry) {
+                        InvoiceState::Expired
+                    } else {
+                        InvoiceState::Unpaid
+                    }
+                }
             };
 
             if let Some(invoice) = self.pending_invoices.get_mut(quote_id) {
                 invoice.state = new_state.clone();
             }
+            self.persist()?;
 
             Ok(PaymentStatus {
                 quote_id: quote_id.to_string(),
@@ -228,75 +570,617 @@ impl LightningManager {
         mint_url: &str,
         payment_request: &str,
         unit: CurrencyUnit,
+        method: PaymentMethod,
     ) -> Result<String> {
-        let mint_client = MintClient::new(mint_url)?;
-        let quote_response = mint_client
-            .request_melt_quote(payment_request, unit)
+        Ok(self
+            .prepare_melt(mint_url, payment_request, unit, None, method)
+            .await?
+            .quote_id)
+    }
+
+    /// Get a melt quote for a bolt11 invoice or bolt12 offer without spending anything.
+    ///
+    /// The mint must advertise `method` for `unit`. Expired invoices are
+    /// rejected, and so are amountless invoices unless `amount_msat` is
+    /// given. For offers, `amount_msat` is only needed when the offer has
+    /// no amount of its own.
+    pub async fn prepare_melt(
+        &self,
+        mint_url: &str,
+        payment_request: &str,
+        unit: CurrencyUnit,
+        amount_msat: Option<u64>,
+        method: PaymentMethod,
+    ) -> Result<MeltPreview> {
+        let mint_client = self.mint_client(mint_url)?;
+        let advertised = mint_client.get_info().await?.melt_methods(&unit);
+        ensure_method(&advertised, method, "melting", &unit, mint_url)?;
+
+        let (invoice, payment_request, options) = match method {
+            PaymentMethod::Bolt11 => {
+                let invoice = DecodedInvoice::parse(payment_request)?;
+                if invoice.is_expired() {
+                    return Err(Error::custom("Invoice has expired"));
+                }
+
+                let amount_msat = invoice.payable_amount_msat(amount_msat)?;
+                let options = invoice
+                    .amount_msat
+                    .is_none()
+                    .then(|| MeltOptions::new_amountless(amount_msat));
+                let payment_request = invoice.payment_request.clone();
+                (Some(invoice), payment_request, options)
+            }
+            PaymentMethod::Bolt12 => {
+                let offer = payment_request.trim();
+                let offer = offer
+                    .strip_prefix("lightning:")
+                    .or_else(|| offer.strip_prefix("LIGHTNING:"))
+                    .unwrap_or(offer);
+                if !offer.to_lowercase().starts_with("lno1") {
+                    return Err(Error::custom("Not a BOLT12 offer"));
+                }
+                (
+                    None,
+                    offer.to_string(),
+                    amount_msat.map(MeltOptions::new_amountless),
+                )
+            }
+        };
+
+        let quote = mint_client
+            .request_melt_quote_with_method(method, &payment_request, unit.clone(), options)
+            .await?;
+
+        Ok(MeltPreview {
+            invoice,
+            payment_request,
+            method,
+            quote_id: quote.quote,
+            amount: quote.amount,
+            fee_reserve: quote.fee_reserve,
+            unit,
+            quote_expiry: quote.expiry,
+        })
+    }
+
+    pub async fn execute_melt_for_wallet(
+        &mut self,
+        wallet: &CashuWalletClient,
+        melt_request: MeltRequest,
+    ) -> Result<MeltResponse> {
+        self.execute_melt(&wallet.wallet, melt_request).await
+    }
+
+    pub async fn execute_melt_for_multimint(
+        &mut self,
+        multimint: &MultimintWallet,
+        mint_url: &str,
+        melt_request: MeltRequest,
+    ) -> Result<MeltResponse> {
+        let wallet = multimint
+            .get_wallet_for_mint(mint_url)
+            .await
+            .ok_or_else(|| {
+                Error::custom(&format!("Mint {} not found in multimint wallet", mint_url))
+            })?;
+        self.execute_melt(&wallet, melt_request).await
+    }
+
+    /// Pay a Lightning address or LNURL-pay link from `wallet`.
+    ///
+    /// Fetches the payee's pay parameters, requests an invoice for
+    /// `amount_msat` with the optional comment, and melts ecash to pay it.
+    pub async fn pay_lnurl(
+        &mut self,
+        wallet: &Wallet,
+        target: &str,
+        amount_msat: u64,
+        comment: Option<&str>,
+    ) -> Result<MeltResponse> {
+        let pay_request = self.lnurl.fetch_pay_request(target).await?;
+        let invoice = self
+            .lnurl
+            .request_invoice(&pay_request, amount_msat, comment)
+            .await?;
+
+        let preview = self
+            .prepare_melt(
+                &wallet.mint_url.to_string(),
+                &invoice.payment_request,
+                wallet.unit.clone(),
+                None,
+                PaymentMethod::Bolt11,
+            )
             .await?;
-        Ok(quote_response.quote)
+
+        self.execute_melt(
+            wallet,
+            MeltRequest {
+                quote_id: preview.quote_id,
+                payment_request: preview.payment_request,
+                amount: preview.amount,
+                unit: preview.unit,
+                proofs: Vec::new(),
+                method: preview.method,
+            },
+        )
+        .await
+    }
+
+    pub async fn pay_lnurl_for_wallet(
+        &mut self,
+        wallet: &CashuWalletClient,
+        target: &str,
+        amount_msat: u64,
+        comment: Option<&str>,
+    ) -> Result<MeltResponse> {
+        self.pay_lnurl(&wallet.wallet, target, amount_msat, comment)
+            .await
+    }
+
+    pub async fn pay_lnurl_for_multimint(
+        &mut self,
+        multimint: &MultimintWallet,
+        mint_url: &str,
+        target: &str,
+        amount_msat: u64,
+        comment: Option<&str>,
+    ) -> Result<MeltResponse> {
+        let wallet = multimint
+            .get_wallet_for_mint(mint_url)
+            .await
+            .ok_or_else(|| {
+                Error::custom(&format!("Mint {} not found in multimint wallet", mint_url))
+            })?;
+        self.pay_lnurl(&wallet, target, amount_msat, comment).await
     }
 
+    /// Pay a melt quote with proofs from `wallet` (NUT-05).
+    ///
+    /// Proofs covering the amount, fee reserve and input fees are marked
+    /// pending before they are sent, together with blank outputs for the fee
+    /// change (NUT-08). If the outcome is unknown the melt stays in
+    /// [`Self::list_pending_melts`] until [`Self::resolve_pending_melt`]
+    /// settles it.
     pub async fn execute_melt(
-        &self,
-        _mint_url: &str,
-        _melt_request: MeltRequest,
+        &mut self,
+        wallet: &Wallet,
+        melt_request: MeltRequest,
     ) -> Result<MeltResponse> {
-        Ok(MeltResponse {
-            success: false,
-            payment_preimage: None,
-            change_proofs: None,
-            fee_paid: 0,
-            message: "Melt operation not yet implemented with proper proof types".to_string(),
-        })
+        if melt_request.unit != wallet.unit {
+            return Err(Error::custom(&format!(
+                "Melt is in {} but the wallet holds {}",
+                melt_request.unit, wallet.unit
+            )));
+        }
+
+        if melt_request.method == PaymentMethod::Bolt11
+            && !melt_request.payment_request.is_empty()
+            && DecodedInvoice::parse(&melt_request.payment_request)?.is_expired()
+        {
+            return Err(Error::custom("Invoice has expired"));
+        }
+
+        let method = melt_request.method;
+        let mint_url = wallet.mint_url.to_string();
+        let mint_client = self.mint_client(&mint_url)?;
+        let quote = mint_client
+            .get_melt_quote_with_method(method, &melt_request.quote_id)
+            .await?;
+        if quote.is_paid() {
+            return Err(Error::custom(&format!(
+                "Melt quote {} is already paid",
+                quote.quote
+            )));
+        }
+
+        let mut available = wallet.get_unspent_proofs().await?;
+        if !melt_request.proofs.is_empty() {
+            available.retain(|proof| melt_request.proofs.contains(&proof.secret.to_string()));
+        }
+
+        let active_keyset_ids: Vec<Id> = wallet
+            .get_active_mint_keysets()
+            .await?
+            .into_iter()
+            .map(|keyset| keyset.id)
+            .collect();
+        let keyset_fees = wallet.get_keyset_fees().await?;
+        let proofs = Wallet::select_proofs(
+            Amount::from(quote.amount + quote.fee_reserve),
+            available,
+            &active_keyset_ids,
+            &keyset_fees,
+            true,
+        )?;
+
+        let blank_outputs = mint_client
+            .melt_change_outputs(method, &quote.quote, &proofs)
+            .await?;
+
+        set_proofs_state(wallet, &proofs, State::Pending).await?;
+        self.pending_melts.insert(
+            quote.quote.clone(),
+            PendingMelt {
+                mint_url: mint_url.clone(),
+                quote_id: quote.quote.clone(),
+                payment_request: melt_request.payment_request.clone(),
+                amount: quote.amount,
+                fee_reserve: quote.fee_reserve,
+                unit: wallet.unit.clone(),
+                proofs: proofs.clone(),
+                created_at: unix_now(),
+                method,
+                outputs: Some(StoredOutputs::from_premint(&blank_outputs)),
+            },
+        );
+        self.persist()?;
+
+        match mint_client
+            .melt_with_outputs(method, &quote.quote, proofs, &blank_outputs)
+            .await
+        {
+            Ok(result) if result.paid => {
+                self.finish_melt(wallet, &quote.quote, result.change.clone())
+                    .await?;
+
+                Ok(MeltResponse {
+                    success: true,
+                    payment_preimage: result.payment_preimage,
+                    change_proofs: Some(result.change),
+                    fee_paid: result.fee_paid,
+                    message: format!("Paid {} with {} fee", quote.amount, result.fee_paid),
+                    verification: result.verification,
+                })
+            }
+            Ok(_) => {
+                let state = self.resolve_pending_melt(wallet, &quote.quote).await?;
+                Ok(MeltResponse {
+                    success: false,
+                    payment_preimage: None,
+                    change_proofs: None,
+                    fee_paid: 0,
+                    message: format!("Melt quote {} is {}", quote.quote, state),
+                    verification: TokenVerification::default(),
+                })
+            }
+            Err(e) => {
+                // The mint may or may not have taken the proofs; settle from the quote state
+                let _ = self.resolve_pending_melt(wallet, &quote.quote).await;
+                Err(e)
+            }
+        }
+    }
+
+    /// Settle a tracked melt from the mint's quote state.
+    ///
+    /// A paid quote removes the locked proofs and stores the change the
+    /// quote reports, unblinded with the melt's blank outputs. An unpaid or
+    /// expired quote returns the proofs to the wallet, and anything else,
+    /// including a mint that cannot be reached, leaves the melt pending.
+    pub async fn resolve_pending_melt(
+        &mut self,
+        wallet: &Wallet,
+        quote_id: &str,
+    ) -> Result<InvoiceState> {
+        let pending = self
+            .pending_melts
+            .get(quote_id)
+            .cloned()
+            .ok_or_else(|| Error::custom("Pending melt not found"))?;
+
+        let mint_client = self.mint_client(&pending.mint_url)?;
+        let quote = mint_client
+            .get_melt_quote_with_method(pending.method, quote_id)
+            .await?;
+        let state = self.melt_quote_state(&quote);
+
+        match state {
+            InvoiceState::Paid => {
+                let change = match (&pending.outputs, quote.change) {
+                    (Some(outputs), Some(signatures)) => {
+                        mint_client
+                            .melt_change(&outputs.to_premint()?, signatures)
+                            .await?
+                            .proofs
+                    }
+                    _ => Vec::new(),
+                };
+                self.finish_melt(wallet, quote_id, change).await?
+            }
+            InvoiceState::Unpaid | InvoiceState::Expired => {
+                set_proofs_state(wallet, &pending.proofs, State::Unspent).await?;
+                self.pending_melts.remove(quote_id);
+                self.persist()?;
+            }
+            InvoiceState::Pending | InvoiceState::Issued | InvoiceState::Failed => {}
+        }
+
+        Ok(state)
+    }
+
+    pub fn list_pending_melts(&self) -> Vec<&PendingMelt> {
+        self.pending_melts.values().collect()
     }
 
+    async fn finish_melt(&mut self, wallet: &Wallet, quote_id: &str, change: Proofs) -> Result<()> {
+        let spent_ys = match self.pending_melts.remove(quote_id) {
+            Some(pending) => pending
+                .proofs
+                .iter()
+                .map(|proof| proof.y())
+                .collect::<std::result::Result<Vec<_>, _>>()
+                .map_err(|e| Error::custom(&format!("Invalid proof: {}", e)))?,
+            None => Vec::new(),
+        };
+
+        let change = change
+            .into_iter()
+            .map(|proof| {
+                ProofInfo::new(
+                    proof,
+                    wallet.mint_url.clone(),
+                    State::Unspent,
+                    wallet.unit.clone(),
+                )
+            })
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        wallet
+            .localstore
+            .update_proofs(change, spent_ys)
+            .await
+            .map_err(|e| Error::custom(&format!("Failed to update proofs: {}", e)))?;
+        self.persist()
+    }
+
+    /// The state of a melt quote at its mint; fails if the mint cannot tell
     pub async fn check_melt_quote_state(
         &self,
         mint_url: &str,
         quote_id: &str,
+        method: PaymentMethod,
     ) -> Result<InvoiceState> {
-        let mint_client = MintClient::new(mint_url)?;
-
-        match mint_client.get_melt_quote(quote_id).await {
-            Ok(quote) => {
-                if quote.paid {
-                    Ok(InvoiceState::Paid)
-                } else if self.is_expired(quote.expiry) {
-                    Ok(InvoiceState::Expired)
-                } else {
-                    Ok(InvoiceState::Unpaid)
-                }
-            }
-            Err(_) => Ok(InvoiceState::Failed),
+        let quote = self
+            .mint_client(mint_url)?
+            .get_melt_quote_with_method(method, quote_id)
+            .await?;
+        Ok(self.melt_quote_state(&quote))
+    }
+
+    /// The state of a melt quote. Only an explicitly unpaid quote is
+    /// reported unpaid or expired; an unknown state counts as pending, so
+    /// proofs that may be in flight are never released.
+    fn melt_quote_state(&self, quote: &PostMeltQuoteResponse) -> InvoiceState {
+        if quote.is_paid() {
+            return InvoiceState::Paid;
+        }
+        match quote.state.as_deref() {
+            // Mints before NUT-05 states only report `paid`
+            Some("UNPAID") | None if self.is_expired(quote.expiry) => InvoiceState::Expired,
+            Some("UNPAID") | None => InvoiceState::Unpaid,
+            _ => InvoiceState::Pending,
         }
     }
 
+    /// Mint the proofs of a paid invoice into `wallet`.
+    ///
+    /// Completing an invoice that was already minted succeeds without
+    /// minting again and reports nothing received.
     pub async fn complete_topup(
         &mut self,
         quote_id: &str,
-        _wallet: &mut CashuWalletClient,
+        wallet: &mut CashuWalletClient,
+    ) -> Result<TopupResult> {
+        self.complete_topup_into(quote_id, &wallet.wallet).await
+    }
+
+    pub async fn complete_topup_for_multimint(
+        &mut self,
+        quote_id: &str,
+        multimint: &MultimintWallet,
+    ) -> Result<TopupResult> {
+        let mint_url = self
+            .pending_invoices
+            .get(quote_id)
+            .map(|invoice| invoice.mint_url.clone())
+            .ok_or_else(|| Error::custom("Invoice not found"))?;
+
+        let wallet = multimint
+            .get_wallet_for_mint(&mint_url)
+            .await
+            .ok_or_else(|| {
+                Error::custom(&format!("Mint {} not found in multimint wallet", mint_url))
+            })?;
+
+        self.complete_topup_into(quote_id, &wallet).await
+    }
+
+    async fn complete_topup_into(
+        &mut self,
+        quote_id: &str,
+        wallet: &Wallet,
     ) -> Result<TopupResult> {
         let invoice = self
             .pending_invoices
             .get(quote_id)
+            .cloned()
             .ok_or_else(|| Error::custom("Invoice not found"))?;
 
-        if invoice.state != InvoiceState::Paid {
-            return Err(Error::custom("Invoice is not paid"));
+        if invoice.unit != wallet.unit {
+            return Err(Error::custom(&format!(
+                "Invoice is in {} but the wallet holds {}",
+                invoice.unit, wallet.unit
+            )));
+        }
+
+        let mint_client = self.mint_client(&invoice.mint_url)?;
+
+        // An earlier attempt sent these outputs but never stored their proofs
+        if let Some(outputs) = &invoice.outputs {
+            let premint = outputs.to_premint()?;
+            let keys = mint_client.get_keys_for_keyset(&outputs.keyset_id).await?;
+            let restored = mint_client.restore_proofs(&premint, &keys).await?;
+            let proofs = SignedProofs {
+                proofs: restored
+                    .proofs
+                    .into_iter()
+                    .map(|(_, proof)| proof)
+                    .collect(),
+                verification: restored.verification,
+            };
+
+            if !proofs.proofs.is_empty() {
+                let state = match invoice.method {
+                    PaymentMethod::Bolt11 => InvoiceState::Issued,
+                    PaymentMethod::Bolt12 => InvoiceState::Unpaid,
+                };
+                return self.store_topup(&invoice, wallet, proofs, state).await;
+            }
+            self.set_invoice_outputs(quote_id, None)?;
+        }
+
+        let state = match invoice.state {
+            InvoiceState::Paid | InvoiceState::Issued => invoice.state.clone(),
+            _ => self.check_payment_status(quote_id).await?.state,
+        };
+
+        match state {
+            InvoiceState::Paid => {}
+            InvoiceState::Issued => return Ok(Self::already_issued(&invoice)),
+            state => return Err(Error::custom(&format!("Invoice is {}", state))),
+        }
+
+        let (amount, signing_key, fully_issued) = match invoice.method {
+            PaymentMethod::Bolt11 => (invoice.amount, None, true),
+            PaymentMethod::Bolt12 => {
+                let quote = mint_client.get_bolt12_mint_quote(quote_id).await?;
+                let signing_key = invoice
+                    .signing_key
+                    .as_deref()
+                    .map(SecretKey::from_hex)
+                    .transpose()
+                    .map_err(|e| Error::custom(&format!("Invalid quote signing key: {}", e)))?;
+                // A reusable offer keeps accepting payments until its amount is minted
+                let fully_issued = invoice.amount > 0 && quote.amount_paid >= invoice.amount;
+                (quote.mintable_amount(), signing_key, fully_issued)
+            }
+        };
+        if amount == 0 {
+            return Ok(Self::already_issued(&invoice));
         }
 
+        let keyset = mint_client.get_active_keyset(&invoice.unit).await?;
+        let keys = mint_client.get_keys_for_keyset(&keyset.id).await?;
+        let premint = crypto::create_blinded_messages_for_amount(
+            amount,
+            &keyset.id,
+            &crypto::keyset_denominations(&keys),
+        )?;
+        // Persist the outputs before the mint signs them, so a topup cut off
+        // before its proofs are stored can still restore them
+        self.set_invoice_outputs(quote_id, Some(StoredOutputs::from_premint(&premint)))?;
+
+        let proofs = match mint_client
+            .mint_outputs_with_method(
+                invoice.method,
+                quote_id,
+                &premint,
+                &keys,
+                signing_key.as_ref(),
+            )
+            .await
+        {
+            Ok(proofs) => proofs,
+            Err(e) if e.mint_error().is_some_and(|e| e.is_tokens_already_issued()) => {
+                self.set_invoice_outputs(quote_id, None)?;
+                self.update_invoice_state(quote_id, InvoiceState::Issued)?;
+                return Ok(Self::already_issued(&invoice));
+            }
+            Err(e) => return Err(e),
+        };
+
+        let state = if fully_issued {
+            InvoiceState::Issued
+        } else {
+            InvoiceState::Unpaid
+        };
+        self.store_topup(&invoice, wallet, proofs, state).await
+    }
+
+    /// Store minted proofs in `wallet`, then forget the outputs they came from
+    async fn store_topup(
+        &mut self,
+        invoice: &LightningInvoice,
+        wallet: &Wallet,
+        proofs: SignedProofs,
+        state: InvoiceState,
+    ) -> Result<TopupResult> {
+        let SignedProofs {
+            proofs,
+            verification,
+        } = proofs;
+        let amount_received: u64 = proofs.iter().map(|proof| u64::from(proof.amount)).sum();
+        let proofs = proofs
+            .into_iter()
+            .map(|proof| {
+                ProofInfo::new(
+                    proof,
+                    wallet.mint_url.clone(),
+                    State::Unspent,
+                    wallet.unit.clone(),
+                )
+            })
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        wallet
+            .localstore
+            .update_proofs(proofs, Vec::new())
+            .await
+            .map_err(|e| Error::custom(&format!("Failed to store minted proofs: {}", e)))?;
+
+        if let Some(stored) = self.pending_invoices.get_mut(&invoice.quote_id) {
+            stored.outputs = None;
+            stored.state = state;
+        }
+        self.persist()?;
+
         Ok(TopupResult {
             success: true,
-            amount_received: invoice.amount,
+            amount_received,
             fee_paid: 0,
             mint_url: invoice.mint_url.clone(),
             message: format!(
-                "Invoice {} is ready for minting. Use the mint client to complete the minting process.",
-                quote_id
+                "Minted {} {} from invoice {}",
+                amount_received, invoice.unit, invoice.quote_id
             ),
+            verification,
         })
     }
 
+    fn set_invoice_outputs(
+        &mut self,
+        quote_id: &str,
+        outputs: Option<StoredOutputs>,
+    ) -> Result<()> {
+        if let Some(invoice) = self.pending_invoices.get_mut(quote_id) {
+            invoice.outputs = outputs;
+        }
+        self.persist()
+    }
+
+    fn already_issued(invoice: &LightningInvoice) -> TopupResult {
+        TopupResult {
+            success: true,
+            amount_received: 0,
+            fee_paid: 0,
+            mint_url: invoice.mint_url.clone(),
+            message: format!("Invoice {} was already minted", invoice.quote_id),
+            verification: TokenVerification::default(),
+        }
+    }
+
     pub fn list_pending_invoices(&self) -> Vec<&LightningInvoice> {
         self.pending_invoices.values().collect()
     }
@@ -305,46 +1189,110 @@ impl LightningManager {
         self.pending_invoices.get(quote_id)
     }
 
-    pub fn cleanup_expired_invoices(&mut self) {
+    pub fn cleanup_expired_invoices(&mut self) -> Result<()> {
         let expired_quotes: Vec<String> = self
             .pending_invoices
             .iter()
-            .filter(|(_, invoice)| self.is_expired(invoice.expiry))
+            // Outputs of an interrupted topup are kept until they are restored
+            .filter(|(_, invoice)| self.is_expired(invoice.expiry) && invoice.outputs.is_none())
             .map(|(quote_id, _)| quote_id.clone())
             .collect();
 
         for quote_id in expired_quotes {
             self.pending_invoices.remove(&quote_id);
         }
+        self.persist()
+    }
+
+    /// Stream state changes of a pending invoice's mint quote (NUT-17)
+    pub fn subscribe_to_invoice(
+        &self,
+        quote_id: &str,
+        poll_interval: Duration,
+    ) -> Result<StateStream> {
+        let invoice = self
+            .pending_invoices
+            .get(quote_id)
+            .ok_or_else(|| Error::custom("Invoice not found"))?;
+
+        Ok(self.mint_client(&invoice.mint_url)?.subscribe(
+            SubscriptionKind::mint_quote(invoice.method),
+            vec![quote_id.to_string()],
+            poll_interval,
+        ))
+    }
+
+    /// Stream state changes of a melt quote (NUT-17)
+    pub fn subscribe_to_melt_quote(
+        &self,
+        mint_url: &str,
+        quote_id: &str,
+        method: PaymentMethod,
+        poll_interval: Duration,
+    ) -> Result<StateStream> {
+        Ok(self.mint_client(mint_url)?.subscribe(
+            SubscriptionKind::melt_quote(method),
+            vec![quote_id.to_string()],
+            poll_interval,
+        ))
     }
 
+    /// Wait until the invoice is paid, it expires or `timeout_seconds` pass.
+    ///
+    /// Uses the mint's WebSocket when available and no proxy is configured,
+    /// and polls every `poll_interval_seconds` otherwise.
     pub async fn wait_for_payment(
         &mut self,
         quote_id: &str,
         timeout_seconds: u64,
         poll_interval_seconds: u64,
     ) -> Result<bool> {
-        let start_time = Instant::now();
-        let timeout = Duration::from_secs(timeout_seconds);
-        let poll_interval = Duration::from_secs(poll_interval_seconds);
-
-        while start_time.elapsed() < timeout {
-            let status = self.check_payment_status(quote_id).await?;
-            if status.state == InvoiceState::Paid {
-                return Ok(true);
-            }
-            if status.state == InvoiceState::Expired || status.state == InvoiceState::Failed {
-                return Ok(false);
-            }
+        let mut updates =
+            self.subscribe_to_invoice(quote_id, Duration::from_secs(poll_interval_seconds))?;
+        let expiry = self
+            .pending_invoices
+            .get(quote_id)
+            .map(|invoice| invoice.expiry)
+            .unwrap_or_default();
 
-            tokio::time::sleep(poll_interval).await;
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let until_expiry = Duration::from_secs(expiry.saturating_sub(now) + 1);
+        let deadline =
+            tokio::time::Instant::now() + Duration::from_secs(timeout_seconds).min(until_expiry);
+
+        loop {
+            match tokio::time::timeout_at(deadline, updates.next()).await {
+                Ok(Some(Ok(StateUpdate::MintQuote(quote)))) if quote.is_paid() => {
+                    if self.get_invoice_state(quote_id) != Some(InvoiceState::Issued) {
+                        self.update_invoice_state(quote_id, InvoiceState::Paid)?;
+                    }
+                    return Ok(true);
+                }
+                Ok(Some(Ok(StateUpdate::Bolt12MintQuote(quote))))
+                    if quote.mintable_amount() > 0 =>
+                {
+                    self.update_invoice_state(quote_id, InvoiceState::Paid)?;
+                    return Ok(true);
+                }
+                Ok(Some(Ok(_))) => continue,
+                Ok(Some(Err(e))) => return Err(e),
+                Ok(None) | Err(_) => break,
+            }
         }
 
+        if self.is_expired(expiry) {
+            self.update_invoice_state(quote_id, InvoiceState::Expired)?;
+        }
         Ok(false)
     }
 
-    pub fn remove_invoice(&mut self, quote_id: &str) -> Option<LightningInvoice> {
-        self.pending_invoices.remove(quote_id)
+    pub fn remove_invoice(&mut self, quote_id: &str) -> Result<Option<LightningInvoice>> {
+        let invoice = self.pending_invoices.remove(quote_id);
+        self.persist()?;
+        Ok(invoice)
     }
 
     pub fn get_payment_request(&self, quote_id: &str) -> Option<&str> {
@@ -374,7 +1322,7 @@ impl LightningManager {
     pub fn update_invoice_state(&mut self, quote_id: &str, state: InvoiceState) -> Result<()> {
         if let Some(invoice) = self.pending_invoices.get_mut(quote_id) {
             invoice.state = state;
-            Ok(())
+            self.persist()
         } else {
             Err(Error::custom("Invoice not found"))
         }
@@ -400,8 +1348,198 @@ impl LightningManager {
     }
 }
 
+fn invoice_kind(method: PaymentMethod) -> &'static str {
+    match method {
+        PaymentMethod::Bolt11 => "invoice",
+        PaymentMethod::Bolt12 => "offer",
+    }
+}
+
+fn ensure_method(
+    advertised: &[PaymentMethod],
+    method: PaymentMethod,
+    operation: &str,
+    unit: &CurrencyUnit,
+    mint_url: &str,
+) -> Result<()> {
+    if advertised.contains(&method) {
+        Ok(())
+    } else {
+        Err(Error::custom(&format!(
+            "Mint {} does not support {} {} for {}",
+            mint_url, operation, method, unit
+        )))
+    }
+}
+
+async fn set_proofs_state(wallet: &Wallet, proofs: &Proofs, state: State) -> Result<()> {
+    let ys = proofs
+        .iter()
+        .map(|proof| proof.y())
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(|e| Error::custom(&format!("Invalid proof: {}", e)))?;
+
+    wallet
+        .localstore
+        .update_proofs_state(ys, state)
+        .await
+        .map_err(|e| Error::custom(&format!("Failed to update proof state: {}", e)))
+}
+
+pub(crate) fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
 impl Default for LightningManager {
     fn default() -> Self {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mint::MintClientConfig;
+
+    // Nothing listens on the discard port, so every request fails fast
+    const UNREACHABLE_MINT: &str = "http://127.0.0.1:9";
+
+    fn invoice(mint_url: &str, quote_id: &str, state: InvoiceState) -> LightningInvoice {
+        LightningInvoice {
+            mint_url: mint_url.to_string(),
+            quote_id: quote_id.to_string(),
+            payment_request: String::new(),
+            amount: 100,
+            unit: CurrencyUnit::Sat,
+            expiry: u64::MAX,
+            state,
+            fee_reserve: None,
+            created_at: 0,
+            method: PaymentMethod::Bolt11,
+            signing_key: None,
+            outputs: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn resume_pending_reports_failures_and_drops_finished_invoices() {
+        let dir = std::env::temp_dir().join(format!(
+            "resume-pending-{}",
+            crypto::generate_random_secret()
+        ));
+        let transport = MintTransport::new(MintClientConfig {
+            max_retries: 0,
+            ..Default::default()
+        })
+        .unwrap();
+        let seed = bip39::Mnemonic::generate(12).unwrap().to_string();
+        let wallet = CashuWalletClient::builder(UNREACHABLE_MINT, &seed)
+            .unit(CurrencyUnit::Sat)
+            .transport(transport.clone())
+            .in_memory()
+            .resume_pending(false)
+            .build()
+            .await
+            .unwrap();
+        let mint_url = wallet.wallet.mint_url.to_string();
+
+        let store = QuoteStore::new(dir.join("quotes.json"));
+        store
+            .save(&StoredQuotes {
+                invoices: vec![
+                    invoice(&mint_url, "issued", InvoiceState::Issued),
+                    invoice(&mint_url, "unpaid", InvoiceState::Unpaid),
+                ],
+                melts: Vec::new(),
+            })
+            .unwrap();
+
+        let mut manager = LightningManager::with_store(transport, store.clone()).unwrap();
+        let report = manager.resume_pending_for_wallet(&wallet).await.unwrap();
+
+        assert!(report.topups.is_empty());
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].quote_id, "unpaid");
+
+        let stored = store.load().unwrap();
+        assert_eq!(stored.invoices.len(), 1);
+        assert_eq!(stored.invoices[0].quote_id, "unpaid");
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    fn melt_quote(state: Option<&str>, expiry: u64) -> PostMeltQuoteResponse {
+        PostMeltQuoteResponse {
+            quote: "melt".to_string(),
+            amount: 100,
+            fee_reserve: 2,
+            paid: false,
+            state: state.map(str::to_string),
+            expiry,
+            payment_preimage: None,
+            change: None,
+        }
+    }
+
+    #[test]
+    fn only_explicitly_unpaid_melt_quotes_release_their_proofs() {
+        let manager = LightningManager::new();
+        let state = |state, expiry| manager.melt_quote_state(&melt_quote(state, expiry));
+
+        assert_eq!(state(Some("PAID"), u64::MAX), InvoiceState::Paid);
+        assert_eq!(state(Some("PENDING"), u64::MAX), InvoiceState::Pending);
+        assert_eq!(state(Some("UNKNOWN"), 0), InvoiceState::Pending);
+        assert_eq!(state(Some("UNPAID"), u64::MAX), InvoiceState::Unpaid);
+        assert_eq!(state(Some("UNPAID"), 0), InvoiceState::Expired);
+        assert_eq!(state(None, u64::MAX), InvoiceState::Unpaid);
+    }
+
+    #[tokio::test]
+    async fn unreachable_mint_leaves_the_melt_pending() {
+        let transport = MintTransport::new(MintClientConfig {
+            max_retries: 0,
+            ..Default::default()
+        })
+        .unwrap();
+        let seed = bip39::Mnemonic::generate(12).unwrap().to_string();
+        let wallet = CashuWalletClient::builder(UNREACHABLE_MINT, &seed)
+            .unit(CurrencyUnit::Sat)
+            .transport(transport.clone())
+            .in_memory()
+            .resume_pending(false)
+            .build()
+            .await
+            .unwrap();
+        let mint_url = wallet.wallet.mint_url.to_string();
+
+        let mut manager = LightningManager::with_transport(transport);
+        manager.pending_melts.insert(
+            "melt".to_string(),
+            PendingMelt {
+                mint_url: mint_url.clone(),
+                quote_id: "melt".to_string(),
+                payment_request: String::new(),
+                amount: 100,
+                fee_reserve: 2,
+                unit: CurrencyUnit::Sat,
+                proofs: Vec::new(),
+                created_at: 0,
+                method: PaymentMethod::Bolt11,
+                outputs: None,
+            },
+        );
+
+        assert!(manager
+            .check_melt_quote_state(&mint_url, "melt", PaymentMethod::Bolt11)
+            .await
+            .is_err());
+        assert!(manager
+            .resolve_pending_melt(&wallet.wallet, "melt")
+            .await
+            .is_err());
+        assert_eq!(manager.list_pending_melts().len(), 1);
+    }
+}
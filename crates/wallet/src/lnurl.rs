@@ -0,0 +1,407 @@
+use crate::bolt11::DecodedInvoice;
+use crate::error::{Error, Result};
+use crate::mint::MintTransport;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// LNURL-pay parameters served by a payee (LUD-06)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PayRequest {
+    pub callback: String,
+    /// Smallest amount the payee accepts, in msat
+    pub min_sendable: u64,
+    /// Largest amount the payee accepts, in msat
+    pub max_sendable: u64,
+    /// JSON-encoded metadata; the invoice description hash commits to it
+    pub metadata: String,
+    /// Longest comment the payee accepts (LUD-12), absent when comments are not allowed
+    #[serde(default)]
+    pub comment_allowed: Option<usize>,
+    pub tag: String,
+}
+
+impl PayRequest {
+    /// The `text/plain` description from the metadata, if any
+    pub fn description(&self) -> Option<String> {
+        let entries: Vec<(String, serde_json::Value)> =
+            serde_json::from_str(&self.metadata).ok()?;
+        entries
+            .into_iter()
+            .find(|(kind, _)| kind == "text/plain")
+            .and_then(|(_, value)| value.as_str().map(str::to_string))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ErrorResponse {
+    status: String,
+    reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct InvoiceResponse {
+    pr: String,
+}
+
+/// Resolves Lightning addresses and LNURL-pay links into invoices
+#[derive(Debug, Clone)]
+pub struct LnurlClient {
+    transport: MintTransport,
+    base_url: Option<String>,
+}
+
+impl LnurlClient {
+    pub fn new(transport: MintTransport) -> Self {
+        Self {
+            transport,
+            base_url: None,
+        }
+    }
+
+    /// Send the requests for Lightning addresses to `base_url` instead of
+    /// the address's domain, e.g. to a local LNURL server in tests
+    pub fn with_base_url(mut self, base_url: &str) -> Self {
+        self.base_url = Some(base_url.trim_end_matches('/').to_string());
+        self
+    }
+
+    /// The LNURL-pay endpoint for `target`; see [`Self::resolve_url`]
+    pub fn pay_url(&self, target: &str) -> Result<String> {
+        let url = Self::resolve_url(target)?;
+        let Some(base_url) = &self.base_url else {
+            return Ok(url);
+        };
+        if !target.contains('@') {
+            return Ok(url);
+        }
+
+        let parsed =
+            url::Url::parse(&url).map_err(|e| Error::custom(&format!("Invalid LNURL: {}", e)))?;
+        Ok(format!("{}{}", base_url, parsed.path()))
+    }
+
+    /// The LNURL-pay endpoint for a Lightning address (`name@domain`, LUD-16),
+    /// a bech32 `lnurl1...` string or a plain `https://` or `lnurlp://` URL.
+    ///
+    /// Plain HTTP is used for `.onion`, `localhost` and `127.0.0.1` hosts.
+    pub fn resolve_url(target: &str) -> Result<String> {
+        let target = target.trim();
+        let target = target
+            .strip_prefix("lightning:")
+            .or_else(|| target.strip_prefix("LIGHTNING:"))
+            .unwrap_or(target);
+
+        if let Some((name, domain)) = target.split_once('@') {
+            if name.is_empty() || domain.is_empty() || domain.contains('/') {
+                return Err(Error::custom(&format!(
+                    "Invalid Lightning address: {}",
+                    target
+                )));
+            }
+            return Ok(format!(
+                "{}://{}/.well-known/lnurlp/{}",
+                scheme_for(domain),
+                domain,
+                name.to_lowercase()
+            ));
+        }
+
+        if target.to_lowercase().starts_with("lnurl1") {
+            let (hrp, data) = bech32::decode(target)
+                .map_err(|e| Error::custom(&format!("Invalid LNURL: {}", e)))?;
+            if hrp.to_lowercase() != "lnurl" {
+                return Err(Error::custom(&format!("Invalid LNURL prefix: {}", hrp)));
+            }
+            return String::from_utf8(data)
+                .map_err(|e| Error::custom(&format!("Invalid LNURL: {}", e)));
+        }
+
+        if let Some(rest) = target.strip_prefix("lnurlp://") {
+            let host = rest.split(['/', ':']).next().unwrap_or_default();
+            return Ok(format!("{}://{}", scheme_for(host), rest));
+        }
+
+        if target.starts_with("https://") || target.starts_with("http://") {
+            return Ok(target.to_string());
+        }
+
+        Err(Error::custom(&format!(
+            "Not a Lightning address or LNURL: {}",
+            target
+        )))
+    }
+
+    /// Fetch the pay parameters for a Lightning address or LNURL-pay link
+    pub async fn fetch_pay_request(&self, target: &str) -> Result<PayRequest> {
+        let url = self.pay_url(target)?;
+        let body = self.get_json(&url).await?;
+
+        let pay_request: PayRequest = serde_json::from_value(body)
+            .map_err(|e| Error::custom(&format!("Invalid LNURL-pay response: {}", e)))?;
+        if pay_request.tag != "payRequest" {
+            return Err(Error::custom(&format!(
+                "LNURL is a {}, not a payRequest",
+                pay_request.tag
+            )));
+        }
+        if pay_request.min_sendable > pay_request.max_sendable {
+            return Err(Error::custom("LNURL-pay min sendable exceeds max sendable"));
+        }
+
+        Ok(pay_request)
+    }
+
+    /// Ask the payee for an invoice of `amount_msat`, with an optional comment.
+    ///
+    /// The amount must be within the payee's limits, and the returned invoice
+    /// must be for that amount and commit to the payee's metadata.
+    pub async fn request_invoice(
+        &self,
+        pay_request: &PayRequest,
+        amount_msat: u64,
+        comment: Option<&str>,
+    ) -> Result<DecodedInvoice> {
+        if amount_msat < pay_request.min_sendable || amount_msat > pay_request.max_sendable {
+            return Err(Error::custom(&format!(
+                "Amount {} msat is outside the payee's range of {} to {} msat",
+                amount_msat, pay_request.min_sendable, pay_request.max_sendable
+            )));
+        }
+
+        let mut url = url::Url::parse(&pay_request.callback)
+            .map_err(|e| Error::custom(&format!("Invalid LNURL callback: {}", e)))?;
+        url.query_pairs_mut()
+            .append_pair("amount", &amount_msat.to_string());
+
+        if let Some(comment) = comment.filter(|comment| !comment.is_empty()) {
+            match pay_request.comment_allowed {
+                Some(max) if comment.chars().count() <= max => {
+                    url.query_pairs_mut().append_pair("comment", comment);
+                }
+                Some(max) => {
+                    return Err(Error::custom(&format!(
+                        "Comment is longer than the {} characters the payee accepts",
+                        max
+                    )))
+                }
+                None => return Err(Error::custom("Payee does not accept comments")),
+            }
+        }
+
+        let body = self.get_json(url.as_str()).await?;
+        let response: InvoiceResponse = serde_json::from_value(body)
+            .map_err(|e| Error::custom(&format!("Invalid LNURL invoice response: {}", e)))?;
+
+        let invoice = DecodedInvoice::parse(&response.pr)?;
+        if invoice.amount_msat != Some(amount_msat) {
+            return Err(Error::custom(&format!(
+                "Payee returned an invoice for {:?} msat instead of {} msat",
+                invoice.amount_msat, amount_msat
+            )));
+        }
+
+        let metadata_hash = hex::encode(Sha256::digest(pay_request.metadata.as_bytes()));
+        if invoice.description_hash.as_deref() != Some(metadata_hash.as_str()) {
+            return Err(Error::custom(
+                "Payee invoice does not commit to the LNURL metadata",
+            ));
+        }
+
+        Ok(invoice)
+    }
+
+    async fn get_json(&self, url: &str) -> Result<serde_json::Value> {
+        let response = self
+            .transport
+            .http_client()
+            .get(url)
+            .send()
+            .await
+            .map_err(|e| Error::custom(&format!("LNURL request failed: {}", e)))?;
+
+        if !response.status().is_success() {
+            return Err(Error::custom(&format!(
+                "LNURL request failed with status: {}",
+                response.status()
+            )));
+        }
+
+        let body: serde_json::Value = response
+            .json()
+            .await
+            .map_err(|e| Error::custom(&format!("Failed to parse LNURL response: {}", e)))?;
+
+        if let Ok(error) = serde_json::from_value::<ErrorResponse>(body.clone()) {
+            if error.status.eq_ignore_ascii_case("ERROR") {
+                return Err(Error::custom(&format!(
+                    "LNURL error: {}",
+                    error.reason.unwrap_or_default()
+                )));
+            }
+        }
+
+        Ok(body)
+    }
+}
+
+fn scheme_for(host: &str) -> &'static str {
+    let host = host.split(':').next().unwrap_or_default();
+    if host.ends_with(".onion") || host == "localhost" || host == "127.0.0.1" {
+        "http"
+    } else {
+        "https"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::hashes::{sha256, Hash};
+    use bitcoin::secp256k1::{Secp256k1, SecretKey};
+    use cdk::lightning_invoice::{Currency, InvoiceBuilder, PaymentSecret};
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+
+    const METADATA: &str = r#"[["text/plain","Coffee for alice"]]"#;
+
+    fn invoice(amount_msat: u64, metadata: &str) -> String {
+        let key = SecretKey::from_slice(&[0x11; 32]).unwrap();
+        InvoiceBuilder::new(Currency::Regtest)
+            .description_hash(sha256::Hash::hash(metadata.as_bytes()))
+            .payment_hash(sha256::Hash::hash(&amount_msat.to_be_bytes()))
+            .payment_secret(PaymentSecret([7; 32]))
+            .current_timestamp()
+            .min_final_cltv_expiry_delta(144)
+            .amount_milli_satoshis(amount_msat)
+            .build_signed(|hash| Secp256k1::new().sign_ecdsa_recoverable(hash, &key))
+            .unwrap()
+            .to_string()
+    }
+
+    /// A minimal LNURL server for `alice` and, with a mismatched invoice
+    /// description hash, `mallory`; returns its base URL
+    fn serve_lnurl() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let callback_base = base_url.clone();
+
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut request_line = String::new();
+                BufReader::new(&stream)
+                    .read_line(&mut request_line)
+                    .unwrap();
+                let path = request_line.split(' ').nth(1).unwrap_or_default();
+                let url = url::Url::parse(&format!("{}{}", callback_base, path)).unwrap();
+                let amount = url
+                    .query_pairs()
+                    .find(|(key, _)| key == "amount")
+                    .and_then(|(_, value)| value.parse::<u64>().ok());
+
+                let body = match (url.path(), amount) {
+                    ("/.well-known/lnurlp/alice", _) | ("/.well-known/lnurlp/mallory", _) => {
+                        let name = url.path().rsplit('/').next().unwrap();
+                        serde_json::json!({
+                            "callback": format!("{}/callback/{}", callback_base, name),
+                            "minSendable": 1_000,
+                            "maxSendable": 100_000,
+                            "metadata": METADATA,
+                            "commentAllowed": 32,
+                            "tag": "payRequest",
+                        })
+                    }
+                    ("/callback/alice", Some(amount)) => {
+                        serde_json::json!({ "pr": invoice(amount, METADATA), "routes": [] })
+                    }
+                    ("/callback/mallory", Some(amount)) => {
+                        serde_json::json!({ "pr": invoice(amount, "[]"), "routes": [] })
+                    }
+                    _ => serde_json::json!({ "status": "ERROR", "reason": "not found" }),
+                }
+                .to_string();
+
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+            }
+        });
+
+        base_url
+    }
+
+    fn client(base_url: &str) -> LnurlClient {
+        LnurlClient::new(MintTransport::shared()).with_base_url(base_url)
+    }
+
+    #[tokio::test]
+    async fn pays_lightning_address_through_local_server() {
+        let lnurl = client(&serve_lnurl());
+
+        let pay_request = lnurl.fetch_pay_request("alice@example.com").await.unwrap();
+        assert_eq!(pay_request.min_sendable, 1_000);
+        assert_eq!(pay_request.max_sendable, 100_000);
+        assert_eq!(
+            pay_request.description().as_deref(),
+            Some("Coffee for alice")
+        );
+
+        let invoice = lnurl
+            .request_invoice(&pay_request, 21_000, Some("thanks"))
+            .await
+            .unwrap();
+        assert_eq!(invoice.amount_msat, Some(21_000));
+        assert_eq!(
+            invoice.description_hash,
+            Some(hex::encode(Sha256::digest(METADATA.as_bytes())))
+        );
+    }
+
+    #[tokio::test]
+    async fn rejects_amounts_outside_payee_range() {
+        let lnurl = client(&serve_lnurl());
+        let pay_request = lnurl.fetch_pay_request("alice@example.com").await.unwrap();
+
+        for amount_msat in [999, 100_001] {
+            let err = lnurl
+                .request_invoice(&pay_request, amount_msat, None)
+                .await
+                .unwrap_err();
+            assert!(err.to_string().contains("outside the payee's range"));
+        }
+    }
+
+    #[tokio::test]
+    async fn rejects_invoice_not_committing_to_metadata() {
+        let lnurl = client(&serve_lnurl());
+        let pay_request = lnurl
+            .fetch_pay_request("mallory@example.com")
+            .await
+            .unwrap();
+
+        let err = lnurl
+            .request_invoice(&pay_request, 21_000, None)
+            .await
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("does not commit to the LNURL metadata"));
+    }
+
+    #[test]
+    fn base_url_only_replaces_lightning_address_host() {
+        let lnurl = client("http://127.0.0.1:8080/");
+        assert_eq!(
+            lnurl.pay_url("Alice@example.com").unwrap(),
+            "http://127.0.0.1:8080/.well-known/lnurlp/alice"
+        );
+        assert_eq!(
+            lnurl.pay_url("https://pay.example.com/lnurlp/bob").unwrap(),
+            "https://pay.example.com/lnurlp/bob"
+        );
+    }
+}
@@ -1,11 +1,24 @@
-use crate::error::{Error, Result};
+use crate::crypto;
+use crate::dleq::{self, DleqPolicy, TokenVerification};
+use crate::error::{Error, MintError, Result};
+use crate::subscription::{self, StateStream, SubscriptionKind};
 use cdk::mint_url::MintUrl;
-use cdk::nuts::{BlindSignature, BlindedMessage, CurrencyUnit, Proof};
-use reqwest::Client;
+use cdk::nuts::{
+    BlindSignature, BlindedMessage, CurrencyUnit, Keys, MeltOptions, MintRequest, PreMintSecrets,
+    Proof, Proofs, PublicKey, SecretKey, State,
+};
+use cdk::Amount;
+use reqwest::{Client, RequestBuilder, Response, StatusCode};
 use serde::{Deserialize, Serialize};
 use serde_json;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
 use std::str::FromStr;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+/// Proofs per NUT-07 state check request
+pub const CHECK_STATE_BATCH_SIZE: usize = 100;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GetInfoResponse {
@@ -19,6 +32,92 @@ pub struct GetInfoResponse {
     pub nuts: HashMap<String, serde_json::Value>,
 }
 
+impl GetInfoResponse {
+    /// Payment methods the mint advertises for minting `unit` (NUT-04)
+    pub fn mint_methods(&self, unit: &CurrencyUnit) -> Vec<PaymentMethod> {
+        self.advertised_methods("4", unit)
+    }
+
+    /// Payment methods the mint advertises for melting `unit` (NUT-05)
+    pub fn melt_methods(&self, unit: &CurrencyUnit) -> Vec<PaymentMethod> {
+        self.advertised_methods("5", unit)
+    }
+
+    fn advertised_methods(&self, nut: &str, unit: &CurrencyUnit) -> Vec<PaymentMethod> {
+        let Some(settings) = self.nuts.get(nut) else {
+            // Mints that predate the settings only speak bolt11
+            return vec![PaymentMethod::Bolt11];
+        };
+        if settings
+            .get("disabled")
+            .and_then(|disabled| disabled.as_bool())
+            == Some(true)
+        {
+            return Vec::new();
+        }
+
+        let unit = unit.to_string();
+        let mut methods = Vec::new();
+        for setting in settings
+            .get("methods")
+            .and_then(|methods| methods.as_array())
+            .into_iter()
+            .flatten()
+        {
+            if setting.get("unit").and_then(|u| u.as_str()) != Some(unit.as_str()) {
+                continue;
+            }
+            let method = setting
+                .get("method")
+                .and_then(|method| method.as_str())
+                .and_then(|method| method.parse().ok());
+            if let Some(method) = method {
+                if !methods.contains(&method) {
+                    methods.push(method);
+                }
+            }
+        }
+        methods
+    }
+}
+
+/// Payment method of a mint or melt quote
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PaymentMethod {
+    #[default]
+    Bolt11,
+    /// BOLT12 offers (NUT-25)
+    Bolt12,
+}
+
+impl PaymentMethod {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PaymentMethod::Bolt11 => "bolt11",
+            PaymentMethod::Bolt12 => "bolt12",
+        }
+    }
+}
+
+impl fmt::Display for PaymentMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for PaymentMethod {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "bolt11" => Ok(PaymentMethod::Bolt11),
+            "bolt12" => Ok(PaymentMethod::Bolt12),
+            other => Err(Error::custom(&format!("Unknown payment method: {}", other))),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct KeysResponse {
     pub keysets: Vec<KeysetResponse>,
@@ -31,6 +130,24 @@ pub struct KeysetResponse {
     pub keys: HashMap<String, String>,
 }
 
+impl KeysetResponse {
+    /// Parse the advertised amount → public key map into cdk [`Keys`]
+    pub fn to_keys(&self) -> Result<Keys> {
+        let mut keys = BTreeMap::new();
+
+        for (amount, pubkey) in &self.keys {
+            let amount: u64 = amount
+                .parse()
+                .map_err(|e| Error::custom(&format!("Invalid key amount {}: {}", amount, e)))?;
+            let pubkey = PublicKey::from_hex(pubkey)
+                .map_err(|e| Error::custom(&format!("Invalid key for amount {}: {}", amount, e)))?;
+            keys.insert(Amount::from(amount), pubkey);
+        }
+
+        Ok(Keys::new(keys))
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct KeysetsResponse {
     pub keysets: Vec<KeysetInfo>,
@@ -41,6 +158,8 @@ pub struct KeysetInfo {
     pub id: String,
     pub unit: String,
     pub active: bool,
+    #[serde(default)]
+    pub input_fee_ppk: u64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -53,14 +172,64 @@ pub struct PostMintQuoteRequest {
 pub struct PostMintQuoteResponse {
     pub quote: String,
     pub request: String,
+    #[serde(default)]
     pub paid: bool,
+    #[serde(default)]
+    pub state: Option<String>,
     pub expiry: u64,
 }
 
+impl PostMintQuoteResponse {
+    /// Whether the invoice has been paid, from either the NUT-04 `state` or the legacy `paid` flag
+    pub fn is_paid(&self) -> bool {
+        self.paid || matches!(self.state.as_deref(), Some("PAID") | Some("ISSUED"))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PostMintQuoteBolt12Request {
+    /// `None` for an offer that accepts any amount
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub amount: Option<u64>,
+    pub unit: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Key that must sign the mint requests for this quote (NUT-20)
+    pub pubkey: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PostMintQuoteBolt12Response {
+    pub quote: String,
+    /// The BOLT12 offer to pay
+    pub request: String,
+    #[serde(default)]
+    pub amount: Option<u64>,
+    pub unit: String,
+    /// `None` for offers that do not expire
+    #[serde(default)]
+    pub expiry: Option<u64>,
+    pub pubkey: String,
+    #[serde(default)]
+    pub amount_paid: u64,
+    #[serde(default)]
+    pub amount_issued: u64,
+}
+
+impl PostMintQuoteBolt12Response {
+    /// Amount paid to the offer that has not been minted yet
+    pub fn mintable_amount(&self) -> u64 {
+        self.amount_paid.saturating_sub(self.amount_issued)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PostMintRequest {
     pub quote: String,
     pub outputs: Vec<BlindedMessage>,
+    /// NUT-20 signature, required for quotes locked to a public key
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -72,6 +241,8 @@ pub struct PostMintResponse {
 pub struct PostMeltQuoteRequest {
     pub request: String,
     pub unit: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub options: Option<MeltOptions>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -79,12 +250,22 @@ pub struct PostMeltQuoteResponse {
     pub quote: String,
     pub amount: u64,
     pub fee_reserve: u64,
+    #[serde(default)]
     pub paid: bool,
+    #[serde(default)]
+    pub state: Option<String>,
     pub expiry: u64,
     pub payment_preimage: Option<String>,
     pub change: Option<Vec<BlindSignature>>,
 }
 
+impl PostMeltQuoteResponse {
+    /// Whether the payment went through, from either the NUT-05 `state` or the legacy `paid` flag
+    pub fn is_paid(&self) -> bool {
+        self.paid || self.state.as_deref() == Some("PAID")
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PostMeltRequest {
     pub quote: String,
@@ -144,36 +325,202 @@ pub struct ValidationError {
     pub error_type: String,
 }
 
-pub struct MintClient {
+/// Proofs unblinded from the mint's signatures, with the outcome of
+/// checking their DLEQ proofs (NUT-12)
+#[derive(Debug, Clone)]
+pub struct SignedProofs {
+    pub proofs: Proofs,
+    pub verification: TokenVerification,
+}
+
+/// Proofs the mint restored (NUT-09), each with the position of its output
+#[derive(Debug, Clone)]
+pub struct RestoredProofs {
+    pub proofs: Vec<(usize, Proof)>,
+    pub verification: TokenVerification,
+}
+
+#[derive(Debug, Clone)]
+pub struct SwapResult {
+    pub proofs: Proofs,
+    pub change: Proofs,
+    pub fee: u64,
+    /// DLEQ check of the signatures on `proofs` and `change`
+    pub verification: TokenVerification,
+}
+
+#[derive(Debug, Clone)]
+pub struct MeltResult {
+    pub paid: bool,
+    pub payment_preimage: Option<String>,
+    pub change: Proofs,
+    pub fee_paid: u64,
+    /// DLEQ check of the signatures on `change`
+    pub verification: TokenVerification,
+}
+
+/// HTTP settings for talking to mints.
+///
+/// They apply in full to [`MintClient`] requests. The cdk wallets behind
+/// [`crate::multimint::MultimintWallet`] only get the `proxy`: cdk 0.11
+/// builds its own HTTP client and cannot be handed one, so its requests use
+/// cdk's timeouts, are not retried and do not share this connection pool.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MintClientConfig {
+    /// Time allowed to open a connection, in seconds
+    pub connect_timeout_secs: u64,
+    /// Time allowed for a whole request, in seconds
+    pub request_timeout_secs: u64,
+    /// Retries after the first attempt, for idempotent requests only
+    pub max_retries: u32,
+    /// Delay before the first retry in milliseconds, doubled on each further retry
+    pub retry_base_delay_ms: u64,
+    /// Idle connections kept open per mint
+    pub pool_max_idle_per_host: usize,
+    /// Proxy for all mint traffic, e.g. `socks5h://127.0.0.1:9050` for Tor.
+    ///
+    /// NUT-17 subscriptions fall back to polling when set, since their
+    /// WebSocket connection cannot be proxied.
+    pub proxy: Option<String>,
+}
+
+impl Default for MintClientConfig {
+    fn default() -> Self {
+        Self {
+            connect_timeout_secs: 10,
+            request_timeout_secs: 30,
+            max_retries: 3,
+            retry_base_delay_ms: 250,
+            pool_max_idle_per_host: 4,
+            proxy: None,
+        }
+    }
+}
+
+impl MintClientConfig {
+    fn retry_delay(&self, attempt: u32) -> Duration {
+        Duration::from_millis(
+            self.retry_base_delay_ms
+                .saturating_mul(1u64 << attempt.min(16)),
+        )
+    }
+}
+
+/// A connection pool built from a [`MintClientConfig`], shared by every
+/// [`MintClient`] created from it. Cloning is cheap and keeps the same pool.
+#[derive(Debug, Clone)]
+pub struct MintTransport {
     client: Client,
+    config: MintClientConfig,
+}
+
+impl MintTransport {
+    pub fn new(config: MintClientConfig) -> Result<Self> {
+        let mut builder = Client::builder()
+            .connect_timeout(Duration::from_secs(config.connect_timeout_secs))
+            .timeout(Duration::from_secs(config.request_timeout_secs))
+            .pool_max_idle_per_host(config.pool_max_idle_per_host);
+
+        if let Some(proxy) = &config.proxy {
+            let proxy = reqwest::Proxy::all(proxy)
+                .map_err(|e| Error::custom(&format!("Invalid proxy {}: {}", proxy, e)))?;
+            builder = builder.proxy(proxy);
+        }
+
+        let client = builder
+            .build()
+            .map_err(|e| Error::custom(&format!("Failed to build HTTP client: {}", e)))?;
+
+        Ok(Self { client, config })
+    }
+
+    /// The process-wide transport with the default configuration
+    pub fn shared() -> Self {
+        static SHARED: OnceLock<MintTransport> = OnceLock::new();
+        SHARED
+            .get_or_init(|| {
+                let config = MintClientConfig::default();
+                Self::new(config.clone()).unwrap_or_else(|_| Self {
+                    client: Client::new(),
+                    config,
+                })
+            })
+            .clone()
+    }
+
+    pub fn config(&self) -> &MintClientConfig {
+        &self.config
+    }
+
+    /// The pooled HTTP client, for requests to services other than mints
+    pub fn http_client(&self) -> &Client {
+        &self.client
+    }
+
+    /// Create a client for `mint_url` that uses this transport's pool
+    pub fn client_for(&self, mint_url: &str) -> Result<MintClient> {
+        MintClient::with_transport(mint_url, self.clone())
+    }
+
+    /// Create a cdk HTTP client for `mint_url` that honours the proxy setting.
+    ///
+    /// cdk keeps its own connection pool and timeouts, so only the proxy
+    /// carries over; see [`MintClientConfig`].
+    pub fn cdk_client(&self, mint_url: MintUrl) -> Result<cdk::wallet::HttpClient> {
+        match &self.config.proxy {
+            Some(proxy) => {
+                let proxy = url::Url::parse(proxy)
+                    .map_err(|e| Error::custom(&format!("Invalid proxy {}: {}", proxy, e)))?;
+                // cdk only proxies hosts matching the pattern, so match them all
+                Ok(cdk::wallet::HttpClient::with_proxy(
+                    mint_url,
+                    proxy,
+                    Some(".*"),
+                    false,
+                )?)
+            }
+            None => Ok(cdk::wallet::HttpClient::new(mint_url)),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct MintClient {
+    transport: MintTransport,
     mint_url: MintUrl,
+    dleq_policy: DleqPolicy,
 }
 
 impl MintClient {
     pub fn new(mint_url: &str) -> Result<Self> {
+        Self::with_transport(mint_url, MintTransport::shared())
+    }
+
+    pub fn with_transport(mint_url: &str, transport: MintTransport) -> Result<Self> {
         let mint_url = MintUrl::from_str(mint_url)
             .map_err(|e| Error::custom(&format!("Invalid mint URL: {}", e)))?;
 
-        let client = Client::new();
+        Ok(Self {
+            transport,
+            mint_url,
+            dleq_policy: DleqPolicy::default(),
+        })
+    }
 
-        Ok(Self { client, mint_url })
+    /// Set how signatures without a DLEQ proof are treated
+    pub fn with_dleq_policy(mut self, policy: DleqPolicy) -> Self {
+        self.dleq_policy = policy;
+        self
     }
 
     pub async fn get_info(&self) -> Result<GetInfoResponse> {
         let url = format!("{}/v1/info", self.mint_url);
 
-        let response = self
-            .client
-            .get(&url)
-            .send()
-            .await
-            .map_err(|e| Error::custom(&format!("Request failed: {}", e)))?;
+        let response = self.send_idempotent(self.client().get(&url)).await?;
 
         if !response.status().is_success() {
-            return Err(Error::custom(&format!(
-                "Request failed with status: {}",
-                response.status()
-            )));
+            return Err(mint_error(response).await);
         }
 
         let info: GetInfoResponse = response
@@ -187,18 +534,10 @@ impl MintClient {
     pub async fn get_keys(&self) -> Result<KeysResponse> {
         let url = format!("{}/v1/keys", self.mint_url);
 
-        let response = self
-            .client
-            .get(&url)
-            .send()
-            .await
-            .map_err(|e| Error::custom(&format!("Request failed: {}", e)))?;
+        let response = self.send_idempotent(self.client().get(&url)).await?;
 
         if !response.status().is_success() {
-            return Err(Error::custom(&format!(
-                "Request failed with status: {}",
-                response.status()
-            )));
+            return Err(mint_error(response).await);
         }
 
         let keys: KeysResponse = response
@@ -212,18 +551,10 @@ impl MintClient {
     pub async fn get_keyset_keys(&self, keyset_id: &str) -> Result<KeysResponse> {
         let url = format!("{}/v1/keys/{}", self.mint_url, keyset_id);
 
-        let response = self
-            .client
-            .get(&url)
-            .send()
-            .await
-            .map_err(|e| Error::custom(&format!("Request failed: {}", e)))?;
+        let response = self.send_idempotent(self.client().get(&url)).await?;
 
         if !response.status().is_success() {
-            return Err(Error::custom(&format!(
-                "Request failed with status: {}",
-                response.status()
-            )));
+            return Err(mint_error(response).await);
         }
 
         let keys: KeysResponse = response
@@ -237,18 +568,10 @@ impl MintClient {
     pub async fn get_keysets(&self) -> Result<KeysetsResponse> {
         let url = format!("{}/v1/keysets", self.mint_url);
 
-        let response = self
-            .client
-            .get(&url)
-            .send()
-            .await
-            .map_err(|e| Error::custom(&format!("Request failed: {}", e)))?;
+        let response = self.send_idempotent(self.client().get(&url)).await?;
 
         if !response.status().is_success() {
-            return Err(Error::custom(&format!(
-                "Request failed with status: {}",
-                response.status()
-            )));
+            return Err(mint_error(response).await);
         }
 
         let keysets: KeysetsResponse = response
@@ -272,18 +595,11 @@ impl MintClient {
         };
 
         let response = self
-            .client
-            .post(&url)
-            .json(&request)
-            .send()
-            .await
-            .map_err(|e| Error::custom(&format!("Request failed: {}", e)))?;
+            .send_once(self.client().post(&url).json(&request))
+            .await?;
 
         if !response.status().is_success() {
-            return Err(Error::custom(&format!(
-                "Request failed with status: {}",
-                response.status()
-            )));
+            return Err(mint_error(response).await);
         }
 
         let quote: PostMintQuoteResponse = response
@@ -297,21 +613,65 @@ impl MintClient {
     pub async fn get_mint_quote(&self, quote: &str) -> Result<PostMintQuoteResponse> {
         let url = format!("{}/v1/mint/quote/bolt11/{}", self.mint_url, quote);
 
+        let response = self.send_idempotent(self.client().get(&url)).await?;
+
+        if !response.status().is_success() {
+            return Err(mint_error(response).await);
+        }
+
+        let quote_response: PostMintQuoteResponse = response
+            .json()
+            .await
+            .map_err(|e| Error::custom(&format!("Failed to parse response: {}", e)))?;
+
+        Ok(quote_response)
+    }
+
+    /// Request a BOLT12 offer to mint against (NUT-25).
+    ///
+    /// Mint requests for the quote must be signed with the secret key of `pubkey`.
+    pub async fn request_bolt12_mint_quote(
+        &self,
+        amount: Option<u64>,
+        unit: CurrencyUnit,
+        description: Option<String>,
+        pubkey: &PublicKey,
+    ) -> Result<PostMintQuoteBolt12Response> {
+        let url = format!("{}/v1/mint/quote/bolt12", self.mint_url);
+
+        let request = PostMintQuoteBolt12Request {
+            amount,
+            unit: unit.to_string(),
+            description,
+            pubkey: pubkey.to_hex(),
+        };
+
         let response = self
-            .client
-            .get(&url)
-            .send()
+            .send_once(self.client().post(&url).json(&request))
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(mint_error(response).await);
+        }
+
+        let quote: PostMintQuoteBolt12Response = response
+            .json()
             .await
-            .map_err(|e| Error::custom(&format!("Request failed: {}", e)))?;
+            .map_err(|e| Error::custom(&format!("Failed to parse response: {}", e)))?;
+
+        Ok(quote)
+    }
+
+    pub async fn get_bolt12_mint_quote(&self, quote: &str) -> Result<PostMintQuoteBolt12Response> {
+        let url = format!("{}/v1/mint/quote/bolt12/{}", self.mint_url, quote);
+
+        let response = self.send_idempotent(self.client().get(&url)).await?;
 
         if !response.status().is_success() {
-            return Err(Error::custom(&format!(
-                "Request failed with status: {}",
-                response.status()
-            )));
+            return Err(mint_error(response).await);
         }
 
-        let quote_response: PostMintQuoteResponse = response
+        let quote_response: PostMintQuoteBolt12Response = response
             .json()
             .await
             .map_err(|e| Error::custom(&format!("Failed to parse response: {}", e)))?;
@@ -324,26 +684,48 @@ impl MintClient {
         quote: &str,
         outputs: Vec<BlindedMessage>,
     ) -> Result<PostMintResponse> {
-        let url = format!("{}/v1/mint/bolt11", self.mint_url);
+        self.mint_tokens_with_method(PaymentMethod::Bolt11, quote, outputs, None)
+            .await
+    }
+
+    /// Mint outputs for a paid quote, signing the request with
+    /// `signing_key` when the quote is locked to a public key (NUT-20)
+    pub async fn mint_tokens_with_method(
+        &self,
+        method: PaymentMethod,
+        quote: &str,
+        outputs: Vec<BlindedMessage>,
+        signing_key: Option<&SecretKey>,
+    ) -> Result<PostMintResponse> {
+        let url = format!("{}/v1/mint/{}", self.mint_url, method);
+
+        let signature = match signing_key {
+            Some(secret_key) => {
+                let mut signed = MintRequest {
+                    quote: quote.to_string(),
+                    outputs: outputs.clone(),
+                    signature: None,
+                };
+                signed
+                    .sign(secret_key.clone())
+                    .map_err(|e| Error::custom(&format!("Failed to sign mint request: {}", e)))?;
+                signed.signature
+            }
+            None => None,
+        };
 
         let request = PostMintRequest {
             quote: quote.to_string(),
             outputs,
+            signature,
         };
 
         let response = self
-            .client
-            .post(&url)
-            .json(&request)
-            .send()
-            .await
-            .map_err(|e| Error::custom(&format!("Request failed: {}", e)))?;
+            .send_once(self.client().post(&url).json(&request))
+            .await?;
 
         if !response.status().is_success() {
-            return Err(Error::custom(&format!(
-                "Request failed with status: {}",
-                response.status()
-            )));
+            return Err(mint_error(response).await);
         }
 
         let mint_response: PostMintResponse = response
@@ -359,26 +741,44 @@ impl MintClient {
         request: &str,
         unit: CurrencyUnit,
     ) -> Result<PostMeltQuoteResponse> {
-        let url = format!("{}/v1/melt/quote/bolt11", self.mint_url);
+        self.request_melt_quote_with_options(request, unit, None)
+            .await
+    }
+
+    /// Request a melt quote, e.g. with [`MeltOptions::new_amountless`] to pay
+    /// an invoice without an amount
+    pub async fn request_melt_quote_with_options(
+        &self,
+        request: &str,
+        unit: CurrencyUnit,
+        options: Option<MeltOptions>,
+    ) -> Result<PostMeltQuoteResponse> {
+        self.request_melt_quote_with_method(PaymentMethod::Bolt11, request, unit, options)
+            .await
+    }
+
+    /// Request a melt quote for a BOLT11 invoice or a BOLT12 offer
+    pub async fn request_melt_quote_with_method(
+        &self,
+        method: PaymentMethod,
+        request: &str,
+        unit: CurrencyUnit,
+        options: Option<MeltOptions>,
+    ) -> Result<PostMeltQuoteResponse> {
+        let url = format!("{}/v1/melt/quote/{}", self.mint_url, method);
 
         let melt_request = PostMeltQuoteRequest {
             request: request.to_string(),
             unit: unit.to_string(),
+            options,
         };
 
         let response = self
-            .client
-            .post(&url)
-            .json(&melt_request)
-            .send()
-            .await
-            .map_err(|e| Error::custom(&format!("Request failed: {}", e)))?;
+            .send_once(self.client().post(&url).json(&melt_request))
+            .await?;
 
         if !response.status().is_success() {
-            return Err(Error::custom(&format!(
-                "Request failed with status: {}",
-                response.status()
-            )));
+            return Err(mint_error(response).await);
         }
 
         let quote: PostMeltQuoteResponse = response
@@ -390,20 +790,21 @@ impl MintClient {
     }
 
     pub async fn get_melt_quote(&self, quote: &str) -> Result<PostMeltQuoteResponse> {
-        let url = format!("{}/v1/melt/quote/bolt11/{}", self.mint_url, quote);
-
-        let response = self
-            .client
-            .get(&url)
-            .send()
+        self.get_melt_quote_with_method(PaymentMethod::Bolt11, quote)
             .await
-            .map_err(|e| Error::custom(&format!("Request failed: {}", e)))?;
+    }
+
+    pub async fn get_melt_quote_with_method(
+        &self,
+        method: PaymentMethod,
+        quote: &str,
+    ) -> Result<PostMeltQuoteResponse> {
+        let url = format!("{}/v1/melt/quote/{}/{}", self.mint_url, method, quote);
+
+        let response = self.send_idempotent(self.client().get(&url)).await?;
 
         if !response.status().is_success() {
-            return Err(Error::custom(&format!(
-                "Request failed with status: {}",
-                response.status()
-            )));
+            return Err(mint_error(response).await);
         }
 
         let quote_response: PostMeltQuoteResponse = response
@@ -420,7 +821,18 @@ impl MintClient {
         inputs: Vec<Proof>,
         outputs: Option<Vec<BlindedMessage>>,
     ) -> Result<PostMeltQuoteResponse> {
-        let url = format!("{}/v1/melt/bolt11", self.mint_url);
+        self.melt_tokens_with_method(PaymentMethod::Bolt11, quote, inputs, outputs)
+            .await
+    }
+
+    pub async fn melt_tokens_with_method(
+        &self,
+        method: PaymentMethod,
+        quote: &str,
+        inputs: Vec<Proof>,
+        outputs: Option<Vec<BlindedMessage>>,
+    ) -> Result<PostMeltQuoteResponse> {
+        let url = format!("{}/v1/melt/{}", self.mint_url, method);
 
         let request = PostMeltRequest {
             quote: quote.to_string(),
@@ -429,18 +841,11 @@ impl MintClient {
         };
 
         let response = self
-            .client
-            .post(&url)
-            .json(&request)
-            .send()
-            .await
-            .map_err(|e| Error::custom(&format!("Request failed: {}", e)))?;
+            .send_once(self.client().post(&url).json(&request))
+            .await?;
 
         if !response.status().is_success() {
-            return Err(Error::custom(&format!(
-                "Request failed with status: {}",
-                response.status()
-            )));
+            return Err(mint_error(response).await);
         }
 
         let melt_response: PostMeltQuoteResponse = response
@@ -461,18 +866,11 @@ impl MintClient {
         let request = PostSwapRequest { inputs, outputs };
 
         let response = self
-            .client
-            .post(&url)
-            .json(&request)
-            .send()
-            .await
-            .map_err(|e| Error::custom(&format!("Request failed: {}", e)))?;
+            .send_once(self.client().post(&url).json(&request))
+            .await?;
 
         if !response.status().is_success() {
-            return Err(Error::custom(&format!(
-                "Request failed with status: {}",
-                response.status()
-            )));
+            return Err(mint_error(response).await);
         }
 
         let swap_response: PostSwapResponse = response
@@ -489,18 +887,11 @@ impl MintClient {
         let request = PostCheckStateRequest { ys };
 
         let response = self
-            .client
-            .post(&url)
-            .json(&request)
-            .send()
-            .await
-            .map_err(|e| Error::custom(&format!("Request failed: {}", e)))?;
+            .send_idempotent(self.client().post(&url).json(&request))
+            .await?;
 
         if !response.status().is_success() {
-            return Err(Error::custom(&format!(
-                "Request failed with status: {}",
-                response.status()
-            )));
+            return Err(mint_error(response).await);
         }
 
         let state_response: PostCheckStateResponse = response
@@ -520,18 +911,11 @@ impl MintClient {
         let request = PostRestoreRequest { outputs };
 
         let response = self
-            .client
-            .post(&url)
-            .json(&request)
-            .send()
-            .await
-            .map_err(|e| Error::custom(&format!("Request failed: {}", e)))?;
+            .send_idempotent(self.client().post(&url).json(&request))
+            .await?;
 
         if !response.status().is_success() {
-            return Err(Error::custom(&format!(
-                "Request failed with status: {}",
-                response.status()
-            )));
+            return Err(mint_error(response).await);
         }
 
         let restore_response: PostRestoreResponse = response
@@ -542,24 +926,89 @@ impl MintClient {
         Ok(restore_response)
     }
 
-    pub async fn check_proofs_spent(&self, proofs: &[Proof]) -> Result<Vec<bool>> {
-        let ys: Vec<String> = proofs
-            .iter()
-            .map(|p| match p.y() {
-                Ok(y) => y.to_string(),
-                Err(_) => String::new(),
-            })
-            .collect();
+    /// Ask the mint for the signatures it issued on any of `premint`'s
+    /// outputs (NUT-09) and unblind them.
+    ///
+    /// Returns each restored proof with the position of its output in
+    /// `premint`. The outputs of [`PreMintSecrets::restore_batch`] carry no
+    /// amount, so the amount the mint reports is used.
+    pub async fn restore_proofs(
+        &self,
+        premint: &PreMintSecrets,
+        keys: &Keys,
+    ) -> Result<RestoredProofs> {
+        let response = self.restore_signatures(premint.blinded_messages()).await?;
+        if response.outputs.len() != response.signatures.len() {
+            return Err(Error::custom(&format!(
+                "Mint returned {} signatures for {} outputs",
+                response.signatures.len(),
+                response.outputs.len()
+            )));
+        }
 
-        let response = self.check_proof_state(ys).await?;
+        let mut positions = Vec::with_capacity(response.outputs.len());
+        let mut restored = PreMintSecrets::new(premint.keyset_id);
+        for output in &response.outputs {
+            let position = premint
+                .secrets
+                .iter()
+                .position(|pre| pre.blinded_message.blinded_secret == output.blinded_secret)
+                .ok_or_else(|| Error::custom("Mint restored an output that was not requested"))?;
+
+            let mut pre = premint.secrets[position].clone();
+            pre.amount = output.amount;
+            pre.blinded_message.amount = output.amount;
+            restored.secrets.push(pre);
+            positions.push(position);
+        }
 
-        let spent_states: Vec<bool> = response
-            .states
-            .iter()
-            .map(|state| state.state == "SPENT")
-            .collect();
+        let verification =
+            dleq::verify_signatures(&restored, &response.signatures, keys, self.dleq_policy)?;
+        let proofs = crypto::unblind_signatures(&restored, response.signatures, keys)?;
 
-        Ok(spent_states)
+        Ok(RestoredProofs {
+            proofs: positions.into_iter().zip(proofs).collect(),
+            verification,
+        })
+    }
+
+    pub async fn check_proofs_spent(&self, proofs: &[Proof]) -> Result<Vec<bool>> {
+        let states = self.check_proof_states(proofs).await?;
+        Ok(states.iter().map(|state| *state == State::Spent).collect())
+    }
+
+    /// The NUT-07 state of every proof, in order, checked in batches of
+    /// [`CHECK_STATE_BATCH_SIZE`]
+    pub async fn check_proof_states(&self, proofs: &[Proof]) -> Result<Vec<State>> {
+        let mut states = Vec::with_capacity(proofs.len());
+
+        for batch in proofs.chunks(CHECK_STATE_BATCH_SIZE) {
+            let ys = batch
+                .iter()
+                .map(|p| p.y().map(|y| y.to_string()))
+                .collect::<std::result::Result<Vec<_>, _>>()
+                .map_err(|e| Error::custom(&format!("Invalid proof: {}", e)))?;
+
+            let response = self.check_proof_state(ys.clone()).await?;
+            if response.states.len() != ys.len() {
+                return Err(Error::custom(&format!(
+                    "Mint returned {} states for {} proofs",
+                    response.states.len(),
+                    ys.len()
+                )));
+            }
+
+            for (y, proof_state) in ys.iter().zip(response.states) {
+                if !proof_state.y.eq_ignore_ascii_case(y) {
+                    return Err(Error::custom("Mint returned proof states out of order"));
+                }
+                states.push(State::from_str(&proof_state.state).map_err(|e| {
+                    Error::custom(&format!("Unknown proof state {}: {}", proof_state.state, e))
+                })?);
+            }
+        }
+
+        Ok(states)
     }
 
     pub async fn validate_proofs(&self, proofs: &[Proof]) -> Result<Vec<Proof>> {
@@ -582,6 +1031,399 @@ impl MintClient {
         Ok(valid_proofs)
     }
 
+    /// Mint the outputs for a paid quote and return the unblinded proofs
+    pub async fn mint_proofs(
+        &self,
+        quote: &str,
+        amount: u64,
+        unit: &CurrencyUnit,
+    ) -> Result<SignedProofs> {
+        self.mint_proofs_with_method(PaymentMethod::Bolt11, quote, amount, unit, None)
+            .await
+    }
+
+    /// Mint `amount` for a paid quote of `method` and return the unblinded proofs
+    pub async fn mint_proofs_with_method(
+        &self,
+        method: PaymentMethod,
+        quote: &str,
+        amount: u64,
+        unit: &CurrencyUnit,
+        signing_key: Option<&SecretKey>,
+    ) -> Result<SignedProofs> {
+        let keyset = self.get_active_keyset(unit).await?;
+        let keys = self.get_keys_for_keyset(&keyset.id).await?;
+
+        let premint = crypto::create_blinded_messages_for_amount(
+            amount,
+            &keyset.id,
+            &crypto::keyset_denominations(&keys),
+        )?;
+
+        self.mint_outputs_with_method(method, quote, &premint, &keys, signing_key)
+            .await
+    }
+
+    /// Mint the prepared outputs of `premint` for a paid quote of `method`
+    /// and return the unblinded proofs.
+    ///
+    /// Callers that keep `premint` until the proofs are stored can recover
+    /// the signatures with [`Self::restore_proofs`] if the response is lost.
+    pub async fn mint_outputs_with_method(
+        &self,
+        method: PaymentMethod,
+        quote: &str,
+        premint: &PreMintSecrets,
+        keys: &Keys,
+        signing_key: Option<&SecretKey>,
+    ) -> Result<SignedProofs> {
+        let amount = u64::from(
+            premint
+                .total_amount()
+                .map_err(|e| Error::custom(&format!("Invalid output amounts: {}", e)))?,
+        );
+        let response = self
+            .mint_tokens_with_method(method, quote, premint.blinded_messages(), signing_key)
+            .await?;
+        let verification =
+            dleq::verify_signatures(premint, &response.signatures, keys, self.dleq_policy)?;
+
+        let proofs = crypto::unblind_signatures(premint, response.signatures, keys)?;
+        Self::ensure_total(&proofs, amount)?;
+
+        Ok(SignedProofs {
+            proofs,
+            verification,
+        })
+    }
+
+    /// Swap proofs for fresh ones.
+    ///
+    /// The returned `proofs` add up to the sum of `target_amounts`; whatever is
+    /// left after the input fee comes back as `change`.
+    pub async fn swap(&self, proofs: Proofs, target_amounts: &[u64]) -> Result<SwapResult> {
+        let keysets = self.get_keysets().await?.keysets;
+        let unit = Self::unit_for_proofs(&proofs, &keysets)?;
+        let keyset = Self::select_active_keyset(&keysets, &unit)?;
+        let keys = self.get_keys_for_keyset(&keyset.id).await?;
+        let keyset_id = cdk::nuts::Id::from_str(&keyset.id)
+            .map_err(|e| Error::custom(&format!("Invalid keyset ID: {}", e)))?;
+
+        let input_total = Self::total_amount(&proofs);
+        let fee = Self::calculate_input_fee(&proofs, &keysets);
+        let target_total: u64 = target_amounts.iter().sum();
+
+        let change_total = input_total
+            .checked_sub(fee)
+            .and_then(|available| available.checked_sub(target_total))
+            .ok_or_else(|| {
+                Error::NotEnoughBalance(format!(
+                    "Inputs of {} cannot cover {} plus {} fee",
+                    input_total, target_total, fee
+                ))
+            })?;
+
+        let denominations = crypto::keyset_denominations(&keys);
+        let mut send_amounts = Vec::new();
+        for &amount in target_amounts {
+            send_amounts.extend(crypto::split_amount(amount, &denominations)?);
+        }
+        let mut premint = crypto::create_blinded_messages_for_amounts(&send_amounts, keyset_id)?;
+        let send_count = premint.len();
+        premint.combine(crypto::create_blinded_messages_for_amounts(
+            &crypto::split_amount(change_total, &denominations)?,
+            keyset_id,
+        )?);
+
+        let response = self.swap_tokens(proofs, premint.blinded_messages()).await?;
+        let verification =
+            dleq::verify_signatures(&premint, &response.signatures, &keys, self.dleq_policy)?;
+
+        let mut new_proofs = crypto::unblind_signatures(&premint, response.signatures, &keys)?;
+        let change = new_proofs.split_off(send_count);
+        Self::ensure_total(&new_proofs, target_total)?;
+        Self::ensure_total(&change, change_total)?;
+
+        Ok(SwapResult {
+            proofs: new_proofs,
+            change,
+            fee,
+            verification,
+        })
+    }
+
+    /// Pay a melt quote with `proofs`, collecting overpaid fees as change (NUT-08)
+    pub async fn melt(&self, quote: &str, proofs: Proofs) -> Result<MeltResult> {
+        self.melt_with_method(PaymentMethod::Bolt11, quote, proofs)
+            .await
+    }
+
+    pub async fn melt_with_method(
+        &self,
+        method: PaymentMethod,
+        quote: &str,
+        proofs: Proofs,
+    ) -> Result<MeltResult> {
+        let blank_outputs = self.melt_change_outputs(method, quote, &proofs).await?;
+        self.melt_with_outputs(method, quote, proofs, &blank_outputs)
+            .await
+    }
+
+    /// Blank outputs (NUT-08) for the change of paying `quote` with `proofs`,
+    /// after checking the proofs cover the amount, fee reserve and input fees
+    pub async fn melt_change_outputs(
+        &self,
+        method: PaymentMethod,
+        quote: &str,
+        proofs: &Proofs,
+    ) -> Result<PreMintSecrets> {
+        let melt_quote = self.get_melt_quote_with_method(method, quote).await?;
+
+        let keysets = self.get_keysets().await?.keysets;
+        let unit = Self::unit_for_proofs(proofs, &keysets)?;
+        let keyset = Self::select_active_keyset(&keysets, &unit)?;
+        let keyset_id = cdk::nuts::Id::from_str(&keyset.id)
+            .map_err(|e| Error::custom(&format!("Invalid keyset ID: {}", e)))?;
+
+        let input_total = Self::total_amount(proofs);
+        let input_fee = Self::calculate_input_fee(proofs, &keysets);
+        let required = melt_quote.amount + melt_quote.fee_reserve + input_fee;
+        if input_total < required {
+            return Err(Error::NotEnoughBalance(format!(
+                "Melt needs {} but inputs only cover {}",
+                required, input_total
+            )));
+        }
+
+        crypto::create_blank_outputs(input_total - melt_quote.amount - input_fee, keyset_id)
+    }
+
+    /// Pay a melt quote with `proofs` and blank outputs from
+    /// [`Self::melt_change_outputs`], which a caller can keep to recover the
+    /// change of a melt whose response is lost
+    pub async fn melt_with_outputs(
+        &self,
+        method: PaymentMethod,
+        quote: &str,
+        proofs: Proofs,
+        blank_outputs: &PreMintSecrets,
+    ) -> Result<MeltResult> {
+        let input_total = Self::total_amount(&proofs);
+        let response = self
+            .melt_tokens_with_method(
+                method,
+                quote,
+                proofs,
+                Some(blank_outputs.blinded_messages()),
+            )
+            .await?;
+        let paid = response.is_paid();
+
+        let change = match response.change {
+            Some(signatures) if paid => self.melt_change(blank_outputs, signatures).await?,
+            _ => SignedProofs {
+                proofs: Vec::new(),
+                verification: TokenVerification::default(),
+            },
+        };
+
+        let fee_paid = if paid {
+            input_total
+                .saturating_sub(response.amount)
+                .saturating_sub(Self::total_amount(&change.proofs))
+        } else {
+            0
+        };
+
+        Ok(MeltResult {
+            paid,
+            payment_preimage: response.payment_preimage,
+            change: change.proofs,
+            fee_paid,
+            verification: change.verification,
+        })
+    }
+
+    /// Unblind the change signatures of a paid melt with its blank outputs
+    pub async fn melt_change(
+        &self,
+        blank_outputs: &PreMintSecrets,
+        signatures: Vec<BlindSignature>,
+    ) -> Result<SignedProofs> {
+        if signatures.is_empty() {
+            return Ok(SignedProofs {
+                proofs: Vec::new(),
+                verification: TokenVerification::default(),
+            });
+        }
+        let keys = self
+            .get_keys_for_keyset(&blank_outputs.keyset_id.to_string())
+            .await?;
+        let verification =
+            dleq::verify_signatures(blank_outputs, &signatures, &keys, self.dleq_policy)?;
+        let proofs = crypto::unblind_change_signatures(blank_outputs, signatures, &keys)?;
+        Ok(SignedProofs {
+            proofs,
+            verification,
+        })
+    }
+
+    /// Active keyset for `unit` with the lowest input fee
+    pub async fn get_active_keyset(&self, unit: &CurrencyUnit) -> Result<KeysetInfo> {
+        let keysets = self.get_keysets().await?.keysets;
+        Self::select_active_keyset(&keysets, unit)
+    }
+
+    /// Fetch the public keys of a single keyset
+    pub async fn get_keys_for_keyset(&self, keyset_id: &str) -> Result<Keys> {
+        let response = self.get_keyset_keys(keyset_id).await?;
+
+        response
+            .keysets
+            .iter()
+            .find(|keyset| keyset.id == keyset_id)
+            .ok_or_else(|| Error::custom(&format!("Mint did not return keyset {}", keyset_id)))?
+            .to_keys()
+    }
+
+    /// Input fee for spending `proofs` (NUT-02), rounded up to a whole unit
+    pub fn calculate_input_fee(proofs: &[Proof], keysets: &[KeysetInfo]) -> u64 {
+        let fee_ppk: u64 = proofs
+            .iter()
+            .map(|proof| {
+                let keyset_id = proof.keyset_id.to_string();
+                keysets
+                    .iter()
+                    .find(|keyset| keyset.id == keyset_id)
+                    .map(|keyset| keyset.input_fee_ppk)
+                    .unwrap_or(0)
+            })
+            .sum();
+
+        fee_ppk.div_ceil(1000)
+    }
+
+    fn select_active_keyset(keysets: &[KeysetInfo], unit: &CurrencyUnit) -> Result<KeysetInfo> {
+        let unit = unit.to_string();
+
+        keysets
+            .iter()
+            .filter(|keyset| keyset.active && keyset.unit == unit)
+            .min_by_key(|keyset| keyset.input_fee_ppk)
+            .cloned()
+            .ok_or_else(|| Error::custom(&format!("Mint has no active keyset for unit {}", unit)))
+    }
+
+    fn unit_for_proofs(proofs: &[Proof], keysets: &[KeysetInfo]) -> Result<CurrencyUnit> {
+        let keyset_id = proofs
+            .first()
+            .ok_or_else(|| Error::custom("No proofs provided"))?
+            .keyset_id
+            .to_string();
+
+        let keyset = keysets
+            .iter()
+            .find(|keyset| keyset.id == keyset_id)
+            .ok_or_else(|| Error::custom(&format!("Unknown keyset {}", keyset_id)))?;
+
+        if proofs.iter().any(|proof| {
+            Self::keyset_unit(keysets, &proof.keyset_id.to_string()) != Some(&keyset.unit)
+        }) {
+            return Err(Error::custom(
+                "Proofs are from keysets with different units",
+            ));
+        }
+
+        CurrencyUnit::from_str(&keyset.unit)
+            .map_err(|e| Error::custom(&format!("Invalid unit {}: {}", keyset.unit, e)))
+    }
+
+    fn keyset_unit<'a>(keysets: &'a [KeysetInfo], keyset_id: &str) -> Option<&'a String> {
+        keysets
+            .iter()
+            .find(|keyset| keyset.id == keyset_id)
+            .map(|keyset| &keyset.unit)
+    }
+
+    fn total_amount(proofs: &[Proof]) -> u64 {
+        proofs.iter().map(|proof| u64::from(proof.amount)).sum()
+    }
+
+    fn ensure_total(proofs: &[Proof], expected: u64) -> Result<()> {
+        let total = Self::total_amount(proofs);
+        if total != expected {
+            return Err(Error::custom(&format!(
+                "Mint returned proofs worth {} instead of {}",
+                total, expected
+            )));
+        }
+        Ok(())
+    }
+
+    fn client(&self) -> &Client {
+        &self.transport.client
+    }
+
+    async fn send_once(&self, request: RequestBuilder) -> Result<Response> {
+        request
+            .send()
+            .await
+            .map_err(|e| Error::custom(&format!("Request failed: {}", e)))
+    }
+
+    /// Send a request that is safe to repeat, retrying connection failures,
+    /// timeouts and transient server errors with exponential backoff
+    async fn send_idempotent(&self, request: RequestBuilder) -> Result<Response> {
+        let config = &self.transport.config;
+        let mut attempt = 0;
+
+        loop {
+            let result = request
+                .try_clone()
+                .ok_or_else(|| Error::custom("Request cannot be retried"))?
+                .send()
+                .await;
+
+            let retryable = match &result {
+                Ok(response) => {
+                    response.status().is_server_error()
+                        || response.status() == StatusCode::TOO_MANY_REQUESTS
+                }
+                Err(e) => e.is_connect() || e.is_timeout(),
+            };
+
+            if !retryable || attempt >= config.max_retries {
+                return result.map_err(|e| Error::custom(&format!("Request failed: {}", e)));
+            }
+
+            tokio::time::sleep(config.retry_delay(attempt)).await;
+            attempt += 1;
+        }
+    }
+
+    /// Subscribe to state changes of quotes or proofs (NUT-17).
+    ///
+    /// `filters` are quote ids, or the `Y` values of proofs for
+    /// [`SubscriptionKind::ProofState`]. Mints without WebSocket support are
+    /// polled every `poll_interval`, and so is every mint when the transport
+    /// has a proxy, as the WebSocket connection would bypass it.
+    ///
+    /// # Panics
+    ///
+    /// Outside a Tokio runtime, which runs the subscription.
+    pub fn subscribe(
+        &self,
+        kind: SubscriptionKind,
+        filters: Vec<String>,
+        poll_interval: Duration,
+    ) -> StateStream {
+        subscription::spawn(self.clone(), kind, filters, poll_interval)
+    }
+
+    pub fn transport(&self) -> &MintTransport {
+        &self.transport
+    }
+
     pub fn mint_url(&self) -> &MintUrl {
         &self.mint_url
     }
@@ -590,3 +1432,330 @@ impl MintClient {
         self.mint_url.to_string()
     }
 }
+
+/// Turn a non-2xx mint response into a typed error, keeping the mint's detail and code
+async fn mint_error(response: reqwest::Response) -> Error {
+    let status = response.status().as_u16();
+    match response.text().await {
+        Ok(body) => MintError::from_body(status, &body).into(),
+        Err(e) => Error::custom(&format!(
+            "Request failed with status {} and unreadable body: {}",
+            status, e
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cdk::dhke;
+    use cdk::nuts::Id;
+    use cdk::secret::Secret;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex};
+
+    /// Input fee of the mock mint's keyset, so two inputs cost one sat
+    const INPUT_FEE_PPK: u64 = 500;
+
+    /// The mock mint's private key for `amount`
+    fn mint_key(amount: Amount) -> SecretKey {
+        let index = u64::from(amount).trailing_zeros() as u8;
+        SecretKey::from_slice(&[index + 1; 32]).unwrap()
+    }
+
+    fn keys() -> Keys {
+        Keys::new(
+            (0..8)
+                .map(|i| {
+                    let amount = Amount::from(1u64 << i);
+                    (amount, mint_key(amount).public_key())
+                })
+                .collect(),
+        )
+    }
+
+    fn keyset_id() -> Id {
+        Id::v1_from_keys(&keys())
+    }
+
+    fn sign(output: &BlindedMessage, amount: Amount) -> BlindSignature {
+        let key = mint_key(amount);
+        let c = dhke::sign_message(&key, &output.blinded_secret).unwrap();
+        BlindSignature::new(amount, c, output.keyset_id, &output.blinded_secret, key).unwrap()
+    }
+
+    fn outputs(body: &serde_json::Value) -> Vec<BlindedMessage> {
+        serde_json::from_value(body["outputs"].clone()).unwrap()
+    }
+
+    fn melt_quote(quote: &str, state: &str, change: Option<Vec<BlindSignature>>) -> String {
+        serde_json::json!({
+            "quote": quote,
+            "amount": 10,
+            "fee_reserve": 4,
+            "paid": state == "PAID",
+            "state": state,
+            "expiry": 0,
+            "payment_preimage": (state == "PAID").then_some("00ff"),
+            "change": change,
+        })
+        .to_string()
+    }
+
+    /// Answer a request to the mock mint with a status and JSON body.
+    ///
+    /// Mint quote `unpaid` is refused; melt quote `paid` pays with 3 sat of
+    /// change and any other melt quote stays pending.
+    fn respond(method: &str, path: &str, body: &serde_json::Value) -> (u16, String) {
+        let id = keyset_id().to_string();
+        match (method, path) {
+            ("GET", "/v1/keysets") => (
+                200,
+                serde_json::json!({ "keysets": [{
+                    "id": id,
+                    "unit": "sat",
+                    "active": true,
+                    "input_fee_ppk": INPUT_FEE_PPK,
+                }]})
+                .to_string(),
+            ),
+            ("GET", path) if path == format!("/v1/keys/{}", id) => {
+                let keys: HashMap<String, String> = keys()
+                    .iter()
+                    .map(|(amount, key)| (amount.to_string(), key.to_hex()))
+                    .collect();
+                (
+                    200,
+                    serde_json::json!({ "keysets": [{ "id": id, "unit": "sat", "keys": keys }] })
+                        .to_string(),
+                )
+            }
+            ("POST", "/v1/mint/bolt11") if body["quote"] == "unpaid" => (
+                400,
+                r#"{"detail":"Quote not paid","code":20001}"#.to_string(),
+            ),
+            ("POST", "/v1/mint/bolt11") | ("POST", "/v1/swap") => {
+                let signatures: Vec<BlindSignature> = outputs(body)
+                    .iter()
+                    .map(|output| sign(output, output.amount))
+                    .collect();
+                (
+                    200,
+                    serde_json::json!({ "signatures": signatures }).to_string(),
+                )
+            }
+            ("GET", path) if path.starts_with("/v1/melt/quote/bolt11/") => {
+                let quote = path.rsplit('/').next().unwrap();
+                (200, melt_quote(quote, "UNPAID", None))
+            }
+            ("POST", "/v1/melt/bolt11") if body["quote"] == "paid" => {
+                let change = outputs(body)
+                    .iter()
+                    .zip([1u64, 2])
+                    .map(|(output, amount)| sign(output, Amount::from(amount)))
+                    .collect();
+                (200, melt_quote("paid", "PAID", Some(change)))
+            }
+            ("POST", "/v1/melt/bolt11") => {
+                let quote = body["quote"].as_str().unwrap();
+                (200, melt_quote(quote, "PENDING", None))
+            }
+            _ => (404, r#"{"detail":"Not found","code":0}"#.to_string()),
+        }
+    }
+
+    /// A mock mint signing with [`keys`]; returns its URL and the requests it received
+    fn serve_mint() -> (String, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mint_url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let received = requests.clone();
+
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(&stream);
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let mut content_length = 0;
+                loop {
+                    let mut header = String::new();
+                    reader.read_line(&mut header).unwrap();
+                    if header.trim().is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = header.split_once(':') {
+                        if name.eq_ignore_ascii_case("content-length") {
+                            content_length = value.trim().parse().unwrap();
+                        }
+                    }
+                }
+                let mut body = vec![0; content_length];
+                reader.read_exact(&mut body).unwrap();
+                let body = serde_json::from_slice(&body).unwrap_or(serde_json::Value::Null);
+
+                let mut parts = request_line.split(' ');
+                let method = parts.next().unwrap_or_default();
+                let path = parts.next().unwrap_or_default();
+                received
+                    .lock()
+                    .unwrap()
+                    .push(format!("{} {}", method, path));
+
+                let (status, body) = respond(method, path, &body);
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 {} OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+            }
+        });
+
+        (mint_url, requests)
+    }
+
+    fn client(mint_url: &str) -> MintClient {
+        let transport = MintTransport::new(MintClientConfig {
+            max_retries: 0,
+            ..Default::default()
+        })
+        .unwrap();
+        MintClient::with_transport(mint_url, transport).unwrap()
+    }
+
+    fn proofs(amounts: &[u64]) -> Proofs {
+        amounts
+            .iter()
+            .map(|&amount| Proof {
+                amount: Amount::from(amount),
+                keyset_id: keyset_id(),
+                secret: Secret::generate(),
+                c: SecretKey::generate().public_key(),
+                witness: None,
+                dleq: None,
+            })
+            .collect()
+    }
+
+    /// Whether every proof carries a valid signature of the mock mint
+    fn signed_by_mint(proofs: &[Proof]) -> bool {
+        proofs.iter().all(|proof| {
+            dhke::verify_message(&mint_key(proof.amount), proof.c, proof.secret.as_bytes()).is_ok()
+        })
+    }
+
+    fn posted(requests: &Mutex<Vec<String>>, path: &str) -> bool {
+        requests
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|request| *request == format!("POST {}", path))
+    }
+
+    #[tokio::test]
+    async fn mint_proofs_unblinds_and_verifies_the_signatures() {
+        let (mint_url, _) = serve_mint();
+
+        let minted = client(&mint_url)
+            .mint_proofs("paid", 13, &CurrencyUnit::Sat)
+            .await
+            .unwrap();
+
+        let mut amounts: Vec<u64> = minted.proofs.iter().map(|p| p.amount.into()).collect();
+        amounts.sort();
+        assert_eq!(amounts, [1, 4, 8]);
+        assert!(signed_by_mint(&minted.proofs));
+        assert_eq!(minted.verification.verified, 3);
+        assert!(minted.verification.is_fully_verified());
+    }
+
+    #[tokio::test]
+    async fn mint_proofs_surfaces_the_mint_error() {
+        let (mint_url, _) = serve_mint();
+
+        let error = client(&mint_url)
+            .mint_proofs("unpaid", 13, &CurrencyUnit::Sat)
+            .await
+            .unwrap_err();
+
+        let mint_error = error.mint_error().unwrap();
+        assert_eq!(mint_error.status, 400);
+        assert!(mint_error.is_quote_not_paid());
+    }
+
+    #[tokio::test]
+    async fn swap_returns_the_targets_and_change_after_the_input_fee() {
+        let (mint_url, _) = serve_mint();
+
+        let swapped = client(&mint_url).swap(proofs(&[8, 4]), &[5]).await.unwrap();
+
+        assert_eq!(swapped.fee, 1);
+        assert_eq!(MintClient::total_amount(&swapped.proofs), 5);
+        assert_eq!(MintClient::total_amount(&swapped.change), 6);
+        assert!(signed_by_mint(&swapped.proofs));
+        assert!(signed_by_mint(&swapped.change));
+        assert_eq!(swapped.verification.verified, 4);
+    }
+
+    #[tokio::test]
+    async fn swap_refuses_targets_the_inputs_cannot_cover() {
+        let (mint_url, requests) = serve_mint();
+
+        let error = client(&mint_url)
+            .swap(proofs(&[8, 4]), &[12])
+            .await
+            .unwrap_err();
+
+        assert!(matches!(error, Error::NotEnoughBalance(_)));
+        assert!(!posted(&requests, "/v1/swap"));
+    }
+
+    #[tokio::test]
+    async fn melt_collects_the_change_of_the_fee_reserve() {
+        let (mint_url, _) = serve_mint();
+
+        let melted = client(&mint_url)
+            .melt("paid", proofs(&[8, 8]))
+            .await
+            .unwrap();
+
+        assert!(melted.paid);
+        assert_eq!(melted.payment_preimage.as_deref(), Some("00ff"));
+        assert_eq!(MintClient::total_amount(&melted.change), 3);
+        assert!(signed_by_mint(&melted.change));
+        assert_eq!(melted.verification.verified, 2);
+        // 16 in, 10 paid, 3 back: the lightning fee plus the input fee
+        assert_eq!(melted.fee_paid, 3);
+    }
+
+    #[tokio::test]
+    async fn pending_melt_returns_no_change() {
+        let (mint_url, _) = serve_mint();
+
+        let melted = client(&mint_url)
+            .melt("pending", proofs(&[8, 8]))
+            .await
+            .unwrap();
+
+        assert!(!melted.paid);
+        assert!(melted.change.is_empty());
+        assert_eq!(melted.fee_paid, 0);
+    }
+
+    #[tokio::test]
+    async fn melt_refuses_inputs_below_amount_fee_reserve_and_input_fee() {
+        let (mint_url, requests) = serve_mint();
+
+        // 10 amount + 4 fee reserve + 1 input fee
+        let error = client(&mint_url)
+            .melt("paid", proofs(&[8, 4, 2]))
+            .await
+            .unwrap_err();
+
+        assert!(matches!(error, Error::NotEnoughBalance(_)));
+        assert!(!posted(&requests, "/v1/melt/bolt11"));
+    }
+}
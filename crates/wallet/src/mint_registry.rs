@@ -0,0 +1,413 @@
+use crate::error::{Error, Result};
+use crate::seed::SeedDerivation;
+use cdk::nuts::{CurrencyUnit, Id, KeySetInfo, Keys, MintInfo};
+use cdk::types::ProofInfo;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+const SCHEMA: &str = r#"
+CREATE TABLE IF NOT EXISTS multimint_mint (
+    mint_url TEXT PRIMARY KEY,
+    active INTEGER NOT NULL DEFAULT 1
+);
+CREATE TABLE IF NOT EXISTS multimint_archive (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    mint_url TEXT NOT NULL,
+    archived_at INTEGER NOT NULL,
+    archive TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS multimint_meta (
+    key TEXT PRIMARY KEY,
+    value TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS multimint_seed_migration (
+    mint_url TEXT NOT NULL,
+    unit TEXT NOT NULL,
+    PRIMARY KEY (mint_url, unit)
+);
+CREATE TABLE IF NOT EXISTS multimint_counter_reset (
+    mint_url TEXT NOT NULL,
+    unit TEXT NOT NULL,
+    PRIMARY KEY (mint_url, unit)
+);
+"#;
+
+/// Latest cdk-sqlite wallet migration whose `keyset` table
+/// [`MintRegistry::reset_keyset_counters_once`] knows how to write
+const KNOWN_CDK_SQLITE_MIGRATION: &str = "20250616144830_add_keyset_expiry.sql";
+
+/// A keyset of a removed mint, with its keys when the wallet had them
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchivedKeyset {
+    pub info: KeySetInfo,
+    pub keys: Option<Keys>,
+}
+
+/// Everything the wallet database held for a mint when it was removed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchivedMint {
+    pub mint_url: String,
+    /// Unix time the mint was removed
+    pub archived_at: u64,
+    pub mint_info: Option<MintInfo>,
+    pub keysets: Vec<ArchivedKeyset>,
+    pub proofs: Vec<ProofInfo>,
+}
+
+/// Multimint bookkeeping kept in the wallet database next to cdk's tables:
+/// which mints are active, the archives of removed mints and the seed
+/// derivation of the database
+#[derive(Debug, Clone)]
+pub struct MintRegistry {
+    location: RegistryLocation,
+}
+
+#[derive(Debug, Clone)]
+enum RegistryLocation {
+    Path(PathBuf),
+    /// A single connection, since every new in-memory connection is a new database
+    Memory(Arc<Mutex<Connection>>),
+}
+
+impl MintRegistry {
+    /// Open the registry in the SQLite database at `path`, creating its tables if needed
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self> {
+        let registry = Self {
+            location: RegistryLocation::Path(path.into()),
+        };
+        registry.with_connection(|conn| conn.execute_batch(SCHEMA).map_err(db_error))?;
+        Ok(registry)
+    }
+
+    /// A registry that lives as long as the wallet, for wallets whose
+    /// database is not a SQLite file
+    pub fn in_memory() -> Result<Self> {
+        let conn = Connection::open_in_memory().map_err(db_error)?;
+        conn.execute_batch(SCHEMA).map_err(db_error)?;
+        Ok(Self {
+            location: RegistryLocation::Memory(Arc::new(Mutex::new(conn))),
+        })
+    }
+
+    /// Path of the SQLite database, `:memory:` for an in-memory registry
+    pub fn path(&self) -> &Path {
+        match &self.location {
+            RegistryLocation::Path(path) => path,
+            RegistryLocation::Memory(_) => Path::new(":memory:"),
+        }
+    }
+
+    pub fn is_in_memory(&self) -> bool {
+        matches!(self.location, RegistryLocation::Memory(_))
+    }
+
+    /// Whether `mint_url` is active. Mints are active until deactivated.
+    pub fn is_active(&self, mint_url: &str) -> Result<bool> {
+        let active: Option<bool> = self.with_connection(|conn| {
+            conn.query_row(
+                "SELECT active FROM multimint_mint WHERE mint_url = ?1",
+                params![mint_url],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(db_error)
+        })?;
+        Ok(active.unwrap_or(true))
+    }
+
+    pub fn set_active(&self, mint_url: &str, active: bool) -> Result<()> {
+        self.with_connection(|conn| {
+            conn.execute(
+                "INSERT INTO multimint_mint (mint_url, active) VALUES (?1, ?2)
+                 ON CONFLICT(mint_url) DO UPDATE SET active = excluded.active",
+                params![mint_url, active],
+            )
+            .map_err(db_error)
+        })?;
+        Ok(())
+    }
+
+    pub fn inactive_mints(&self) -> Result<HashSet<String>> {
+        self.with_connection(|conn| {
+            let mut statement = conn
+                .prepare("SELECT mint_url FROM multimint_mint WHERE active = 0")
+                .map_err(db_error)?;
+            let mints = statement
+                .query_map([], |row| row.get(0))
+                .map_err(db_error)?
+                .collect::<std::result::Result<HashSet<String>, _>>()
+                .map_err(db_error)?;
+            Ok(mints)
+        })
+    }
+
+    /// Store the archive of a removed mint and forget its active flag
+    pub fn archive(&self, archive: &ArchivedMint) -> Result<()> {
+        let archive_json = serde_json::to_string(archive)?;
+        self.with_connection(|conn| {
+            let tx = conn.transaction().map_err(db_error)?;
+            tx.execute(
+                "INSERT INTO multimint_archive (mint_url, archived_at, archive) VALUES (?1, ?2, ?3)",
+                params![archive.mint_url, archive.archived_at as i64, archive_json],
+            )
+            .map_err(db_error)?;
+            tx.execute(
+                "DELETE FROM multimint_mint WHERE mint_url = ?1",
+                params![archive.mint_url],
+            )
+            .map_err(db_error)?;
+            tx.commit().map_err(db_error)
+        })
+    }
+
+    /// Archives of `mint_url`, oldest first
+    pub fn archives(&self, mint_url: &str) -> Result<Vec<ArchivedMint>> {
+        let rows = self.with_connection(|conn| {
+            let mut statement = conn
+                .prepare("SELECT archive FROM multimint_archive WHERE mint_url = ?1 ORDER BY id")
+                .map_err(db_error)?;
+            let rows = statement
+                .query_map(params![mint_url], |row| row.get::<_, String>(0))
+                .map_err(db_error)?
+                .collect::<std::result::Result<Vec<_>, _>>()
+                .map_err(db_error)?;
+            Ok(rows)
+        })?;
+
+        rows.iter()
+            .map(|archive| Ok(serde_json::from_str(archive)?))
+            .collect()
+    }
+
+    /// Seed derivation recorded for the database, if any yet
+    pub fn seed_derivation(&self) -> Result<Option<SeedDerivation>> {
+        let value: Option<String> = self.with_connection(|conn| {
+            conn.query_row(
+                "SELECT value FROM multimint_meta WHERE key = 'seed_derivation'",
+                [],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(db_error)
+        })?;
+        value
+            .map(|value| SeedDerivation::from_str(&value))
+            .transpose()
+    }
+
+    pub fn set_seed_derivation(&self, derivation: SeedDerivation) -> Result<()> {
+        self.with_connection(|conn| {
+            conn.execute(
+                "INSERT INTO multimint_meta (key, value) VALUES ('seed_derivation', ?1)
+                 ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+                params![derivation.as_str()],
+            )
+            .map_err(db_error)
+        })?;
+        Ok(())
+    }
+
+    /// Whether the proofs of `mint_url` in `unit` were moved onto the
+    /// wallet seed by a seed migration
+    pub fn is_seed_migrated(&self, mint_url: &str, unit: &CurrencyUnit) -> Result<bool> {
+        let migrated: Option<i64> = self.with_connection(|conn| {
+            conn.query_row(
+                "SELECT 1 FROM multimint_seed_migration WHERE mint_url = ?1 AND unit = ?2",
+                params![mint_url, unit.to_string()],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(db_error)
+        })?;
+        Ok(migrated.is_some())
+    }
+
+    pub fn mark_seed_migrated(&self, mint_url: &str, unit: &CurrencyUnit) -> Result<()> {
+        self.with_connection(|conn| {
+            conn.execute(
+                "INSERT OR IGNORE INTO multimint_seed_migration (mint_url, unit) VALUES (?1, ?2)",
+                params![mint_url, unit.to_string()],
+            )
+            .map_err(db_error)
+        })?;
+        Ok(())
+    }
+
+    /// Set cdk's deterministic counters of `keyset_ids` back to zero, unless
+    /// they were already reset for `mint_url` in `unit`.
+    ///
+    /// The reset is recorded in the same transaction, so a repeated seed
+    /// migration never rewinds counters the new seed has used. Returns
+    /// whether the counters were reset.
+    ///
+    /// cdk can only increment counters, so this writes the `keyset` table
+    /// of cdk-sqlite directly. It needs the registry to share the wallet's
+    /// SQLite file and refuses schemas newer than it knows.
+    pub fn reset_keyset_counters_once(
+        &self,
+        mint_url: &str,
+        unit: &CurrencyUnit,
+        keyset_ids: &[Id],
+    ) -> Result<bool> {
+        if self.is_in_memory() {
+            return Err(Error::custom(
+                "Keyset counters can only be reset in a SQLite wallet database",
+            ));
+        }
+        self.with_connection(|conn| {
+            let tx = conn.transaction().map_err(db_error)?;
+            let done: Option<i64> = tx
+                .query_row(
+                    "SELECT 1 FROM multimint_counter_reset WHERE mint_url = ?1 AND unit = ?2",
+                    params![mint_url, unit.to_string()],
+                    |row| row.get(0),
+                )
+                .optional()
+                .map_err(db_error)?;
+            if done.is_some() {
+                return Ok(false);
+            }
+
+            ensure_known_cdk_schema(&tx)?;
+            for id in keyset_ids {
+                tx.execute(
+                    "UPDATE keyset SET counter = 0 WHERE id = ?1",
+                    params![id.to_string()],
+                )
+                .map_err(db_error)?;
+            }
+            tx.execute(
+                "INSERT INTO multimint_counter_reset (mint_url, unit) VALUES (?1, ?2)",
+                params![mint_url, unit.to_string()],
+            )
+            .map_err(db_error)?;
+            tx.commit().map_err(db_error)?;
+            Ok(true)
+        })
+    }
+
+    fn with_connection<T>(&self, f: impl FnOnce(&mut Connection) -> Result<T>) -> Result<T> {
+        match &self.location {
+            RegistryLocation::Path(path) => {
+                let mut conn = Connection::open(path).map_err(db_error)?;
+                conn.busy_timeout(std::time::Duration::from_secs(5))
+                    .map_err(db_error)?;
+                f(&mut conn)
+            }
+            RegistryLocation::Memory(conn) => {
+                let mut conn = conn
+                    .lock()
+                    .map_err(|_| Error::custom("Wallet database lock poisoned"))?;
+                f(&mut conn)
+            }
+        }
+    }
+}
+
+fn db_error(e: rusqlite::Error) -> Error {
+    Error::custom(&format!("Wallet database error: {}", e))
+}
+
+/// Fail unless the database was last migrated by the cdk-sqlite version
+/// whose `keyset` table this registry writes
+fn ensure_known_cdk_schema(conn: &Connection) -> Result<()> {
+    let latest: Option<String> = conn
+        .query_row(
+            "SELECT name FROM migrations ORDER BY name DESC LIMIT 1",
+            [],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(db_error)?;
+
+    match latest {
+        Some(latest) if latest == KNOWN_CDK_SQLITE_MIGRATION => Ok(()),
+        latest => Err(Error::custom(&format!(
+            "Cannot reset keyset counters: wallet database schema {} is not the supported {}",
+            latest.unwrap_or_else(|| "unknown".to_string()),
+            KNOWN_CDK_SQLITE_MIGRATION
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn in_memory_registry_keeps_state_across_calls() {
+        let registry = MintRegistry::in_memory().unwrap();
+        assert!(registry.is_in_memory());
+        assert_eq!(registry.path(), Path::new(":memory:"));
+
+        let mint_url = "https://mint.example/";
+        assert!(registry.is_active(mint_url).unwrap());
+        registry.set_active(mint_url, false).unwrap();
+        assert!(!registry.is_active(mint_url).unwrap());
+        assert!(registry.inactive_mints().unwrap().contains(mint_url));
+
+        registry.set_seed_derivation(SeedDerivation::Bip39).unwrap();
+        assert_eq!(
+            registry.seed_derivation().unwrap(),
+            Some(SeedDerivation::Bip39)
+        );
+    }
+
+    #[tokio::test]
+    async fn keyset_counters_are_reset_once_per_mint() {
+        use crate::storage::WalletStorage;
+        use cdk::mint_url::MintUrl;
+
+        let path = std::env::temp_dir().join(format!(
+            "counter-reset-{}.sqlite",
+            crate::crypto::generate_random_secret()
+        ));
+        let localstore = WalletStorage::Path(path.clone()).open().await.unwrap();
+        let mint_url = MintUrl::from_str("https://mint.example").unwrap();
+        let keyset = KeySetInfo {
+            id: Id::from_str("009a1f293253e41e").unwrap(),
+            unit: CurrencyUnit::Sat,
+            active: true,
+            input_fee_ppk: 0,
+            final_expiry: None,
+        };
+        localstore.add_mint(mint_url.clone(), None).await.unwrap();
+        localstore
+            .add_mint_keysets(mint_url.clone(), vec![keyset.clone()])
+            .await
+            .unwrap();
+        localstore
+            .increment_keyset_counter(&keyset.id, 5)
+            .await
+            .unwrap();
+
+        let registry = MintRegistry::open(&path).unwrap();
+        let url = mint_url.to_string();
+        assert!(registry
+            .reset_keyset_counters_once(&url, &CurrencyUnit::Sat, &[keyset.id])
+            .unwrap());
+        assert_eq!(
+            localstore.get_keyset_counter(&keyset.id).await.unwrap(),
+            Some(0)
+        );
+
+        // Counters used after the reset survive a repeated migration
+        localstore
+            .increment_keyset_counter(&keyset.id, 3)
+            .await
+            .unwrap();
+        assert!(!registry
+            .reset_keyset_counters_once(&url, &CurrencyUnit::Sat, &[keyset.id])
+            .unwrap());
+        assert_eq!(
+            localstore.get_keyset_counter(&keyset.id).await.unwrap(),
+            Some(3)
+        );
+
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,191 @@
+use cdk::mint_url::MintUrl;
+use cdk::nuts::CurrencyUnit;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// A mint that could fund a multimint send
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MintCandidate {
+    pub mint_url: String,
+    pub unit: CurrencyUnit,
+    /// Spendable balance in `unit`
+    pub balance: u64,
+    /// Input fee of the mint's cheapest active keyset, in parts per thousand per proof
+    pub input_fee_ppk: u64,
+}
+
+/// Decides which mints a multimint send draws from when no mint is given
+pub trait MintSelectionPolicy: fmt::Debug + Send + Sync {
+    /// Order `candidates` from most to least preferred, leaving out mints
+    /// that must not be used
+    fn rank(&self, candidates: Vec<MintCandidate>) -> Vec<MintCandidate>;
+}
+
+/// Built-in mint selection strategies
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "strategy", content = "mints", rename_all = "snake_case")]
+pub enum MintSelectionStrategy {
+    /// Largest balance first
+    #[default]
+    MostBalance,
+    /// Lowest keyset input fee first, then largest balance
+    LowestFee,
+    /// Only the mints the recipient accepts, in the recipient's order
+    RecipientPreferred(Vec<String>),
+    /// Mints in the user's order of trust; unranked mints come last, by balance
+    TrustRanking(Vec<String>),
+}
+
+impl MintSelectionPolicy for MintSelectionStrategy {
+    fn rank(&self, mut candidates: Vec<MintCandidate>) -> Vec<MintCandidate> {
+        candidates.sort_by_key(|candidate| std::cmp::Reverse(candidate.balance));
+
+        match self {
+            MintSelectionStrategy::MostBalance => candidates,
+            MintSelectionStrategy::LowestFee => {
+                // Stable sort keeps the balance order among mints with equal fees
+                candidates.sort_by_key(|candidate| candidate.input_fee_ppk);
+                candidates
+            }
+            MintSelectionStrategy::RecipientPreferred(mints) => {
+                let mints = normalize_all(mints);
+                candidates.retain(|candidate| mints.contains(&candidate.mint_url));
+                candidates.sort_by_key(|candidate| position(&mints, &candidate.mint_url));
+                candidates
+            }
+            MintSelectionStrategy::TrustRanking(mints) => {
+                let mints = normalize_all(mints);
+                candidates.sort_by_key(|candidate| position(&mints, &candidate.mint_url));
+                candidates
+            }
+        }
+    }
+}
+
+/// The most preferred of `ranked` that holds `amount` on its own
+pub(crate) fn first_covering(ranked: Vec<MintCandidate>, amount: u64) -> Option<MintCandidate> {
+    ranked
+        .into_iter()
+        .find(|candidate| candidate.balance >= amount)
+}
+
+fn normalize_all(mints: &[String]) -> Vec<String> {
+    mints
+        .iter()
+        .map(|mint| {
+            MintUrl::from_str(mint)
+                .map(|url| url.to_string())
+                .unwrap_or_else(|_| mint.clone())
+        })
+        .collect()
+}
+
+fn position(mints: &[String], mint_url: &str) -> usize {
+    mints
+        .iter()
+        .position(|mint| mint == mint_url)
+        .unwrap_or(usize::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(mint: &str, balance: u64, input_fee_ppk: u64) -> MintCandidate {
+        MintCandidate {
+            mint_url: format!("https://{}.example", mint),
+            unit: CurrencyUnit::Sat,
+            balance,
+            input_fee_ppk,
+        }
+    }
+
+    fn candidates() -> Vec<MintCandidate> {
+        vec![
+            candidate("a", 50, 100),
+            candidate("b", 200, 0),
+            candidate("c", 50, 0),
+            candidate("d", 100, 100),
+        ]
+    }
+
+    fn mints(ranked: &[MintCandidate]) -> Vec<&str> {
+        ranked
+            .iter()
+            .map(|candidate| {
+                candidate
+                    .mint_url
+                    .trim_start_matches("https://")
+                    .trim_end_matches(".example")
+            })
+            .collect()
+    }
+
+    #[test]
+    fn most_balance_keeps_the_given_order_among_equal_balances() {
+        let ranked = MintSelectionStrategy::MostBalance.rank(candidates());
+        assert_eq!(mints(&ranked), ["b", "d", "a", "c"]);
+    }
+
+    #[test]
+    fn lowest_fee_breaks_ties_by_balance() {
+        let ranked = MintSelectionStrategy::LowestFee.rank(candidates());
+        assert_eq!(mints(&ranked), ["b", "c", "d", "a"]);
+
+        let same_fee = vec![candidate("a", 50, 0), candidate("b", 50, 0)];
+        let ranked = MintSelectionStrategy::LowestFee.rank(same_fee);
+        assert_eq!(mints(&ranked), ["a", "b"]);
+    }
+
+    #[test]
+    fn recipient_preferred_skips_mints_the_wallet_does_not_hold() {
+        let strategy = MintSelectionStrategy::RecipientPreferred(vec![
+            "https://missing.example".to_string(),
+            "https://c.example/".to_string(),
+            "https://a.example".to_string(),
+        ]);
+        let ranked = strategy.rank(candidates());
+        assert_eq!(mints(&ranked), ["c", "a"]);
+
+        let strategy =
+            MintSelectionStrategy::RecipientPreferred(vec!["https://missing.example".to_string()]);
+        assert!(strategy.rank(candidates()).is_empty());
+        assert!(MintSelectionStrategy::RecipientPreferred(Vec::new())
+            .rank(candidates())
+            .is_empty());
+    }
+
+    #[test]
+    fn trust_ranking_puts_unranked_mints_last_by_balance() {
+        let strategy = MintSelectionStrategy::TrustRanking(vec![
+            "https://c.example/".to_string(),
+            "https://missing.example".to_string(),
+            "https://a.example".to_string(),
+        ]);
+        let ranked = strategy.rank(candidates());
+        assert_eq!(mints(&ranked), ["c", "a", "b", "d"]);
+
+        let ranked = MintSelectionStrategy::TrustRanking(Vec::new()).rank(candidates());
+        assert_eq!(mints(&ranked), ["b", "d", "a", "c"]);
+    }
+
+    #[test]
+    fn balances_too_small_are_passed_over_but_stay_ranked() {
+        let strategy = MintSelectionStrategy::TrustRanking(vec![
+            "https://a.example".to_string(),
+            "https://d.example".to_string(),
+        ]);
+        let ranked = strategy.rank(candidates());
+        assert_eq!(mints(&ranked), ["a", "d", "b", "c"]);
+
+        let covering = first_covering(ranked.clone(), 50).unwrap();
+        assert_eq!(covering.mint_url, "https://a.example");
+        let covering = first_covering(ranked.clone(), 51).unwrap();
+        assert_eq!(covering.mint_url, "https://d.example");
+        let covering = first_covering(ranked.clone(), 101).unwrap();
+        assert_eq!(covering.mint_url, "https://b.example");
+        assert!(first_covering(ranked, 201).is_none());
+        assert!(first_covering(Vec::new(), 1).is_none());
+    }
+}
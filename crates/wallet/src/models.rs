@@ -1,3 +1,5 @@
+use cdk::nuts::{CurrencyUnit, Proofs};
+use cdk::wallet::PreparedSend;
 use serde::{Deserialize, Serialize};
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -13,3 +15,64 @@ pub struct SendTokenPendingResponse {
     pub key: String,
     pub key_id: String,
 }
+
+/// Fee handling for a send
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub struct SendFeeOptions {
+    /// Add the fee the receiver pays to redeem the token, so they end up
+    /// with exactly the amount sent
+    #[serde(default)]
+    pub include_fees: bool,
+}
+
+/// What a send would spend, without spending anything
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SendPreview {
+    pub mint_url: String,
+    pub unit: CurrencyUnit,
+    /// Requested amount
+    pub amount: u64,
+    /// Proofs that would be spent, including those swapped for exact amounts
+    pub proofs: Proofs,
+    /// Input fee the sender pays to swap for exact amounts
+    pub swap_fee: u64,
+    /// Input fee the receiver pays to redeem the token
+    pub redeem_fee: u64,
+    /// Value of the token: `amount`, plus `redeem_fee` when fees are included
+    pub token_amount: u64,
+    /// What the receiver keeps after redeeming
+    pub received: u64,
+    /// Returned to the sender's wallet
+    pub change: u64,
+}
+
+impl SendPreview {
+    pub(crate) fn from_prepared(
+        mint_url: &str,
+        unit: &CurrencyUnit,
+        prepared: &PreparedSend,
+    ) -> Self {
+        let amount = u64::from(prepared.amount());
+        let swap_fee = u64::from(prepared.swap_fee());
+        let redeem_fee = u64::from(prepared.send_fee());
+        let token_amount = if prepared.options().include_fee {
+            amount + redeem_fee
+        } else {
+            amount
+        };
+        let proofs = prepared.proofs();
+        let input_total: u64 = proofs.iter().map(|proof| u64::from(proof.amount)).sum();
+
+        Self {
+            mint_url: mint_url.to_string(),
+            unit: unit.clone(),
+            amount,
+            proofs,
+            swap_fee,
+            redeem_fee,
+            token_amount,
+            received: token_amount.saturating_sub(redeem_fee),
+            change: input_total.saturating_sub(token_amount + swap_fee),
+        }
+    }
+}
@@ -1,28 +1,56 @@
 use crate::{
+    crypto,
     error::{Error, Result},
-    models::SendTokenPendingResponse,
-    wallet::CashuWalletClient,
+    inventory::{
+        ConsolidationOptions, ConsolidationResult, KeysetMigration, ProofInventory, ProofStateSync,
+    },
+    lightning::{unix_now, LightningManager, MeltRequest, ResumeReport, TopupRequest},
+    mint::{MintTransport, PaymentMethod, CHECK_STATE_BATCH_SIZE},
+    mint_registry::{ArchivedKeyset, ArchivedMint, MintRegistry},
+    mint_selection::{self, MintCandidate, MintSelectionPolicy, MintSelectionStrategy},
+    models::{SendFeeOptions, SendPreview, SendTokenPendingResponse},
+    price::{self, PriceOracle, StaticPriceOracle},
+    quote_store::QuoteStore,
+    seed::{self, SeedDerivation, SeedMigration},
+    storage::{WalletStorage, WalletStore},
+    token_bundle::{BundlePart, TokenBundle},
+    wallet::{cdk_send_options, CashuWalletClient},
 };
+use std::path::PathBuf;
+use std::time::Duration;
 use std::{collections::HashMap, str::FromStr, sync::Arc};
-
-use bip39::Mnemonic;
+use tokio::{
+    sync::{mpsc, Mutex},
+    task::JoinHandle,
+};
 
 use cdk::{
-    cdk_database::{self, WalletDatabase},
+    amount::SplitTarget,
     mint_url::MintUrl,
-    nuts::CurrencyUnit,
+    nuts::{CurrencyUnit, Id, State, Token},
     wallet::{
-        multi_mint_wallet::MultiMintWallet as CdkMultiMintWallet, types::WalletKey, ReceiveOptions,
-        SendOptions,
+        multi_mint_wallet::MultiMintWallet as CdkMultiMintWallet, types::WalletKey, PreparedSend,
+        ReceiveOptions, Wallet, WalletBuilder,
     },
     Amount,
 };
-use cdk_sqlite::WalletSqliteDatabase;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MultimintBalance {
+    /// Total across all active mints, in sats
     pub total_balance: u64,
+    /// Unit `display_total` is reported in
+    pub display_unit: CurrencyUnit,
+    pub display_total: u64,
+    /// Units whose price was missing, leaving some balances out of the totals
+    #[serde(default)]
+    pub unpriced_units: Vec<CurrencyUnit>,
+    /// One entry per mint and unit
+    pub balances: Vec<MintBalance>,
+    /// The entries of `balances` keyed by mint URL, holding one unit per mint
+    #[deprecated(note = "holds only one unit per mint, use `balances`")]
+    #[serde(default)]
     pub balances_by_mint: HashMap<String, MintBalance>,
 }
 
@@ -31,6 +59,8 @@ pub struct MintBalance {
     pub mint_url: String,
     pub balance: u64,
     pub unit: CurrencyUnit,
+    /// `balance` converted to the display unit, `None` without a price for `unit`
+    pub display_balance: Option<u64>,
     pub proof_count: usize,
 }
 
@@ -39,41 +69,277 @@ pub struct MultimintSendOptions {
     pub preferred_mint: Option<String>,
     pub unit: Option<CurrencyUnit>,
     pub split_across_mints: bool,
+    /// How mints are chosen when `preferred_mint` is `None` or the send is split
+    #[serde(default)]
+    pub strategy: MintSelectionStrategy,
+    /// Custom policy used instead of `strategy`
+    #[serde(skip)]
+    pub policy: Option<Arc<dyn MintSelectionPolicy>>,
+    #[serde(default, flatten)]
+    pub fees: SendFeeOptions,
+}
+
+/// The token drawn from one mint during a multimint send
+pub type MintSendPart = BundlePart;
+
+/// The tokens of a multimint send, one part per mint used; see
+/// [`TokenBundle::mints_used`]
+pub type MultimintSendResult = TokenBundle;
+
+/// What happened to one mint's tokens when receiving a [`TokenBundle`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MintReceiveOutcome {
+    pub mint_url: String,
+    pub unit: CurrencyUnit,
+    /// Value of the tokens for this mint
+    pub amount: u64,
+    /// Amount added to the wallet after fees, 0 when the receive failed
+    pub received: u64,
+    pub error: Option<String>,
+}
+
+/// Per-mint results of receiving a [`TokenBundle`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundleReceipt {
+    pub outcomes: Vec<MintReceiveOutcome>,
+}
+
+impl BundleReceipt {
+    pub fn total_received(&self) -> u64 {
+        self.outcomes.iter().map(|outcome| outcome.received).sum()
+    }
+
+    /// Whether every mint's tokens were received
+    pub fn is_complete(&self) -> bool {
+        self.outcomes.iter().all(|outcome| outcome.error.is_none())
+    }
+
+    pub fn failures(&self) -> Vec<&MintReceiveOutcome> {
+        self.outcomes
+            .iter()
+            .filter(|outcome| outcome.error.is_some())
+            .collect()
+    }
+}
+
+/// The quotes behind a transfer between two mints, fetched before anything is spent
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransferQuote {
+    pub from_mint: String,
+    pub to_mint: String,
+    /// Amount arriving at `to_mint`, in `to_unit`
+    pub amount: u64,
+    pub to_unit: CurrencyUnit,
+    pub mint_quote_id: String,
+    pub payment_request: String,
+    pub melt_quote_id: String,
+    /// Amount the source mint pays out, in `from_unit`
+    pub melt_amount: u64,
+    /// Lightning fee reserve of the melt quote
+    pub fee_reserve: u64,
+    /// Input fee of the proofs the melt would spend
+    pub input_fee: u64,
+    pub from_unit: CurrencyUnit,
+}
+
+impl TransferQuote {
+    /// Most the transfer can cost on top of `melt_amount`; unused fee reserve comes back as change
+    pub fn max_fee(&self) -> u64 {
+        self.fee_reserve + self.input_fee
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransferResult {
+    pub from_mint: String,
+    pub to_mint: String,
+    /// Amount taken from `from_mint`, excluding fees
+    pub amount_sent: u64,
+    pub amount_received: u64,
+    pub fee_paid: u64,
+    pub mint_quote_id: String,
+    pub melt_quote_id: String,
+}
+
+/// Builds a [`MultimintWallet`] with a chosen passphrase, transport and storage.
+///
+/// Storage defaults to `multimint.sqlite` in the working directory. The
+/// mint registry shares a SQLite file storage and the transfer quotes are
+/// kept next to it; other storage keeps both in memory unless
+/// [`registry`](Self::registry) and [`quote_store`](Self::quote_store) say
+/// otherwise. A custom store holding an existing wallet needs a persistent
+/// registry, which records the database's [`SeedDerivation`].
+#[derive(Debug, Clone)]
+pub struct MultimintWalletBuilder {
+    seed: String,
+    passphrase: String,
+    transport: MintTransport,
+    storage: WalletStorage,
+    registry: Option<MintRegistry>,
+    quote_store: Option<QuoteStore>,
+}
+
+impl MultimintWalletBuilder {
+    pub fn new(seed: &str) -> Self {
+        Self {
+            seed: seed.to_string(),
+            passphrase: String::new(),
+            transport: MintTransport::shared(),
+            storage: WalletStorage::Path(PathBuf::from("multimint.sqlite")),
+            registry: None,
+            quote_store: None,
+        }
+    }
+
+    /// BIP39 passphrase protecting the mnemonic
+    pub fn passphrase(mut self, passphrase: &str) -> Self {
+        self.passphrase = passphrase.to_string();
+        self
+    }
+
+    pub fn transport(mut self, transport: MintTransport) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    pub fn storage(mut self, storage: WalletStorage) -> Self {
+        self.storage = storage;
+        self
+    }
+
+    /// SQLite database at `path`
+    pub fn db_path(self, path: impl Into<PathBuf>) -> Self {
+        self.storage(WalletStorage::Path(path.into()))
+    }
+
+    /// Empty in-memory database
+    pub fn in_memory(self) -> Self {
+        self.storage(WalletStorage::Memory)
+    }
+
+    /// Any cdk wallet database
+    pub fn localstore(self, localstore: WalletStore) -> Self {
+        self.storage(WalletStorage::Custom(localstore))
+    }
+
+    pub fn registry(mut self, registry: MintRegistry) -> Self {
+        self.registry = Some(registry);
+        self
+    }
+
+    pub fn quote_store(mut self, quote_store: QuoteStore) -> Self {
+        self.quote_store = Some(quote_store);
+        self
+    }
+
+    pub async fn build(self) -> Result<MultimintWallet> {
+        let seed: Arc<[u8]> = Arc::new(seed::derive_seed(&self.seed, &self.passphrase)?);
+
+        let localstore = self.storage.open().await?;
+        let db_path = self.storage.path()?;
+
+        let registry = match (self.registry, &db_path) {
+            (Some(registry), _) => registry,
+            (None, Some(db_path)) => MintRegistry::open(db_path)?,
+            (None, None) => MintRegistry::in_memory()?,
+        };
+        let quote_store = self.quote_store.or_else(|| {
+            db_path.map(|db_path| QuoteStore::new(format!("{}.quotes.json", db_path.display())))
+        });
+        let lightning = match quote_store {
+            Some(store) => LightningManager::with_store(self.transport.clone(), store)?,
+            None => LightningManager::with_transport(self.transport.clone()),
+        };
+
+        let derivation = match registry.seed_derivation()? {
+            Some(derivation) => derivation,
+            None => {
+                // Databases from before the derivation was recorded that
+                // already know mints were written with the legacy seed
+                let known_mints = localstore
+                    .get_mints()
+                    .await
+                    .map_err(|e| Error::custom(&e.to_string()))?;
+                let derivation = if known_mints.is_empty() {
+                    SeedDerivation::Bip39
+                } else {
+                    SeedDerivation::Legacy32
+                };
+                registry.set_seed_derivation(derivation)?;
+                derivation
+            }
+        };
+        let legacy_seed: Option<Arc<[u8]>> = match derivation {
+            SeedDerivation::Bip39 => None,
+            SeedDerivation::Legacy32 => Some(Arc::new(seed::legacy_seed(&self.seed)?)),
+        };
+
+        let inner = CdkMultiMintWallet::new(localstore, seed.clone(), vec![]);
+
+        Ok(MultimintWallet {
+            inner,
+            seed,
+            legacy_seed,
+            transport: self.transport,
+            registry,
+            lightning: Arc::new(Mutex::new(lightning)),
+            price_oracle: None,
+        })
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct MultimintWallet {
     inner: CdkMultiMintWallet,
+    seed: Arc<[u8]>,
+    /// Seed of the mints not yet moved off [`SeedDerivation::Legacy32`]
+    legacy_seed: Option<Arc<[u8]>>,
+    transport: MintTransport,
+    registry: MintRegistry,
+    /// Tracks the quotes of transfers between mints so they can be resumed
+    lightning: Arc<Mutex<LightningManager>>,
+    /// Prices for converting fiat balances, needed once a fiat mint holds funds
+    price_oracle: Option<Arc<dyn PriceOracle>>,
 }
 
 impl MultimintWallet {
     pub async fn new(seed: &str, base_db_path: &str) -> Result<Self> {
-        let mnemonic = Mnemonic::from_str(seed).map_err(|_| Error::custom("Invalid mnemonic"))?;
-        let seed_full = mnemonic.to_seed_normalized("");
-        let seed_bytes: [u8; 32] = seed_full[0..32]
-            .try_into()
-            .map_err(|_| Error::custom("Invalid seed length"))?;
+        Self::with_transport(seed, base_db_path, MintTransport::shared()).await
+    }
 
-        let db_path = format!("{}.sqlite", base_db_path);
+    pub async fn with_transport(
+        seed: &str,
+        base_db_path: &str,
+        transport: MintTransport,
+    ) -> Result<Self> {
+        Self::builder(seed)
+            .db_path(format!("{}.sqlite", base_db_path))
+            .transport(transport)
+            .build()
+            .await
+    }
 
-        let localstore: Arc<dyn WalletDatabase<Err = cdk_database::Error> + Send + Sync> = Arc::new(
-            WalletSqliteDatabase::new(&db_path)
-                .await
-                .map_err(|e| Error::custom(&e.to_string()))?,
-        );
+    pub fn builder(seed: &str) -> MultimintWalletBuilder {
+        MultimintWalletBuilder::new(seed)
+    }
 
-        let inner = CdkMultiMintWallet::new(localstore, Arc::new(seed_bytes), vec![]);
+    pub fn with_price_oracle(mut self, oracle: Arc<dyn PriceOracle>) -> Self {
+        self.price_oracle = Some(oracle);
+        self
+    }
 
-        Ok(Self { inner })
+    pub fn price_oracle(&self) -> Option<&Arc<dyn PriceOracle>> {
+        self.price_oracle.as_ref()
     }
 
     pub async fn from_existing_wallet(
-        _wallet: &CashuWalletClient,
+        wallet: &CashuWalletClient,
         mint_url: &str,
         seed: &str,
         base_db_path: &str,
     ) -> Result<Self> {
-        let multimint = Self::new(seed, base_db_path).await?;
+        let multimint =
+            Self::with_transport(seed, base_db_path, wallet.transport().clone()).await?;
         multimint
             .add_mint(mint_url, Some(CurrencyUnit::Msat))
             .await?;
@@ -82,26 +348,228 @@ impl MultimintWallet {
 
     pub async fn add_mint(&self, mint_url: &str, unit: Option<CurrencyUnit>) -> Result<()> {
         let currency_unit = unit.unwrap_or(CurrencyUnit::Msat);
-        let _mint_url_parsed =
+        let mint_url_parsed =
             MintUrl::from_str(mint_url).map_err(|e| Error::custom(&e.to_string()))?;
 
-        let _wallet = self
-            .inner
-            .create_and_add_wallet(mint_url, currency_unit.clone(), None)
+        if self.legacy_seed.is_some() {
+            let known = self
+                .inner
+                .localstore
+                .get_mint(mint_url_parsed.clone())
+                .await
+                .map_err(|e| Error::custom(&e.to_string()))?
+                .is_some();
+            // A mint new to a legacy database has nothing to migrate
+            if !known {
+                self.registry
+                    .mark_seed_migrated(&mint_url_parsed.to_string(), &currency_unit)?;
+            }
+        }
+
+        let seed = self.seed_for(&mint_url_parsed, &currency_unit)?;
+        let wallet = self.build_wallet(mint_url_parsed, currency_unit, &seed)?;
+
+        wallet
+            .get_mint_info()
             .await
             .map_err(|e| Error::custom(&e.to_string()))?;
 
+        self.inner.add_wallet(wallet).await;
+
         Ok(())
     }
 
+    fn build_wallet(&self, mint_url: MintUrl, unit: CurrencyUnit, seed: &[u8]) -> Result<Wallet> {
+        WalletBuilder::new()
+            .mint_url(mint_url.clone())
+            .unit(unit)
+            .localstore(self.inner.localstore.clone())
+            .seed(seed)
+            .target_proof_count(3)
+            .client(self.transport.cdk_client(mint_url)?)
+            .build()
+            .map_err(|e| Error::custom(&e.to_string()))
+    }
+
+    /// The seed the wallet of `mint_url` in `unit` derives its secrets from
+    fn seed_for(&self, mint_url: &MintUrl, unit: &CurrencyUnit) -> Result<Arc<[u8]>> {
+        match &self.legacy_seed {
+            Some(legacy_seed)
+                if !self
+                    .registry
+                    .is_seed_migrated(&mint_url.to_string(), unit)? =>
+            {
+                Ok(legacy_seed.clone())
+            }
+            _ => Ok(self.seed.clone()),
+        }
+    }
+
+    /// Whether the database still holds proofs derived from the legacy
+    /// 32-byte seed; see [`Self::migrate_seed_derivation`]
+    pub fn needs_seed_migration(&self) -> bool {
+        self.legacy_seed.is_some()
+    }
+
+    /// Move a legacy database onto the seed of [`seed::derive_seed`].
+    ///
+    /// For each mint and unit still on the legacy seed, the keyset counters
+    /// are reset and all unspent proofs are swapped into outputs derived
+    /// from the wallet seed, so a restore from the mnemonic and passphrase
+    /// finds them. Mints are marked as they finish; a failed run can be
+    /// repeated and continues with the remaining mints. Counters are reset
+    /// only once per mint and unit, so a repeated run never reuses them.
+    pub async fn migrate_seed_derivation(&mut self) -> Result<Vec<SeedMigration>> {
+        if self.legacy_seed.is_none() {
+            return Ok(Vec::new());
+        }
+
+        let mut migrations = Vec::new();
+        for wallet in self.inner.get_wallets().await {
+            let mint_url = wallet.mint_url.to_string();
+            if self.registry.is_seed_migrated(&mint_url, &wallet.unit)? {
+                continue;
+            }
+
+            let keyset_ids: Vec<Id> = self
+                .inner
+                .localstore
+                .get_mint_keysets(wallet.mint_url.clone())
+                .await
+                .map_err(|e| Error::custom(&e.to_string()))?
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|keyset| keyset.unit == wallet.unit)
+                .map(|keyset| keyset.id)
+                .collect();
+            // Start the new seed's secrets at counter 0 so a restore finds them
+            self.registry
+                .reset_keyset_counters_once(&mint_url, &wallet.unit, &keyset_ids)?;
+
+            let migrated =
+                self.build_wallet(wallet.mint_url.clone(), wallet.unit.clone(), &self.seed)?;
+            let proofs = wallet.get_unspent_proofs().await?;
+            let proofs_moved = proofs.len();
+            let total: u64 = proofs.iter().map(|p| u64::from(p.amount)).sum();
+            let mut fee = 0;
+            if !proofs.is_empty() {
+                fee = migrated.get_proofs_fee(&proofs).await?.into();
+                migrated
+                    .swap(None, SplitTarget::None, proofs, None, false)
+                    .await?;
+            }
+
+            self.registry.mark_seed_migrated(&mint_url, &wallet.unit)?;
+            migrations.push(SeedMigration {
+                mint_url,
+                unit: wallet.unit.clone(),
+                proofs_moved,
+                amount_moved: total.saturating_sub(fee),
+                fee,
+            });
+            self.inner.add_wallet(migrated).await;
+        }
+
+        self.registry.set_seed_derivation(SeedDerivation::Bip39)?;
+        self.legacy_seed = None;
+        Ok(migrations)
+    }
+
+    /// Detach a mint and archive what the wallet database held for it.
+    ///
+    /// The mint must have no unspent, pending or reserved proofs left. Its
+    /// mint info, keysets, keys and spent proofs are moved into the archive
+    /// of the [`MintRegistry`] and removed from the live tables.
     pub async fn remove_mint(&self, mint_url: &str) -> Result<()> {
-        let balance = self.get_mint_balance(mint_url).await?;
-        if balance > 0 {
+        let mint_url_parsed =
+            MintUrl::from_str(mint_url).map_err(|e| Error::custom(&e.to_string()))?;
+        let wallets: Vec<_> = self
+            .inner
+            .get_wallets()
+            .await
+            .into_iter()
+            .filter(|wallet| wallet.mint_url == mint_url_parsed)
+            .collect();
+        if wallets.is_empty() {
+            return Err(Error::custom(&format!(
+                "Mint {} not found in multimint wallet",
+                mint_url
+            )));
+        }
+
+        // Checked on the proofs of every unit, not a balance of one unit
+        let localstore = &self.inner.localstore;
+        let held = localstore
+            .get_proofs(
+                Some(mint_url_parsed.clone()),
+                None,
+                Some(vec![
+                    State::Unspent,
+                    State::Pending,
+                    State::Reserved,
+                    State::PendingSpent,
+                ]),
+                None,
+            )
+            .await
+            .map_err(|e| Error::custom(&e.to_string()))?;
+        if !held.is_empty() {
             return Err(Error::custom(
-                "Cannot remove mint with non-zero balance. Transfer funds first.",
+                "Cannot remove mint with unspent or pending proofs. Transfer or settle them first.",
             ));
         }
 
+        let proofs = localstore
+            .get_proofs(Some(mint_url_parsed.clone()), None, None, None)
+            .await
+            .map_err(|e| Error::custom(&e.to_string()))?;
+        let mut keysets = Vec::new();
+        for info in localstore
+            .get_mint_keysets(mint_url_parsed.clone())
+            .await
+            .map_err(|e| Error::custom(&e.to_string()))?
+            .unwrap_or_default()
+        {
+            let keys = localstore
+                .get_keys(&info.id)
+                .await
+                .map_err(|e| Error::custom(&e.to_string()))?;
+            keysets.push(ArchivedKeyset { info, keys });
+        }
+        let mint_info = localstore
+            .get_mint(mint_url_parsed.clone())
+            .await
+            .map_err(|e| Error::custom(&e.to_string()))?;
+
+        self.registry.archive(&ArchivedMint {
+            mint_url: mint_url_parsed.to_string(),
+            archived_at: unix_now(),
+            mint_info,
+            keysets: keysets.clone(),
+            proofs: proofs.clone(),
+        })?;
+
+        localstore
+            .update_proofs(Vec::new(), proofs.iter().map(|proof| proof.y).collect())
+            .await
+            .map_err(|e| Error::custom(&e.to_string()))?;
+        for keyset in &keysets {
+            localstore
+                .remove_keys(&keyset.info.id)
+                .await
+                .map_err(|e| Error::custom(&e.to_string()))?;
+        }
+        localstore
+            .remove_mint(mint_url_parsed)
+            .await
+            .map_err(|e| Error::custom(&e.to_string()))?;
+
+        for wallet in wallets {
+            self.inner
+                .remove_wallet(&WalletKey::new(wallet.mint_url, wallet.unit))
+                .await;
+        }
+
         Ok(())
     }
 
@@ -110,40 +578,321 @@ impl MultimintWallet {
         wallets.iter().map(|f| f.mint_url.to_string()).collect()
     }
 
+    /// Mints that take part in sends, balances and topups
+    pub async fn list_active_mints(&self) -> Result<Vec<String>> {
+        let inactive = self.registry.inactive_mints()?;
+        Ok(self
+            .list_mints()
+            .await
+            .into_iter()
+            .filter(|mint_url| !inactive.contains(mint_url))
+            .collect())
+    }
+
     pub async fn get_total_balance(&self) -> Result<MultimintBalance> {
-        let mut total_balance = 0u64;
-        let mut balances_by_mint = HashMap::new();
+        self.get_total_balance_in(&CurrencyUnit::Sat).await
+    }
 
-        for unit in [CurrencyUnit::Msat, CurrencyUnit::Sat] {
-            let balances = self
-                .inner
-                .get_balances(&unit)
-                .await
-                .map_err(|e| Error::custom(&e.to_string()))?;
+    /// Balances of all active mints in every unit, totalled in `display_unit`.
+    ///
+    /// Balances in units other than sat and msat are converted with the
+    /// wallet's price oracle. Without a price for their unit they are still
+    /// listed per mint but left out of the totals and their unit is named in
+    /// [`MultimintBalance::unpriced_units`]. Without a price for a fiat
+    /// `display_unit`, sat and msat balances still count towards
+    /// `total_balance` and `display_unit` itself is named instead.
+    pub async fn get_total_balance_in(
+        &self,
+        display_unit: &CurrencyUnit,
+    ) -> Result<MultimintBalance> {
+        let inactive = self.registry.inactive_mints()?;
+        let mut total_msat = 0u64;
+        let mut display_total = 0u64;
+        let mut unpriced_units = Vec::new();
+        let mut balances = Vec::new();
+
+        for wallet in self.inner.get_wallets().await {
+            let mint_url = wallet.mint_url.to_string();
+            if inactive.contains(&mint_url) {
+                continue;
+            }
 
-            for (mint_url, amount) in balances {
-                let balance: u64 = amount.into();
-                let normalized_balance = self.normalize_to_sats(balance, &unit);
-                total_balance += normalized_balance;
+            let proofs = wallet.get_unspent_proofs().await?;
+            let balance: u64 = proofs.iter().map(|proof| u64::from(proof.amount)).sum();
+            if balance == 0 {
+                continue;
+            }
 
-                balances_by_mint.insert(
-                    mint_url.to_string(),
-                    MintBalance {
-                        mint_url: mint_url.to_string(),
-                        balance,
-                        unit: unit.clone(),
-                        proof_count: 0,
-                    },
-                );
+            // sat and msat convert to each other exactly, so a missing fiat
+            // price only drops the balance from the total it is needed for
+            let msat = self.convert(balance, &wallet.unit, &CurrencyUnit::Msat);
+            let display_balance = self.convert(balance, &wallet.unit, display_unit).ok();
+            let unpriced = match (&msat, display_balance) {
+                (Err(_), _) => Some(&wallet.unit),
+                (Ok(_), None) => Some(display_unit),
+                (Ok(_), Some(_)) => None,
+            };
+            total_msat += msat.unwrap_or(0);
+            display_total += display_balance.unwrap_or(0);
+            if let Some(unit) = unpriced {
+                if !unpriced_units.contains(unit) {
+                    unpriced_units.push(unit.clone());
+                }
             }
+
+            balances.push(MintBalance {
+                mint_url,
+                balance,
+                unit: wallet.unit.clone(),
+                display_balance,
+                proof_count: proofs.len(),
+            });
         }
 
+        let balances_by_mint = balances
+            .iter()
+            .map(|balance| (balance.mint_url.clone(), balance.clone()))
+            .collect();
+
+        #[allow(deprecated)]
         Ok(MultimintBalance {
-            total_balance,
+            total_balance: total_msat / 1000,
+            display_unit: display_unit.clone(),
+            display_total,
+            unpriced_units,
+            balances,
             balances_by_mint,
         })
     }
 
+    /// Unspent proofs of every active mint and unit, by keyset and denomination
+    pub async fn proof_inventory(&self) -> Result<Vec<ProofInventory>> {
+        let inactive = self.registry.inactive_mints()?;
+        let mut inventories = Vec::new();
+
+        for wallet in self.inner.get_wallets().await {
+            let mint_url = wallet.mint_url.to_string();
+            if inactive.contains(&mint_url) {
+                continue;
+            }
+
+            let proofs = wallet.get_unspent_proofs().await?;
+            if !proofs.is_empty() {
+                inventories.push(ProofInventory::from_proofs(
+                    &mint_url,
+                    wallet.unit.clone(),
+                    &proofs,
+                ));
+            }
+        }
+
+        Ok(inventories)
+    }
+
+    /// Swap the unspent proofs of `mint_url`, or of every active mint, into
+    /// the denominations of the active keyset.
+    ///
+    /// Mints with fewer than `options.min_proofs` proofs, or where the swap
+    /// would not reduce the number of proofs, are left alone.
+    pub async fn consolidate(
+        &self,
+        mint_url: Option<&str>,
+        options: &ConsolidationOptions,
+    ) -> Result<Vec<ConsolidationResult>> {
+        let mint_url = mint_url
+            .map(|mint_url| {
+                MintUrl::from_str(mint_url)
+                    .map(|url| url.to_string())
+                    .map_err(|e| Error::custom(&e.to_string()))
+            })
+            .transpose()?;
+        let inactive = self.registry.inactive_mints()?;
+        let mut results = Vec::new();
+
+        for wallet in self.inner.get_wallets().await {
+            let wallet_mint = wallet.mint_url.to_string();
+            if inactive.contains(&wallet_mint)
+                || mint_url
+                    .as_ref()
+                    .is_some_and(|mint_url| *mint_url != wallet_mint)
+            {
+                continue;
+            }
+
+            let proofs = wallet.get_unspent_proofs().await?;
+            if proofs.is_empty() || proofs.len() < options.min_proofs {
+                continue;
+            }
+
+            let total: u64 = proofs.iter().map(|proof| u64::from(proof.amount)).sum();
+            let fee: u64 = wallet.get_proofs_fee(&proofs).await?.into();
+            let Some(available) = total.checked_sub(fee).filter(|&available| available > 0) else {
+                continue;
+            };
+
+            let keyset = wallet.get_active_mint_keyset().await?;
+            let keys = wallet.get_keyset_keys(keyset.id).await?;
+            let targets =
+                options.target_amounts(available, &crypto::keyset_denominations(&keys))?;
+            if targets.len() >= proofs.len() {
+                continue;
+            }
+
+            let proofs_before = proofs.len();
+            let proofs_after = targets.len();
+            wallet
+                .swap(
+                    None,
+                    SplitTarget::Values(targets.into_iter().map(Amount::from).collect()),
+                    proofs,
+                    None,
+                    false,
+                )
+                .await?;
+
+            results.push(ConsolidationResult {
+                mint_url: wallet_mint,
+                unit: wallet.unit.clone(),
+                proofs_before,
+                proofs_after,
+                fee,
+            });
+        }
+
+        Ok(results)
+    }
+
+    /// Swap proofs on keysets a mint has rotated out into its active keyset,
+    /// before the mint stops honoring them.
+    ///
+    /// Keysets are refreshed from every active mint first. Returns one entry
+    /// per mint and unit that had such proofs.
+    pub async fn migrate_inactive_keysets(&self) -> Result<Vec<KeysetMigration>> {
+        let inactive = self.registry.inactive_mints()?;
+        let mut migrations = Vec::new();
+
+        for wallet in self.inner.get_wallets().await {
+            let mint_url = wallet.mint_url.to_string();
+            if inactive.contains(&mint_url) {
+                continue;
+            }
+
+            let inactive_ids: Vec<Id> = wallet
+                .get_mint_keysets()
+                .await?
+                .into_iter()
+                .filter(|keyset| !keyset.active && keyset.unit == wallet.unit)
+                .map(|keyset| keyset.id)
+                .collect();
+            let stale_proofs: Vec<_> = wallet
+                .get_unspent_proofs()
+                .await?
+                .into_iter()
+                .filter(|proof| inactive_ids.contains(&proof.keyset_id))
+                .collect();
+            if stale_proofs.is_empty() {
+                continue;
+            }
+
+            let mut inactive_keysets: Vec<String> = stale_proofs
+                .iter()
+                .map(|proof| proof.keyset_id.to_string())
+                .collect();
+            inactive_keysets.sort();
+            inactive_keysets.dedup();
+
+            let proofs_moved = stale_proofs.len();
+            let total: u64 = stale_proofs.iter().map(|p| u64::from(p.amount)).sum();
+            let fee: u64 = wallet.get_proofs_fee(&stale_proofs).await?.into();
+            wallet
+                .swap(None, SplitTarget::None, stale_proofs, None, false)
+                .await?;
+
+            migrations.push(KeysetMigration {
+                mint_url,
+                unit: wallet.unit.clone(),
+                inactive_keysets,
+                proofs_moved,
+                amount_moved: total.saturating_sub(fee),
+                fee,
+            });
+        }
+
+        Ok(migrations)
+    }
+
+    /// Check the unspent proofs of every active mint against their NUT-07
+    /// states, removing those spent elsewhere and reporting pending ones
+    pub async fn sync_proof_states(&self) -> Result<Vec<ProofStateSync>> {
+        let inactive = self.registry.inactive_mints()?;
+        let mut syncs = Vec::new();
+
+        for wallet in self.inner.get_wallets().await {
+            let mint_url = wallet.mint_url.to_string();
+            if inactive.contains(&mint_url) {
+                continue;
+            }
+
+            let proofs = wallet.get_unspent_proofs().await?;
+            if proofs.is_empty() {
+                continue;
+            }
+
+            let mut sync = ProofStateSync {
+                mint_url,
+                unit: wallet.unit.clone(),
+                checked: proofs.len(),
+                spent_count: 0,
+                spent_amount: 0,
+                pending: Vec::new(),
+            };
+
+            for batch in proofs.chunks(CHECK_STATE_BATCH_SIZE) {
+                // cdk removes the proofs reported spent from the wallet database
+                let states = wallet.check_proofs_spent(batch.to_vec()).await?;
+                for (proof, proof_state) in batch.iter().zip(states) {
+                    match proof_state.state {
+                        State::Spent => {
+                            sync.spent_count += 1;
+                            sync.spent_amount += u64::from(proof.amount);
+                        }
+                        State::Pending => sync.pending.push(proof.clone()),
+                        _ => {}
+                    }
+                }
+            }
+
+            syncs.push(sync);
+        }
+
+        Ok(syncs)
+    }
+
+    /// Run [`Self::sync_proof_states`] every `interval` in the background
+    /// until the returned handle is aborted.
+    ///
+    /// The outcome of every round, including errors, is sent to the returned
+    /// receiver, and a failed round is retried at the next interval. Once
+    /// the receiver is dropped the rounds go on unreported.
+    pub fn spawn_proof_state_sync(
+        &self,
+        interval: Duration,
+    ) -> (JoinHandle<()>, mpsc::Receiver<Result<Vec<ProofStateSync>>>) {
+        let wallet = self.clone();
+        let (sender, receiver) = mpsc::channel(8);
+        let handle = tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                let round = wallet.sync_proof_states().await;
+                if !sender.is_closed() {
+                    let _ = sender.send(round).await;
+                }
+            }
+        });
+        (handle, receiver)
+    }
+
     pub async fn get_mint_balance(&self, mint_url: &str) -> Result<u64> {
         let mint_url_parsed =
             MintUrl::from_str(mint_url).map_err(|e| Error::custom(&e.to_string()))?;
@@ -163,107 +912,328 @@ impl MultimintWallet {
         Ok(0)
     }
 
-    pub async fn send(&self, amount: u64, options: MultimintSendOptions) -> Result<String> {
+    pub async fn send(
+        &self,
+        amount: u64,
+        options: MultimintSendOptions,
+    ) -> Result<MultimintSendResult> {
+        let prepared = self.prepare_sends(amount, &options).await?;
+        let mut bundle = TokenBundle::new();
+        let mut prepared = prepared.into_iter();
+
+        while let Some((wallet, prepared_send)) = prepared.next() {
+            match wallet.send(prepared_send, None).await {
+                Ok(token) => bundle.push_token(&token.to_string())?,
+                Err(e) => {
+                    Self::cancel_sends(prepared.collect()).await;
+                    return Err(Error::custom(&e.to_string()));
+                }
+            }
+        }
+
+        Ok(bundle)
+    }
+
+    /// The proofs and fees `send` would use at each mint, without sending
+    pub async fn preview_send(
+        &self,
+        amount: u64,
+        options: &MultimintSendOptions,
+    ) -> Result<Vec<SendPreview>> {
+        let prepared = self.prepare_sends(amount, options).await?;
+        let previews = prepared
+            .iter()
+            .map(|(wallet, prepared_send)| {
+                SendPreview::from_prepared(
+                    &wallet.mint_url.to_string(),
+                    &wallet.unit,
+                    prepared_send,
+                )
+            })
+            .collect();
+        Self::cancel_sends(prepared).await;
+
+        Ok(previews)
+    }
+
+    /// Reserve the proofs for a send of `amount`, one prepared send per mint
+    async fn prepare_sends(
+        &self,
+        amount: u64,
+        options: &MultimintSendOptions,
+    ) -> Result<Vec<(Wallet, PreparedSend)>> {
         if options.split_across_mints {
-            self.send_split_across_mints(amount, options).await
+            self.prepare_split_across_mints(amount, options).await
         } else {
-            self.send_from_single_mint(amount, options).await
+            self.prepare_from_single_mint(amount, options).await
         }
     }
 
-    async fn send_from_single_mint(
+    /// Send from `preferred_mint`, or from the first mint the selection
+    /// policy ranks that can cover `amount` on its own
+    async fn prepare_from_single_mint(
         &self,
         amount: u64,
-        options: MultimintSendOptions,
-    ) -> Result<String> {
-        let amount_obj = Amount::from(amount);
-
-        let mint_url = options.preferred_mint.unwrap();
-        let wallet = self.get_wallet_for_mint(&mint_url).await.unwrap();
+        options: &MultimintSendOptions,
+    ) -> Result<Vec<(Wallet, PreparedSend)>> {
+        let wallet = match &options.preferred_mint {
+            Some(mint_url) => {
+                self.ensure_mint_active(mint_url)?;
+                self.wallet_for(mint_url).await?
+            }
+            None => {
+                let unit = options.unit.clone().unwrap_or(CurrencyUnit::Msat);
+                let ranked = self.ranked_candidates(options, &unit).await?;
+                let candidate =
+                    mint_selection::first_covering(ranked, amount).ok_or_else(|| {
+                        Error::custom(&format!(
+                            "No eligible mint holds {} {} on its own",
+                            amount, unit
+                        ))
+                    })?;
+                self.wallet_for(&candidate.mint_url).await?
+            }
+        };
 
         let prepared_send = wallet
-            .prepare_send(amount_obj, SendOptions::default())
-            .await
-            .map_err(|e| Error::custom(&e.to_string()))?;
-        let token = wallet
-            .send(prepared_send, None)
+            .prepare_send(Amount::from(amount), cdk_send_options(options.fees))
             .await
             .map_err(|e| Error::custom(&e.to_string()))?;
 
-        Ok(token.to_string())
+        Ok(vec![(wallet, prepared_send)])
     }
 
-    async fn send_split_across_mints(
+    /// Send from as many mints as needed, in the order of the selection policy
+    async fn prepare_split_across_mints(
         &self,
         amount: u64,
-        options: MultimintSendOptions,
-    ) -> Result<String> {
-        let unit = options.unit.unwrap_or(CurrencyUnit::Msat);
-        let balances = self
-            .inner
-            .get_balances(&unit)
-            .await
-            .map_err(|e| Error::custom(&e.to_string()))?;
+        options: &MultimintSendOptions,
+    ) -> Result<Vec<(Wallet, PreparedSend)>> {
+        let unit = options.unit.clone().unwrap_or(CurrencyUnit::Msat);
+        let candidates = self.ranked_candidates(options, &unit).await?;
 
-        let total_balance: u64 = balances
-            .values()
-            .map(|a| {
-                let val: u64 = (*a).into();
-                val
-            })
-            .sum();
+        let total_balance: u64 = candidates.iter().map(|candidate| candidate.balance).sum();
         if total_balance < amount {
             return Err(Error::custom("Insufficient total balance"));
         }
 
         let mut remaining_amount = amount;
-        let mut tokens = Vec::new();
-
-        let mut mint_balances: Vec<_> = balances.iter().collect();
-        mint_balances.sort_by(|a, b| b.1.cmp(a.1));
+        let mut prepared = Vec::new();
 
-        for (mint_url, mint_balance) in mint_balances {
+        for candidate in candidates {
             if remaining_amount == 0 {
                 break;
             }
 
-            let send_amount = remaining_amount.min({
-                let balance_u64: u64 = (*mint_balance).into();
-                balance_u64
-            });
-            if send_amount > 0 {
-                let wallet_key = WalletKey::new(mint_url.clone(), unit.clone());
-                if let Some(wallet) = self.inner.get_wallet(&wallet_key).await {
-                    let amount_obj = Amount::from(send_amount);
-                    let prepared_send = wallet
-                        .prepare_send(amount_obj, SendOptions::default())
-                        .await
-                        .map_err(|e| Error::custom(&e.to_string()))?;
-                    let token = wallet
-                        .send(prepared_send, None)
-                        .await
-                        .map_err(|e| Error::custom(&e.to_string()))?;
-                    tokens.push(token.to_string());
-                    remaining_amount -= send_amount;
+            let wallet_key = WalletKey::new(
+                MintUrl::from_str(&candidate.mint_url)
+                    .map_err(|e| Error::custom(&e.to_string()))?,
+                unit.clone(),
+            );
+            let Some(wallet) = self.inner.get_wallet(&wallet_key).await else {
+                continue;
+            };
+
+            let send_amount = remaining_amount.min(candidate.balance);
+            match Self::prepare_up_to(&wallet, send_amount, options.fees).await {
+                Ok(Some(prepared_send)) => {
+                    remaining_amount -= u64::from(prepared_send.amount());
+                    prepared.push((wallet, prepared_send));
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    Self::cancel_sends(prepared).await;
+                    return Err(e);
                 }
             }
         }
 
         if remaining_amount > 0 {
+            Self::cancel_sends(prepared).await;
             return Err(Error::custom("Could not fulfill amount across all mints"));
         }
 
-        Ok(tokens.join("\n"))
+        Ok(prepared)
+    }
+
+    /// Prepare a send of `amount` from `wallet`, or of as much as is left once
+    /// fees are paid when the balance cannot cover `amount` plus fees
+    async fn prepare_up_to(
+        wallet: &Wallet,
+        amount: u64,
+        fees: SendFeeOptions,
+    ) -> Result<Option<PreparedSend>> {
+        match wallet
+            .prepare_send(Amount::from(amount), cdk_send_options(fees))
+            .await
+        {
+            Ok(prepared_send) => Ok(Some(prepared_send)),
+            Err(cdk::Error::InsufficientFunds) => {
+                // Spending every proof bounds both the swap fee and the fee
+                // to redeem the token
+                let fee_bound: u64 = wallet
+                    .get_proofs_fee(&wallet.get_unspent_proofs().await?)
+                    .await?
+                    .into();
+                let fee_bound = if fees.include_fees {
+                    fee_bound * 2
+                } else {
+                    fee_bound
+                };
+                let reduced = amount.saturating_sub(fee_bound);
+                if reduced == 0 {
+                    return Ok(None);
+                }
+
+                let prepared_send = wallet
+                    .prepare_send(Amount::from(reduced), cdk_send_options(fees))
+                    .await
+                    .map_err(|e| Error::custom(&e.to_string()))?;
+                Ok(Some(prepared_send))
+            }
+            Err(e) => Err(Error::custom(&e.to_string())),
+        }
+    }
+
+    /// Release the proofs reserved by prepared sends that will not be sent
+    async fn cancel_sends(prepared: Vec<(Wallet, PreparedSend)>) {
+        for (wallet, prepared_send) in prepared {
+            let _ = wallet.cancel_send(prepared_send).await;
+        }
     }
 
-    pub async fn receive(&self, token: &str) -> Result<String> {
-        println!("{:?}", token);
-        let received = self
+    /// Active mints holding `unit`, ranked by the policy of `options`
+    async fn ranked_candidates(
+        &self,
+        options: &MultimintSendOptions,
+        unit: &CurrencyUnit,
+    ) -> Result<Vec<MintCandidate>> {
+        let inactive = self.registry.inactive_mints()?;
+        let mut candidates = Vec::new();
+
+        for wallet in self.inner.get_wallets().await {
+            let mint_url = wallet.mint_url.to_string();
+            if wallet.unit != *unit || inactive.contains(&mint_url) {
+                continue;
+            }
+
+            let balance: u64 = wallet.total_balance().await?.into();
+            if balance == 0 {
+                continue;
+            }
+
+            let input_fee_ppk = wallet
+                .get_active_mint_keysets()
+                .await?
+                .iter()
+                .map(|keyset| keyset.input_fee_ppk)
+                .min()
+                .unwrap_or_default();
+
+            candidates.push(MintCandidate {
+                mint_url,
+                unit: unit.clone(),
+                balance,
+                input_fee_ppk,
+            });
+        }
+
+        Ok(match &options.policy {
+            Some(policy) => policy.rank(candidates),
+            None => options.strategy.rank(candidates),
+        })
+    }
+
+    /// Receive a single token, a [`TokenBundle`] envelope or tokens separated by whitespace
+    pub async fn receive(&self, tokens: &str) -> Result<BundleReceipt> {
+        let bundle = TokenBundle::from_str(tokens)?;
+        self.receive_bundle(&bundle).await
+    }
+
+    /// Receive every part of `bundle`.
+    ///
+    /// The proofs of each mint and unit are swapped together, so a mint's
+    /// share is received entirely or not at all. A failing mint does not stop
+    /// the others; its error is reported in the receipt.
+    pub async fn receive_bundle(&self, bundle: &TokenBundle) -> Result<BundleReceipt> {
+        let mut groups: Vec<(String, CurrencyUnit, Vec<&BundlePart>)> = Vec::new();
+        for part in &bundle.parts {
+            match groups
+                .iter_mut()
+                .find(|(mint_url, unit, _)| *mint_url == part.mint_url && *unit == part.unit)
+            {
+                Some((_, _, parts)) => parts.push(part),
+                None => groups.push((part.mint_url.clone(), part.unit.clone(), vec![part])),
+            }
+        }
+
+        let mut outcomes = Vec::new();
+        for (mint_url, unit, parts) in groups {
+            let amount = parts.iter().map(|part| part.amount).sum();
+            let (received, error) = match self.receive_parts(&mint_url, &unit, &parts).await {
+                Ok(received) => (received, None),
+                Err(e) => (0, Some(e.to_string())),
+            };
+            outcomes.push(MintReceiveOutcome {
+                mint_url,
+                unit,
+                amount,
+                received,
+                error,
+            });
+        }
+
+        Ok(BundleReceipt { outcomes })
+    }
+
+    async fn receive_parts(
+        &self,
+        mint_url: &str,
+        unit: &CurrencyUnit,
+        parts: &[&BundlePart],
+    ) -> Result<u64> {
+        let mint_url_parsed =
+            MintUrl::from_str(mint_url).map_err(|e| Error::custom(&e.to_string()))?;
+        let wallet = self
+            .inner
+            .get_wallet(&WalletKey::new(mint_url_parsed.clone(), unit.clone()))
+            .await
+            .ok_or_else(|| {
+                Error::custom(&format!(
+                    "Mint {} with unit {} is not in the multimint wallet",
+                    mint_url, unit
+                ))
+            })?;
+
+        let keysets = match self
             .inner
-            .receive(token, ReceiveOptions::default())
+            .localstore
+            .get_mint_keysets(mint_url_parsed)
+            .await
+            .map_err(|e| Error::custom(&e.to_string()))?
+        {
+            Some(keysets) => keysets,
+            None => wallet.get_mint_keysets().await?,
+        };
+
+        let mut proofs = Vec::new();
+        let mut memo = None;
+        for part in parts {
+            let token = Token::from_str(&part.token)
+                .map_err(|e| Error::custom(&format!("Invalid token: {}", e)))?;
+            proofs.extend(
+                token
+                    .proofs(&keysets)
+                    .map_err(|e| Error::custom(&format!("Invalid token: {}", e)))?,
+            );
+            memo = memo.or_else(|| token.memo().clone());
+        }
+
+        let received = wallet
+            .receive_proofs(proofs, ReceiveOptions::default(), memo)
             .await
             .map_err(|e| Error::custom(&e.to_string()))?;
-        Ok(received.to_string())
+        Ok(received.into())
     }
 
     pub async fn redeem_pendings(&self) -> Result<()> {
@@ -299,15 +1269,51 @@ impl MultimintWallet {
         Ok(table)
     }
 
-    pub async fn set_mint_active(&self, _mint_url: &str, _active: bool) -> Result<()> {
-        Ok(())
+    /// Include or exclude a mint from sends, balances and topups.
+    ///
+    /// The flag is kept in the wallet database and survives restarts.
+    pub async fn set_mint_active(&self, mint_url: &str, active: bool) -> Result<()> {
+        let mint_url_parsed =
+            MintUrl::from_str(mint_url).map_err(|e| Error::custom(&e.to_string()))?;
+        if self.get_wallet_for_mint(mint_url).await.is_none() {
+            return Err(Error::custom(&format!(
+                "Mint {} not found in multimint wallet",
+                mint_url
+            )));
+        }
+
+        self.registry
+            .set_active(&mint_url_parsed.to_string(), active)
     }
 
-    fn normalize_to_sats(&self, amount: u64, unit: &CurrencyUnit) -> u64 {
-        match unit {
-            CurrencyUnit::Msat => amount / 1000,
-            CurrencyUnit::Sat => amount,
-            _ => amount,
+    pub fn is_mint_active(&self, mint_url: &str) -> Result<bool> {
+        let mint_url_parsed =
+            MintUrl::from_str(mint_url).map_err(|e| Error::custom(&e.to_string()))?;
+        self.registry.is_active(&mint_url_parsed.to_string())
+    }
+
+    /// Fail with an error naming the mint when it has been deactivated
+    pub fn ensure_mint_active(&self, mint_url: &str) -> Result<()> {
+        if self.is_mint_active(mint_url)? {
+            Ok(())
+        } else {
+            Err(Error::custom(&format!("Mint {} is inactive", mint_url)))
+        }
+    }
+
+    /// Archives of mints removed with [`Self::remove_mint`]
+    pub fn archived_mint(&self, mint_url: &str) -> Result<Vec<ArchivedMint>> {
+        let mint_url_parsed =
+            MintUrl::from_str(mint_url).map_err(|e| Error::custom(&e.to_string()))?;
+        self.registry.archives(&mint_url_parsed.to_string())
+    }
+
+    fn convert(&self, amount: u64, from: &CurrencyUnit, to: &CurrencyUnit) -> Result<u64> {
+        match &self.price_oracle {
+            Some(oracle) => price::convert(oracle.as_ref(), amount, from, to),
+            None => price::convert(&StaticPriceOracle::new(), amount, from, to).map_err(|_| {
+                Error::custom(&format!("No price oracle to convert {} to {}", from, to))
+            }),
         }
     }
 
@@ -370,29 +1376,224 @@ impl MultimintWallet {
         None
     }
 
+    /// Get the quotes for moving `amount` (in the target mint's unit) from
+    /// `from_mint` to `to_mint` over Lightning, without spending anything.
+    ///
+    /// The target mint issues an invoice and the source mint quotes paying
+    /// it, which together give the fees up front. The target's invoice is
+    /// only an estimate and is not kept in the quote store.
+    pub async fn estimate_transfer(
+        &self,
+        from_mint: &str,
+        to_mint: &str,
+        amount: u64,
+    ) -> Result<TransferQuote> {
+        let mut lightning = self.lightning.lock().await;
+        let quote = self
+            .quote_transfer(&mut lightning, from_mint, to_mint, amount)
+            .await?;
+        lightning.remove_invoice(&quote.mint_quote_id)?;
+        Ok(quote)
+    }
+
+    /// Move `amount` (in the target mint's unit) from `from_mint` to
+    /// `to_mint` by melting at the source to pay an invoice of the target.
+    ///
+    /// Fails before spending anything when the transfer could cost more
+    /// than `max_fee` (in the source mint's unit). The quotes are kept in
+    /// the wallet's quote store, so a transfer interrupted after the melt is
+    /// finished by [`Self::resume_transfers`].
     pub async fn transfer_between_mints(
         &self,
         from_mint: &str,
-        _to_mint: &str,
+        to_mint: &str,
         amount: u64,
-    ) -> Result<String> {
-        let token = self
-            .send_from_single_mint(
-                amount,
-                MultimintSendOptions {
-                    preferred_mint: Some(from_mint.to_string()),
-                    ..Default::default()
+        max_fee: Option<u64>,
+    ) -> Result<TransferResult> {
+        let mut lightning = self.lightning.lock().await;
+        let quote = self
+            .quote_transfer(&mut lightning, from_mint, to_mint, amount)
+            .await?;
+
+        if let Some(max_fee) = max_fee {
+            if quote.max_fee() > max_fee {
+                lightning.remove_invoice(&quote.mint_quote_id)?;
+                return Err(Error::custom(&format!(
+                    "Transfer could cost up to {} {} in fees, more than the limit of {}",
+                    quote.max_fee(),
+                    quote.from_unit,
+                    max_fee
+                )));
+            }
+        }
+
+        let source = self.wallet_for(from_mint).await?;
+        let melt = lightning
+            .execute_melt(
+                &source,
+                MeltRequest {
+                    quote_id: quote.melt_quote_id.clone(),
+                    payment_request: quote.payment_request.clone(),
+                    amount: quote.melt_amount,
+                    unit: quote.from_unit.clone(),
+                    proofs: Vec::new(),
+                    method: PaymentMethod::Bolt11,
                 },
             )
-            .await?;
+            .await;
+        let melt = match melt {
+            Ok(melt) if melt.success => melt,
+            Ok(melt) => {
+                // Still pending at the source: keep both quotes for resume_transfers
+                return Err(Error::custom(&format!(
+                    "Transfer payment did not complete: {}",
+                    melt.message
+                )));
+            }
+            Err(e) => {
+                if lightning
+                    .list_pending_melts()
+                    .iter()
+                    .all(|pending| pending.quote_id != quote.melt_quote_id)
+                {
+                    lightning.remove_invoice(&quote.mint_quote_id)?;
+                }
+                return Err(e);
+            }
+        };
 
-        self.receive(&token).await
+        let topup = lightning
+            .complete_topup_for_multimint(&quote.mint_quote_id, self)
+            .await
+            .map_err(|e| {
+                Error::custom(&format!(
+                    "Paid invoice {} at {} but minting failed, resume the transfer later: {}",
+                    quote.mint_quote_id, to_mint, e
+                ))
+            })?;
+        lightning.remove_invoice(&quote.mint_quote_id)?;
+
+        Ok(TransferResult {
+            from_mint: quote.from_mint,
+            to_mint: quote.to_mint,
+            amount_sent: quote.melt_amount,
+            amount_received: topup.amount_received,
+            fee_paid: melt.fee_paid,
+            mint_quote_id: quote.mint_quote_id,
+            melt_quote_id: quote.melt_quote_id,
+        })
+    }
+
+    /// Finish transfers that were interrupted: settle melts left pending
+    /// and mint invoices that were paid
+    pub async fn resume_transfers(&self) -> Result<ResumeReport> {
+        let mut lightning = self.lightning.lock().await;
+        lightning.resume_pending_for_multimint(self).await
+    }
+
+    async fn quote_transfer(
+        &self,
+        lightning: &mut LightningManager,
+        from_mint: &str,
+        to_mint: &str,
+        amount: u64,
+    ) -> Result<TransferQuote> {
+        let source = self.wallet_for(from_mint).await?;
+        let target = self.wallet_for(to_mint).await?;
+        if source.mint_url == target.mint_url {
+            return Err(Error::custom("Cannot transfer to the same mint"));
+        }
+        self.ensure_mint_active(from_mint)?;
+        self.ensure_mint_active(to_mint)?;
+
+        let invoice = lightning
+            .create_invoice_for_multimint(
+                self,
+                TopupRequest {
+                    amount,
+                    unit: target.unit.clone(),
+                    mint_url: Some(target.mint_url.to_string()),
+                    description: None,
+                    method: PaymentMethod::Bolt11,
+                },
+            )
+            .await?
+            .invoice;
+
+        let preview = match lightning
+            .prepare_melt(
+                &source.mint_url.to_string(),
+                &invoice.payment_request,
+                source.unit.clone(),
+                None,
+                PaymentMethod::Bolt11,
+            )
+            .await
+        {
+            Ok(preview) => preview,
+            Err(e) => {
+                lightning.remove_invoice(&invoice.quote_id)?;
+                return Err(e);
+            }
+        };
+
+        let input_fee =
+            match Self::estimate_input_fee(&source, preview.amount + preview.fee_reserve).await {
+                Ok(input_fee) => input_fee,
+                Err(e) => {
+                    lightning.remove_invoice(&invoice.quote_id)?;
+                    return Err(e);
+                }
+            };
+
+        Ok(TransferQuote {
+            from_mint: source.mint_url.to_string(),
+            to_mint: target.mint_url.to_string(),
+            amount,
+            to_unit: target.unit,
+            mint_quote_id: invoice.quote_id,
+            payment_request: invoice.payment_request,
+            melt_quote_id: preview.quote_id,
+            melt_amount: preview.amount,
+            fee_reserve: preview.fee_reserve,
+            input_fee,
+            from_unit: source.unit,
+        })
+    }
+
+    /// Input fee of the proofs `wallet` would select to spend `amount`
+    async fn estimate_input_fee(wallet: &Wallet, amount: u64) -> Result<u64> {
+        let active_keyset_ids: Vec<Id> = wallet
+            .get_active_mint_keysets()
+            .await?
+            .into_iter()
+            .map(|keyset| keyset.id)
+            .collect();
+        let keyset_fees = wallet.get_keyset_fees().await?;
+        let proofs = Wallet::select_proofs(
+            Amount::from(amount),
+            wallet.get_unspent_proofs().await?,
+            &active_keyset_ids,
+            &keyset_fees,
+            true,
+        )?;
+        Ok(wallet.get_proofs_fee(&proofs).await?.into())
+    }
+
+    async fn wallet_for(&self, mint_url: &str) -> Result<Wallet> {
+        self.get_wallet_for_mint(mint_url).await.ok_or_else(|| {
+            Error::custom(&format!("Mint {} not found in multimint wallet", mint_url))
+        })
     }
 
     pub fn cdk_wallet(&self) -> &CdkMultiMintWallet {
         &self.inner
     }
 
+    pub fn transport(&self) -> &MintTransport {
+        &self.transport
+    }
+
     async fn check_and_redeem_pending(&self) -> Result<()> {
         let wallets = self.inner.get_wallets().await;
 
@@ -410,3 +1611,182 @@ impl MultimintWallet {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seed() -> String {
+        bip39::Mnemonic::generate(12).unwrap().to_string()
+    }
+
+    #[tokio::test]
+    async fn in_memory_wallet_starts_empty_on_the_bip39_seed() {
+        let wallet = MultimintWalletBuilder::new(&seed())
+            .in_memory()
+            .build()
+            .await
+            .unwrap();
+
+        assert!(!wallet.needs_seed_migration());
+        assert!(wallet.list_mints().await.is_empty());
+
+        let balance = wallet.get_total_balance().await.unwrap();
+        assert_eq!(balance.total_balance, 0);
+        assert!(balance.balances.is_empty());
+        assert!(balance.unpriced_units.is_empty());
+    }
+
+    #[tokio::test]
+    async fn in_memory_wallets_do_not_share_state() {
+        let seed = seed();
+        let first = MultimintWalletBuilder::new(&seed)
+            .in_memory()
+            .build()
+            .await
+            .unwrap();
+        let second = MultimintWalletBuilder::new(&seed)
+            .in_memory()
+            .build()
+            .await
+            .unwrap();
+
+        first
+            .registry
+            .set_active(
+                &MintUrl::from_str("https://mint.example")
+                    .unwrap()
+                    .to_string(),
+                false,
+            )
+            .unwrap();
+        assert!(!first.is_mint_active("https://mint.example").unwrap());
+        assert!(second.is_mint_active("https://mint.example").unwrap());
+    }
+
+    /// Add a wallet for `unit` at `mint_url`, holding one proof of `amount` if non-zero
+    async fn add_unit(
+        wallet: &MultimintWallet,
+        mint_url: &MintUrl,
+        unit: CurrencyUnit,
+        amount: u64,
+    ) {
+        use cdk::nuts::{Proof, SecretKey};
+        use cdk::secret::Secret;
+        use cdk::types::ProofInfo;
+
+        let localstore = &wallet.inner.localstore;
+        localstore.add_mint(mint_url.clone(), None).await.unwrap();
+        let unit_wallet = wallet
+            .build_wallet(mint_url.clone(), unit.clone(), &wallet.seed)
+            .unwrap();
+        wallet.inner.add_wallet(unit_wallet).await;
+        if amount == 0 {
+            return;
+        }
+
+        let proof = Proof {
+            amount: Amount::from(amount),
+            keyset_id: Id::from_str("009a1f293253e41e").unwrap(),
+            secret: Secret::generate(),
+            c: SecretKey::generate().public_key(),
+            witness: None,
+            dleq: None,
+        };
+        let info = ProofInfo::new(proof, mint_url.clone(), State::Unspent, unit).unwrap();
+        localstore
+            .update_proofs(vec![info], Vec::new())
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn remove_mint_refuses_a_funded_unit_next_to_an_empty_one() {
+        let wallet = MultimintWalletBuilder::new(&seed())
+            .in_memory()
+            .build()
+            .await
+            .unwrap();
+        let mint_url = MintUrl::from_str("https://mint.example").unwrap();
+        add_unit(&wallet, &mint_url, CurrencyUnit::Msat, 0).await;
+        add_unit(&wallet, &mint_url, CurrencyUnit::Sat, 21).await;
+
+        assert!(wallet.remove_mint(&mint_url.to_string()).await.is_err());
+        let proofs = wallet
+            .inner
+            .localstore
+            .get_proofs(Some(mint_url.clone()), None, None, None)
+            .await
+            .unwrap();
+        assert_eq!(proofs.len(), 1);
+        assert!(wallet
+            .registry
+            .archives(&mint_url.to_string())
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn sat_balances_are_totalled_without_a_price_for_the_display_unit() {
+        let wallet = MultimintWalletBuilder::new(&seed())
+            .in_memory()
+            .build()
+            .await
+            .unwrap();
+        let mint_url = MintUrl::from_str("https://mint.example").unwrap();
+        add_unit(&wallet, &mint_url, CurrencyUnit::Sat, 21).await;
+        add_unit(&wallet, &mint_url, CurrencyUnit::Msat, 2_000).await;
+
+        let balance = wallet
+            .get_total_balance_in(&CurrencyUnit::Usd)
+            .await
+            .unwrap();
+        assert_eq!(balance.total_balance, 23);
+        assert_eq!(balance.display_total, 0);
+        assert_eq!(balance.unpriced_units, vec![CurrencyUnit::Usd]);
+        assert_eq!(balance.balances.len(), 2);
+        assert!(balance
+            .balances
+            .iter()
+            .all(|balance| balance.display_balance.is_none()));
+    }
+
+    #[tokio::test]
+    async fn fiat_balances_are_left_out_of_the_totals_without_a_price() {
+        let wallet = MultimintWalletBuilder::new(&seed())
+            .in_memory()
+            .build()
+            .await
+            .unwrap();
+        let mint_url = MintUrl::from_str("https://mint.example").unwrap();
+        add_unit(&wallet, &mint_url, CurrencyUnit::Sat, 100_000).await;
+        add_unit(&wallet, &mint_url, CurrencyUnit::Usd, 650).await;
+
+        let balance = wallet.get_total_balance().await.unwrap();
+        assert_eq!(balance.total_balance, 100_000);
+        assert_eq!(balance.display_total, 100_000);
+        assert_eq!(balance.unpriced_units, vec![CurrencyUnit::Usd]);
+        let usd = balance
+            .balances
+            .iter()
+            .find(|balance| balance.unit == CurrencyUnit::Usd)
+            .unwrap();
+        assert_eq!(usd.balance, 650);
+        assert_eq!(usd.display_balance, None);
+
+        // 65,000 usd per bitcoin, in cents
+        let oracle = StaticPriceOracle::new().with_price(CurrencyUnit::Usd, 6_500_000.0);
+        let wallet = wallet.with_price_oracle(Arc::new(oracle));
+
+        let balance = wallet.get_total_balance().await.unwrap();
+        assert_eq!(balance.total_balance, 110_000);
+        assert!(balance.unpriced_units.is_empty());
+
+        let balance = wallet
+            .get_total_balance_in(&CurrencyUnit::Usd)
+            .await
+            .unwrap();
+        assert_eq!(balance.display_total, 7_150);
+        assert!(balance.unpriced_units.is_empty());
+    }
+}
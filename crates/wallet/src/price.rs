@@ -0,0 +1,196 @@
+use crate::error::{Error, Result};
+use cdk::nuts::CurrencyUnit;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+const MSAT_PER_BTC: f64 = 100_000_000_000.0;
+
+/// Source of bitcoin prices for converting between units
+pub trait PriceOracle: fmt::Debug + Send + Sync {
+    /// Price of one bitcoin in `unit`, counted the way mints count that unit
+    /// (cents for `usd` and `eur`)
+    fn btc_price(&self, unit: &CurrencyUnit) -> Result<f64>;
+}
+
+/// Convert `amount` between units, using `oracle` for anything but sat and msat.
+///
+/// The result is rounded to the nearest whole unit.
+pub fn convert(
+    oracle: &dyn PriceOracle,
+    amount: u64,
+    from: &CurrencyUnit,
+    to: &CurrencyUnit,
+) -> Result<u64> {
+    if from == to {
+        return Ok(amount);
+    }
+
+    let msat = match from {
+        CurrencyUnit::Msat => amount as f64,
+        CurrencyUnit::Sat => amount as f64 * 1000.0,
+        unit => amount as f64 * MSAT_PER_BTC / checked_price(oracle, unit)?,
+    };
+
+    let converted = match to {
+        CurrencyUnit::Msat => msat,
+        CurrencyUnit::Sat => msat / 1000.0,
+        unit => msat * checked_price(oracle, unit)? / MSAT_PER_BTC,
+    };
+
+    Ok(converted.round() as u64)
+}
+
+fn checked_price(oracle: &dyn PriceOracle, unit: &CurrencyUnit) -> Result<f64> {
+    let price = oracle.btc_price(unit)?;
+    if price.is_finite() && price > 0.0 {
+        Ok(price)
+    } else {
+        Err(Error::custom(&format!("Invalid {} price: {}", unit, price)))
+    }
+}
+
+/// Fixed prices, e.g. for tests or offline use
+#[derive(Debug, Clone, Default)]
+pub struct StaticPriceOracle {
+    prices: HashMap<String, f64>,
+}
+
+impl StaticPriceOracle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_price(mut self, unit: CurrencyUnit, btc_price: f64) -> Self {
+        self.prices.insert(unit.to_string(), btc_price);
+        self
+    }
+}
+
+impl PriceOracle for StaticPriceOracle {
+    fn btc_price(&self, unit: &CurrencyUnit) -> Result<f64> {
+        self.prices
+            .get(&unit.to_string())
+            .copied()
+            .ok_or_else(|| Error::custom(&format!("No price for {}", unit)))
+    }
+}
+
+/// Prices read from a JSON file mapping units to bitcoin prices, e.g.
+/// `{"usd": 6500000, "eur": 6000000}`.
+///
+/// The file is read on every lookup, so it can be updated while in use.
+#[derive(Debug, Clone)]
+pub struct FilePriceOracle {
+    path: PathBuf,
+}
+
+impl FilePriceOracle {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl PriceOracle for FilePriceOracle {
+    fn btc_price(&self, unit: &CurrencyUnit) -> Result<f64> {
+        let content = std::fs::read_to_string(&self.path)?;
+        let prices: HashMap<String, f64> = serde_json::from_str(&content)?;
+        prices.get(&unit.to_string()).copied().ok_or_else(|| {
+            Error::custom(&format!("No price for {} in {}", unit, self.path.display()))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 65,000 usd per bitcoin, in cents
+    const USD_PRICE: f64 = 6_500_000.0;
+
+    fn oracle() -> StaticPriceOracle {
+        StaticPriceOracle::new().with_price(CurrencyUnit::Usd, USD_PRICE)
+    }
+
+    #[test]
+    fn sat_and_msat_convert_without_a_price() {
+        let oracle = StaticPriceOracle::new();
+        let sat = CurrencyUnit::Sat;
+        let msat = CurrencyUnit::Msat;
+
+        assert_eq!(convert(&oracle, 21, &sat, &msat).unwrap(), 21_000);
+        assert_eq!(convert(&oracle, 1_499, &msat, &sat).unwrap(), 1);
+        assert_eq!(convert(&oracle, 1_500, &msat, &sat).unwrap(), 2);
+        assert_eq!(convert(&oracle, 7, &sat, &sat).unwrap(), 7);
+        assert_eq!(
+            convert(&oracle, 7, &CurrencyUnit::Usd, &CurrencyUnit::Usd).unwrap(),
+            7
+        );
+    }
+
+    #[test]
+    fn fiat_converts_through_the_price_and_rounds_to_the_nearest_unit() {
+        let oracle = oracle();
+        let usd = CurrencyUnit::Usd;
+
+        // 100,000 sat is 0.001 btc, i.e. 6,500 cents
+        assert_eq!(
+            convert(&oracle, 100_000, &CurrencyUnit::Sat, &usd).unwrap(),
+            6_500
+        );
+        assert_eq!(
+            convert(&oracle, 6_500, &usd, &CurrencyUnit::Sat).unwrap(),
+            100_000
+        );
+        // One cent is 15.38 sat and 15,384.6 msat
+        assert_eq!(convert(&oracle, 1, &usd, &CurrencyUnit::Sat).unwrap(), 15);
+        assert_eq!(
+            convert(&oracle, 1, &usd, &CurrencyUnit::Msat).unwrap(),
+            15_385
+        );
+        // 7 sat is 0.455 cents
+        assert_eq!(convert(&oracle, 7, &CurrencyUnit::Sat, &usd).unwrap(), 0);
+        assert_eq!(convert(&oracle, 8, &CurrencyUnit::Sat, &usd).unwrap(), 1);
+    }
+
+    #[test]
+    fn fiat_conversions_fail_without_a_usable_price() {
+        let usd = CurrencyUnit::Usd;
+        let eur = CurrencyUnit::Eur;
+
+        assert!(convert(&oracle(), 100, &CurrencyUnit::Sat, &eur).is_err());
+        assert!(convert(&oracle(), 100, &eur, &usd).is_err());
+        for price in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            let oracle = StaticPriceOracle::new().with_price(usd.clone(), price);
+            assert!(convert(&oracle, 100, &CurrencyUnit::Sat, &usd).is_err());
+            assert!(convert(&oracle, 100, &usd, &CurrencyUnit::Sat).is_err());
+        }
+    }
+
+    #[test]
+    fn file_prices_are_read_on_every_lookup() {
+        let path = std::env::temp_dir().join(format!(
+            "prices-{}.json",
+            crate::crypto::generate_random_secret()
+        ));
+        let oracle = FilePriceOracle::new(&path);
+        assert!(oracle.btc_price(&CurrencyUnit::Usd).is_err());
+
+        std::fs::write(&path, r#"{"usd": 6500000}"#).unwrap();
+        assert_eq!(oracle.btc_price(&CurrencyUnit::Usd).unwrap(), USD_PRICE);
+        assert!(oracle.btc_price(&CurrencyUnit::Eur).is_err());
+
+        std::fs::write(&path, r#"{"usd": 7000000, "eur": 6000000}"#).unwrap();
+        assert_eq!(oracle.btc_price(&CurrencyUnit::Usd).unwrap(), 7_000_000.0);
+        assert_eq!(oracle.btc_price(&CurrencyUnit::Eur).unwrap(), 6_000_000.0);
+
+        std::fs::write(&path, "not json").unwrap();
+        assert!(oracle.btc_price(&CurrencyUnit::Usd).is_err());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
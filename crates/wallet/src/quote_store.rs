@@ -0,0 +1,61 @@
+use crate::error::{Error, Result};
+use crate::lightning::{LightningInvoice, PendingMelt};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StoredQuotes {
+    #[serde(default)]
+    pub invoices: Vec<LightningInvoice>,
+    #[serde(default)]
+    pub melts: Vec<PendingMelt>,
+}
+
+/// JSON file holding the pending invoices and melts of a [`LightningManager`]
+///
+/// [`LightningManager`]: crate::lightning::LightningManager
+#[derive(Debug, Clone)]
+pub struct QuoteStore {
+    path: PathBuf,
+}
+
+impl QuoteStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// Store next to a wallet database created under the home directory,
+    /// e.g. `wallet.sqlite` becomes `wallet.sqlite.quotes.json`
+    pub fn for_wallet_db(db_name: &str) -> Result<Self> {
+        let home_dir =
+            home::home_dir().ok_or_else(|| Error::custom("Could not determine home directory"))?;
+        Ok(Self::new(home_dir.join(format!("{}.quotes.json", db_name))))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Load the stored quotes, or nothing if the file does not exist yet
+    pub fn load(&self) -> Result<StoredQuotes> {
+        if !self.path.exists() {
+            return Ok(StoredQuotes::default());
+        }
+
+        let content = std::fs::read_to_string(&self.path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    /// Replace the stored quotes, writing to a temporary file first so a
+    /// crash never leaves a truncated file behind
+    pub fn save(&self, quotes: &StoredQuotes) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let tmp_path = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp_path, serde_json::to_string_pretty(quotes)?)?;
+        std::fs::rename(&tmp_path, &self.path)?;
+        Ok(())
+    }
+}
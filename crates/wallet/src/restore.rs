@@ -0,0 +1,270 @@
+use crate::{
+    dleq::TokenVerification,
+    error::{Error, Result},
+    mint::MintTransport,
+    seed::derive_seed,
+    wallet::CashuWalletClient,
+};
+use bitcoin::bip32::Xpriv;
+use bitcoin::Network;
+use cdk::{
+    mint_url::MintUrl,
+    nuts::{CurrencyUnit, PreMintSecrets, Proofs, State},
+    types::ProofInfo,
+};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// How far [`restore`] scans the deterministic counters of each keyset (NUT-13)
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct RestoreOptions {
+    /// Counters asked about per restore request
+    pub batch_size: u32,
+    /// Batches in a row without any signature before a keyset is done
+    pub gap_limit: u32,
+}
+
+impl Default for RestoreOptions {
+    fn default() -> Self {
+        Self {
+            batch_size: 100,
+            gap_limit: 3,
+        }
+    }
+}
+
+/// What was recovered from one keyset
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeysetRestore {
+    pub keyset_id: String,
+    pub unit: CurrencyUnit,
+    /// Signatures the mint had for our outputs, spent or not
+    pub signatures_found: usize,
+    pub unspent_proofs: usize,
+    pub pending_proofs: usize,
+    /// Value of the unspent and pending proofs
+    pub amount: u64,
+    /// Highest counter the mint had a signature for
+    pub last_counter: Option<u32>,
+    /// DLEQ check of the restored signatures
+    #[serde(default)]
+    pub verification: TokenVerification,
+}
+
+/// What was recovered from one mint
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MintRestore {
+    pub mint_url: String,
+    pub keysets: Vec<KeysetRestore>,
+}
+
+impl MintRestore {
+    /// Value recovered in `unit`
+    pub fn amount(&self, unit: &CurrencyUnit) -> u64 {
+        self.keysets
+            .iter()
+            .filter(|keyset| keyset.unit == *unit)
+            .map(|keyset| keyset.amount)
+            .sum()
+    }
+}
+
+/// Rebuild the wallet database `db_name` from `mnemonic` and its BIP39
+/// `passphrase`, empty if it has none (NUT-13).
+///
+/// For every keyset of every mint, the outputs derived from the seed are
+/// sent to the mint's restore endpoint in batches until `options.gap_limit`
+/// batches in a row come back empty. Restored signatures are unblinded,
+/// spent proofs are dropped and the rest are stored with their state, and
+/// the keyset counters are moved past the highest counter found so new
+/// outputs never reuse a restored secret.
+pub async fn restore(
+    mnemonic: &str,
+    passphrase: &str,
+    mints: &[String],
+    db_name: &str,
+    options: &RestoreOptions,
+) -> Result<Vec<MintRestore>> {
+    restore_with_transport(
+        mnemonic,
+        passphrase,
+        mints,
+        db_name,
+        options,
+        MintTransport::shared(),
+    )
+    .await
+}
+
+pub async fn restore_with_transport(
+    mnemonic: &str,
+    passphrase: &str,
+    mints: &[String],
+    db_name: &str,
+    options: &RestoreOptions,
+    transport: MintTransport,
+) -> Result<Vec<MintRestore>> {
+    if options.batch_size == 0 || options.gap_limit == 0 {
+        return Err(Error::custom(
+            "Restore batch size and gap limit must be positive",
+        ));
+    }
+
+    let seed = derive_seed(mnemonic, passphrase)?;
+    let xpriv = Xpriv::new_master(Network::Bitcoin, &seed)
+        .map_err(|e| Error::custom(&format!("Failed to derive master key: {}", e)))?;
+
+    let mut restores = Vec::new();
+    for mint_url in mints {
+        restores.push(
+            restore_mint(
+                mnemonic, passphrase, mint_url, db_name, xpriv, options, &transport,
+            )
+            .await?,
+        );
+    }
+
+    Ok(restores)
+}
+
+async fn restore_mint(
+    mnemonic: &str,
+    passphrase: &str,
+    mint_url: &str,
+    db_name: &str,
+    xpriv: Xpriv,
+    options: &RestoreOptions,
+    transport: &MintTransport,
+) -> Result<MintRestore> {
+    let client = CashuWalletClient::builder(mint_url, mnemonic)
+        .passphrase(passphrase)
+        .db_name(db_name)
+        .unit(CurrencyUnit::Sat)
+        .transport(transport.clone())
+        .build()
+        .await?;
+    let wallet = &client.wallet;
+    let mint_client = client.mint_client()?;
+    let mint_url = MintUrl::from_str(mint_url)
+        .map_err(|e| Error::custom(&format!("Invalid mint URL: {}", e)))?;
+
+    // Stores the mint and its keysets in the database the proofs go into
+    wallet.get_mint_info().await?;
+    let keysets = wallet.get_mint_keysets().await?;
+
+    let mut restored_keysets = Vec::new();
+    for keyset in keysets {
+        let keys = wallet.get_keyset_keys(keyset.id).await?;
+        let mut restored = KeysetRestore {
+            keyset_id: keyset.id.to_string(),
+            unit: keyset.unit.clone(),
+            signatures_found: 0,
+            unspent_proofs: 0,
+            pending_proofs: 0,
+            amount: 0,
+            last_counter: None,
+            verification: TokenVerification::default(),
+        };
+
+        let mut start = 0u32;
+        let mut empty_batches = 0;
+        while empty_batches < options.gap_limit {
+            let end = start + options.batch_size - 1;
+            let premint = PreMintSecrets::restore_batch(keyset.id, xpriv, start, end)
+                .map_err(|e| Error::custom(&format!("Failed to derive outputs: {}", e)))?;
+            let found = mint_client.restore_proofs(&premint, &keys).await?;
+            restored.verification.merge(&found.verification);
+            let found = found.proofs;
+            let batch_start = start;
+            start = end + 1;
+
+            if found.is_empty() {
+                empty_batches += 1;
+                continue;
+            }
+            empty_batches = 0;
+
+            let last_in_batch = found
+                .iter()
+                .map(|(position, _)| batch_start + *position as u32)
+                .max();
+            restored.last_counter = restored.last_counter.max(last_in_batch);
+
+            let proofs: Proofs = found.into_iter().map(|(_, proof)| proof).collect();
+            restored.signatures_found += proofs.len();
+            let states = mint_client.check_proof_states(&proofs).await?;
+
+            let mut infos = Vec::new();
+            for (proof, state) in proofs.into_iter().zip(states) {
+                let state = match state {
+                    State::Spent => continue,
+                    State::Pending => {
+                        restored.pending_proofs += 1;
+                        State::Pending
+                    }
+                    _ => {
+                        restored.unspent_proofs += 1;
+                        State::Unspent
+                    }
+                };
+                restored.amount += u64::from(proof.amount);
+                infos.push(ProofInfo::new(
+                    proof,
+                    mint_url.clone(),
+                    state,
+                    keyset.unit.clone(),
+                )?);
+            }
+
+            wallet
+                .localstore
+                .update_proofs(infos, Vec::new())
+                .await
+                .map_err(|e| Error::custom(&e.to_string()))?;
+        }
+
+        if let Some(last_counter) = restored.last_counter {
+            let counter = match wallet
+                .localstore
+                .get_keyset_counter(&keyset.id)
+                .await
+                .map_err(|e| Error::custom(&e.to_string()))?
+            {
+                Some(counter) => counter,
+                None => {
+                    // Incrementing a counter without a keyset row changes nothing
+                    wallet
+                        .localstore
+                        .add_mint_keysets(mint_url.clone(), vec![keyset.clone()])
+                        .await
+                        .map_err(|e| Error::custom(&e.to_string()))?;
+                    wallet
+                        .localstore
+                        .get_keyset_counter(&keyset.id)
+                        .await
+                        .map_err(|e| Error::custom(&e.to_string()))?
+                        .ok_or_else(|| {
+                            Error::custom(&format!(
+                                "Keyset {} is missing from the wallet database",
+                                keyset.id
+                            ))
+                        })?
+                }
+            };
+            if last_counter > counter {
+                wallet
+                    .localstore
+                    .increment_keyset_counter(&keyset.id, last_counter - counter)
+                    .await
+                    .map_err(|e| Error::custom(&e.to_string()))?;
+            }
+        }
+
+        restored_keysets.push(restored);
+    }
+
+    Ok(MintRestore {
+        mint_url: mint_url.to_string(),
+        keysets: restored_keysets,
+    })
+}
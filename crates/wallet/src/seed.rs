@@ -0,0 +1,74 @@
+use crate::error::{Error, Result};
+use bip39::Mnemonic;
+use cdk::nuts::CurrencyUnit;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// Which seed the deterministic secrets of a wallet database come from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SeedDerivation {
+    /// The 64-byte seed of [`derive_seed`]
+    Bip39,
+    /// The first 32 bytes of the passphrase-less BIP39 seed, used by
+    /// multimint databases created before passphrase support
+    Legacy32,
+}
+
+impl SeedDerivation {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Bip39 => "bip39",
+            Self::Legacy32 => "legacy-32",
+        }
+    }
+}
+
+impl FromStr for SeedDerivation {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "bip39" => Ok(Self::Bip39),
+            "legacy-32" => Ok(Self::Legacy32),
+            other => Err(Error::custom(&format!(
+                "Unknown seed derivation: {}",
+                other
+            ))),
+        }
+    }
+}
+
+/// The wallet seed of `mnemonic` protected by `passphrase`.
+///
+/// Every wallet type uses this one derivation: the full 64-byte BIP39 seed
+/// (PBKDF2-HMAC-SHA512 over the NFKD-normalized mnemonic, salted with
+/// `"mnemonic" + passphrase`) is the BIP32 master seed, and the NUT-13
+/// secret and blinding factor for a keyset's counter are derived at
+/// `m/129372'/0'/{keyset}'/{counter}'/{0,1}`. An empty passphrase gives
+/// the seed of wallets created without one.
+pub fn derive_seed(mnemonic: &str, passphrase: &str) -> Result<[u8; 64]> {
+    let mnemonic =
+        Mnemonic::from_str(mnemonic).map_err(|_| Error::custom("Invalid mnemonic seed"))?;
+    Ok(mnemonic.to_seed_normalized(passphrase))
+}
+
+/// The seed of [`SeedDerivation::Legacy32`] databases, only needed until
+/// they are migrated
+pub fn legacy_seed(mnemonic: &str) -> Result<[u8; 32]> {
+    let seed = derive_seed(mnemonic, "")?;
+    seed[0..32]
+        .try_into()
+        .map_err(|_| Error::custom("Invalid seed length"))
+}
+
+/// Proofs of one mint moved from legacy-derived secrets onto the wallet seed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SeedMigration {
+    pub mint_url: String,
+    pub unit: CurrencyUnit,
+    pub proofs_moved: usize,
+    /// Value of the new proofs, after the fee
+    pub amount_moved: u64,
+    /// Input fee paid for the swap
+    pub fee: u64,
+}
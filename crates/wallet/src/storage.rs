@@ -0,0 +1,61 @@
+use crate::error::{Error, Result};
+use cdk::cdk_database::{self, WalletDatabase};
+use cdk_sqlite::WalletSqliteDatabase;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// A cdk wallet database shared between wallets
+pub type WalletStore = Arc<dyn WalletDatabase<Err = cdk_database::Error> + Send + Sync>;
+
+/// Where a wallet keeps its proofs, keysets and counters
+#[derive(Debug, Clone)]
+pub enum WalletStorage {
+    /// SQLite database at this path under the home directory
+    Home(String),
+    /// SQLite database at this path
+    Path(PathBuf),
+    /// Empty SQLite database in memory, gone once the wallet is dropped
+    Memory,
+    /// Any cdk wallet database
+    Custom(WalletStore),
+}
+
+impl WalletStorage {
+    /// Path of the SQLite database file, if the storage has one
+    pub fn path(&self) -> Result<Option<PathBuf>> {
+        match self {
+            Self::Home(db_name) => {
+                let home_dir = home::home_dir()
+                    .ok_or_else(|| Error::custom("Could not determine home directory"))?;
+                Ok(Some(home_dir.join(db_name)))
+            }
+            Self::Path(path) => Ok(Some(path.clone())),
+            Self::Memory | Self::Custom(_) => Ok(None),
+        }
+    }
+
+    /// Open the database, creating the SQLite file and its directory if needed
+    pub async fn open(&self) -> Result<WalletStore> {
+        if let Self::Custom(store) = self {
+            return Ok(store.clone());
+        }
+
+        let localstore = match self.path()? {
+            Some(db_path) => {
+                if let Some(parent) = db_path.parent() {
+                    std::fs::create_dir_all(parent).map_err(|e| {
+                        Error::custom(&format!("Failed to create database directory: {}", e))
+                    })?;
+                }
+                WalletSqliteDatabase::new(&db_path)
+                    .await
+                    .map_err(|e| Error::custom(&format!("Failed to create database: {}", e)))?
+            }
+            None => cdk_sqlite::wallet::memory::empty()
+                .await
+                .map_err(|e| Error::custom(&format!("Failed to create database: {}", e)))?,
+        };
+
+        Ok(Arc::new(localstore))
+    }
+}
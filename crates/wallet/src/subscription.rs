@@ -0,0 +1,352 @@
+use crate::crypto;
+use crate::error::{Error, Result};
+use crate::mint::{
+    MintClient, PaymentMethod, PostMeltQuoteResponse, PostMintQuoteBolt12Response,
+    PostMintQuoteResponse, ProofState,
+};
+use futures::{SinkExt, Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio_tungstenite::{connect_async, tungstenite::Message};
+
+/// Subscription kinds defined by NUT-17
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SubscriptionKind {
+    Bolt11MintQuote,
+    Bolt11MeltQuote,
+    Bolt12MintQuote,
+    Bolt12MeltQuote,
+    ProofState,
+}
+
+impl SubscriptionKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SubscriptionKind::Bolt11MintQuote => "bolt11_mint_quote",
+            SubscriptionKind::Bolt11MeltQuote => "bolt11_melt_quote",
+            SubscriptionKind::Bolt12MintQuote => "bolt12_mint_quote",
+            SubscriptionKind::Bolt12MeltQuote => "bolt12_melt_quote",
+            SubscriptionKind::ProofState => "proof_state",
+        }
+    }
+
+    pub fn mint_quote(method: PaymentMethod) -> Self {
+        match method {
+            PaymentMethod::Bolt11 => SubscriptionKind::Bolt11MintQuote,
+            PaymentMethod::Bolt12 => SubscriptionKind::Bolt12MintQuote,
+        }
+    }
+
+    pub fn melt_quote(method: PaymentMethod) -> Self {
+        match method {
+            PaymentMethod::Bolt11 => SubscriptionKind::Bolt11MeltQuote,
+            PaymentMethod::Bolt12 => SubscriptionKind::Bolt12MeltQuote,
+        }
+    }
+}
+
+/// A state change reported by the mint
+#[derive(Debug, Clone)]
+pub enum StateUpdate {
+    MintQuote(PostMintQuoteResponse),
+    Bolt12MintQuote(PostMintQuoteBolt12Response),
+    /// A bolt11 or bolt12 melt quote
+    MeltQuote(PostMeltQuoteResponse),
+    ProofState(ProofState),
+}
+
+impl StateUpdate {
+    fn parse(kind: SubscriptionKind, payload: serde_json::Value) -> Result<Self> {
+        Ok(match kind {
+            SubscriptionKind::Bolt11MintQuote => {
+                StateUpdate::MintQuote(serde_json::from_value(payload)?)
+            }
+            SubscriptionKind::Bolt12MintQuote => {
+                StateUpdate::Bolt12MintQuote(serde_json::from_value(payload)?)
+            }
+            SubscriptionKind::Bolt11MeltQuote | SubscriptionKind::Bolt12MeltQuote => {
+                StateUpdate::MeltQuote(serde_json::from_value(payload)?)
+            }
+            SubscriptionKind::ProofState => {
+                StateUpdate::ProofState(serde_json::from_value(payload)?)
+            }
+        })
+    }
+
+    /// The quote id or proof `Y` this update is about
+    pub fn key(&self) -> &str {
+        match self {
+            StateUpdate::MintQuote(quote) => &quote.quote,
+            StateUpdate::Bolt12MintQuote(quote) => &quote.quote,
+            StateUpdate::MeltQuote(quote) => &quote.quote,
+            StateUpdate::ProofState(state) => &state.y,
+        }
+    }
+
+    fn fingerprint(&self) -> String {
+        match self {
+            StateUpdate::MintQuote(quote) => format!("{}:{:?}", quote.paid, quote.state),
+            StateUpdate::Bolt12MintQuote(quote) => {
+                format!("{}:{}", quote.amount_paid, quote.amount_issued)
+            }
+            StateUpdate::MeltQuote(quote) => format!(
+                "{}:{:?}:{:?}",
+                quote.paid, quote.state, quote.payment_preimage
+            ),
+            StateUpdate::ProofState(state) => format!("{}:{:?}", state.state, state.witness),
+        }
+    }
+}
+
+/// Stream of state changes for one subscription. Dropping it unsubscribes.
+pub struct StateStream {
+    receiver: mpsc::Receiver<Result<StateUpdate>>,
+}
+
+impl Stream for StateStream {
+    type Item = Result<StateUpdate>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.receiver.poll_recv(cx)
+    }
+}
+
+/// Start a subscription on a background task of the current Tokio runtime.
+///
+/// Uses the mint's WebSocket when it advertises NUT-17 for `kind`, and polls
+/// every `poll_interval` otherwise or once the WebSocket fails. Polling only
+/// reports states that changed since the last poll. With a proxy configured
+/// the WebSocket is never used, since its connection would not go through
+/// the proxy.
+///
+/// # Panics
+///
+/// Outside a Tokio runtime.
+pub(crate) fn spawn(
+    client: MintClient,
+    kind: SubscriptionKind,
+    filters: Vec<String>,
+    poll_interval: Duration,
+) -> StateStream {
+    let (sender, receiver) = mpsc::channel(32);
+
+    tokio::spawn(async move {
+        if supports_websocket(&client, kind).await
+            && run_websocket(&client, kind, &filters, &sender)
+                .await
+                .is_ok()
+        {
+            return;
+        }
+
+        run_polling(&client, kind, &filters, poll_interval, &sender).await;
+    });
+
+    StateStream { receiver }
+}
+
+async fn supports_websocket(client: &MintClient, kind: SubscriptionKind) -> bool {
+    // The WebSocket connection would bypass the configured proxy, so proxied
+    // clients always poll over HTTP
+    if client.transport().config().proxy.is_some() {
+        return false;
+    }
+
+    let info = match client.get_info().await {
+        Ok(info) => info,
+        Err(_) => return false,
+    };
+
+    info.nuts
+        .get("17")
+        .and_then(|nut| nut.get("supported"))
+        .and_then(|supported| supported.as_array())
+        .is_some_and(|supported| {
+            supported.iter().any(|method| {
+                method
+                    .get("commands")
+                    .and_then(|commands| commands.as_array())
+                    .is_some_and(|commands| {
+                        commands
+                            .iter()
+                            .any(|command| command.as_str() == Some(kind.as_str()))
+                    })
+            })
+        })
+}
+
+/// Forward notifications until the stream is dropped (`Ok`) or the connection fails (`Err`)
+async fn run_websocket(
+    client: &MintClient,
+    kind: SubscriptionKind,
+    filters: &[String],
+    sender: &mpsc::Sender<Result<StateUpdate>>,
+) -> Result<()> {
+    let mint_url = client.url_string();
+    let base = mint_url.trim_end_matches('/');
+    let url = if let Some(rest) = base.strip_prefix("https://") {
+        format!("wss://{}/v1/ws", rest)
+    } else if let Some(rest) = base.strip_prefix("http://") {
+        format!("ws://{}/v1/ws", rest)
+    } else {
+        return Err(Error::custom(&format!(
+            "Unsupported mint URL: {}",
+            mint_url
+        )));
+    };
+
+    let connect_timeout = Duration::from_secs(client.transport().config().connect_timeout_secs);
+    let (mut socket, _) = tokio::time::timeout(connect_timeout, connect_async(url.as_str()))
+        .await
+        .map_err(|_| Error::custom("WebSocket connection timed out"))?
+        .map_err(|e| Error::custom(&format!("WebSocket connection failed: {}", e)))?;
+
+    let sub_id = crypto::generate_random_secret();
+    let subscribe = json!({
+        "jsonrpc": "2.0",
+        "id": 0,
+        "method": "subscribe",
+        "params": {
+            "kind": kind.as_str(),
+            "subId": sub_id,
+            "filters": filters,
+        },
+    });
+    socket
+        .send(Message::text(subscribe.to_string()))
+        .await
+        .map_err(|e| Error::custom(&format!("Failed to subscribe: {}", e)))?;
+
+    loop {
+        tokio::select! {
+            _ = sender.closed() => {
+                let unsubscribe = json!({
+                    "jsonrpc": "2.0",
+                    "id": 1,
+                    "method": "unsubscribe",
+                    "params": { "subId": sub_id },
+                });
+                let _ = socket.send(Message::text(unsubscribe.to_string())).await;
+                let _ = socket.close(None).await;
+                return Ok(());
+            }
+            message = socket.next() => {
+                let text = match message {
+                    Some(Ok(Message::Text(text))) => text,
+                    Some(Ok(Message::Close(_))) | None => {
+                        return Err(Error::custom("WebSocket closed by mint"))
+                    }
+                    Some(Ok(_)) => continue,
+                    Some(Err(e)) => {
+                        return Err(Error::custom(&format!("WebSocket error: {}", e)))
+                    }
+                };
+
+                let value: serde_json::Value = serde_json::from_str(text.as_str())?;
+                if let Some(error) = value.get("error") {
+                    return Err(Error::custom(&format!(
+                        "Mint rejected subscription: {}",
+                        error
+                    )));
+                }
+
+                // Anything but a notification for this subscription is an acknowledgement
+                let is_notification = value["method"].as_str() == Some("subscribe")
+                    && value["params"]["subId"].as_str() == Some(sub_id.as_str());
+                if !is_notification {
+                    continue;
+                }
+
+                let update = StateUpdate::parse(kind, value["params"]["payload"].clone());
+                let _ = sender.send(update).await;
+            }
+        }
+    }
+}
+
+async fn run_polling(
+    client: &MintClient,
+    kind: SubscriptionKind,
+    filters: &[String],
+    poll_interval: Duration,
+    sender: &mpsc::Sender<Result<StateUpdate>>,
+) {
+    let mut last_seen: HashMap<String, String> = HashMap::new();
+
+    loop {
+        match poll_once(client, kind, filters).await {
+            Ok(updates) => {
+                for update in updates {
+                    let fingerprint = update.fingerprint();
+                    if last_seen.get(update.key()) == Some(&fingerprint) {
+                        continue;
+                    }
+                    last_seen.insert(update.key().to_string(), fingerprint);
+
+                    if sender.send(Ok(update)).await.is_err() {
+                        return;
+                    }
+                }
+            }
+            Err(e) => {
+                if sender.send(Err(e)).await.is_err() {
+                    return;
+                }
+            }
+        }
+
+        tokio::select! {
+            _ = sender.closed() => return,
+            _ = tokio::time::sleep(poll_interval) => {}
+        }
+    }
+}
+
+async fn poll_once(
+    client: &MintClient,
+    kind: SubscriptionKind,
+    filters: &[String],
+) -> Result<Vec<StateUpdate>> {
+    let mut updates = Vec::new();
+
+    match kind {
+        SubscriptionKind::Bolt11MintQuote => {
+            for quote in filters {
+                updates.push(StateUpdate::MintQuote(client.get_mint_quote(quote).await?));
+            }
+        }
+        SubscriptionKind::Bolt12MintQuote => {
+            for quote in filters {
+                updates.push(StateUpdate::Bolt12MintQuote(
+                    client.get_bolt12_mint_quote(quote).await?,
+                ));
+            }
+        }
+        SubscriptionKind::Bolt11MeltQuote => {
+            for quote in filters {
+                updates.push(StateUpdate::MeltQuote(client.get_melt_quote(quote).await?));
+            }
+        }
+        SubscriptionKind::Bolt12MeltQuote => {
+            for quote in filters {
+                updates.push(StateUpdate::MeltQuote(
+                    client
+                        .get_melt_quote_with_method(PaymentMethod::Bolt12, quote)
+                        .await?,
+                ));
+            }
+        }
+        SubscriptionKind::ProofState => {
+            let response = client.check_proof_state(filters.to_vec()).await?;
+            updates.extend(response.states.into_iter().map(StateUpdate::ProofState));
+        }
+    }
+
+    Ok(updates)
+}
@@ -0,0 +1,237 @@
+use crate::error::{Error, Result};
+use cdk::nuts::{CurrencyUnit, Token};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Version of the JSON envelope written by [`TokenBundle`]
+pub const TOKEN_BUNDLE_VERSION: u8 = 1;
+
+/// One mint's share of a [`TokenBundle`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BundlePart {
+    pub mint_url: String,
+    pub unit: CurrencyUnit,
+    pub amount: u64,
+    /// Encoded cashu token holding the proofs of `mint_url`
+    pub token: String,
+}
+
+impl BundlePart {
+    /// Describe an encoded token, reading the mint, unit and amount from it
+    pub fn from_token(token: &str) -> Result<Self> {
+        let token = token.trim();
+        let parsed =
+            Token::from_str(token).map_err(|e| Error::custom(&format!("Invalid token: {}", e)))?;
+        let mint_url = parsed
+            .mint_url()
+            .map_err(|e| Error::custom(&format!("Invalid token: {}", e)))?;
+        let amount = parsed
+            .value()
+            .map_err(|e| Error::custom(&format!("Invalid token: {}", e)))?;
+
+        Ok(Self {
+            mint_url: mint_url.to_string(),
+            unit: parsed.unit().unwrap_or_default(),
+            amount: amount.into(),
+            token: token.to_string(),
+        })
+    }
+}
+
+/// Tokens from several mints sent together.
+///
+/// Encodes as a JSON envelope, `{"version":1,"parts":[...]}`, with one
+/// token per part. Parsing also accepts a single token or tokens separated
+/// by whitespace.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenBundle {
+    pub version: u8,
+    pub parts: Vec<BundlePart>,
+}
+
+impl Default for TokenBundle {
+    fn default() -> Self {
+        Self {
+            version: TOKEN_BUNDLE_VERSION,
+            parts: Vec::new(),
+        }
+    }
+}
+
+impl TokenBundle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_tokens<I, S>(tokens: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut bundle = Self::new();
+        for token in tokens {
+            bundle.push_token(token.as_ref())?;
+        }
+        Ok(bundle)
+    }
+
+    pub fn push_token(&mut self, token: &str) -> Result<()> {
+        self.parts.push(BundlePart::from_token(token)?);
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.parts.is_empty()
+    }
+
+    /// Sum of the parts in `unit`
+    pub fn total_amount(&self, unit: &CurrencyUnit) -> u64 {
+        self.parts
+            .iter()
+            .filter(|part| part.unit == *unit)
+            .map(|part| part.amount)
+            .sum()
+    }
+
+    /// The mints the tokens were drawn from, in order and without duplicates
+    pub fn mints_used(&self) -> Vec<&str> {
+        let mut mints: Vec<&str> = Vec::new();
+        for part in &self.parts {
+            if !mints.contains(&part.mint_url.as_str()) {
+                mints.push(&part.mint_url);
+            }
+        }
+        mints
+    }
+
+    /// The tokens of all parts, one per line
+    pub fn tokens(&self) -> String {
+        self.parts
+            .iter()
+            .map(|part| part.token.as_str())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    /// Parse a JSON envelope, checking every part against its token
+    pub fn from_json(json: &str) -> Result<Self> {
+        let bundle: Self = serde_json::from_str(json)?;
+        if bundle.version != TOKEN_BUNDLE_VERSION {
+            return Err(Error::custom(&format!(
+                "Unsupported token bundle version: {}",
+                bundle.version
+            )));
+        }
+
+        for part in &bundle.parts {
+            if BundlePart::from_token(&part.token)? != *part {
+                return Err(Error::custom(&format!(
+                    "Token bundle part for {} does not match its token",
+                    part.mint_url
+                )));
+            }
+        }
+
+        Ok(bundle)
+    }
+}
+
+impl fmt::Display for TokenBundle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let json = serde_json::to_string(self).map_err(|_| fmt::Error)?;
+        f.write_str(&json)
+    }
+}
+
+impl FromStr for TokenBundle {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        if s.starts_with('{') {
+            Self::from_json(s)
+        } else {
+            let bundle = Self::from_tokens(s.split_whitespace())?;
+            if bundle.is_empty() {
+                return Err(Error::custom("No tokens to receive"));
+            }
+            Ok(bundle)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cdk::mint_url::MintUrl;
+    use cdk::nuts::{Id, Proof, PublicKey};
+    use cdk::secret::Secret;
+    use cdk::Amount;
+
+    fn token(mint_url: &str, amounts: &[u64]) -> String {
+        let c = PublicKey::from_hex(
+            "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+        )
+        .unwrap();
+        let proofs = amounts
+            .iter()
+            .map(|amount| Proof {
+                amount: Amount::from(*amount),
+                keyset_id: Id::from_str("009a1f293253e41e").unwrap(),
+                secret: Secret::generate(),
+                c,
+                witness: None,
+                dleq: None,
+            })
+            .collect();
+        Token::new(
+            MintUrl::from_str(mint_url).unwrap(),
+            proofs,
+            None,
+            CurrencyUnit::Sat,
+        )
+        .to_string()
+    }
+
+    #[test]
+    fn reports_each_mint_used_once() {
+        let bundle = TokenBundle::from_tokens([
+            token("https://mint-a.example", &[8, 2]),
+            token("https://mint-b.example", &[4]),
+            token("https://mint-a.example", &[1]),
+        ])
+        .unwrap();
+
+        assert_eq!(
+            bundle.mints_used(),
+            vec!["https://mint-a.example", "https://mint-b.example"]
+        );
+        assert_eq!(bundle.parts[0].amount, 10);
+        assert_eq!(bundle.total_amount(&CurrencyUnit::Sat), 15);
+    }
+
+    #[test]
+    fn json_and_token_list_parse_to_the_same_bundle() {
+        let bundle = TokenBundle::from_tokens([
+            token("https://mint-a.example", &[8]),
+            token("https://mint-b.example", &[4]),
+        ])
+        .unwrap();
+
+        assert_eq!(TokenBundle::from_str(&bundle.to_string()).unwrap(), bundle);
+        assert_eq!(TokenBundle::from_str(&bundle.tokens()).unwrap(), bundle);
+    }
+
+    #[test]
+    fn rejects_parts_that_do_not_match_their_token() {
+        let mut bundle = TokenBundle::from_tokens([token("https://mint-a.example", &[8])]).unwrap();
+        bundle.parts[0].amount = 80;
+
+        assert!(TokenBundle::from_json(&bundle.to_json().unwrap()).is_err());
+    }
+}
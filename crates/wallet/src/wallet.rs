@@ -1,28 +1,173 @@
 use crate::{
     error::{Error, Result},
-    models::SendTokenPendingResponse,
+    lightning::{LightningManager, ResumeReport},
+    mint::{MintClient, MintTransport},
+    models::{SendFeeOptions, SendPreview, SendTokenPendingResponse},
     multimint::MultimintWallet,
+    quote_store::QuoteStore,
+    seed::derive_seed,
+    storage::{WalletStorage, WalletStore},
 };
-use std::{str::FromStr, sync::Arc};
+use std::{path::PathBuf, str::FromStr, sync::Arc};
+use tokio::sync::Mutex;
 
 use bip39::Mnemonic;
-use cdk::wallet::{HttpClient, ReceiveOptions, SendOptions, Wallet, WalletBuilder};
-use cdk_sqlite::WalletSqliteDatabase;
+use cdk::wallet::{ReceiveOptions, SendOptions, Wallet, WalletBuilder};
 
+/// cdk send options carrying the fee handling of `options`
+pub(crate) fn cdk_send_options(options: SendFeeOptions) -> SendOptions {
+    SendOptions {
+        include_fee: options.include_fees,
+        ..Default::default()
+    }
+}
+
+/// The seed of a mnemonic without passphrase; see [`derive_seed`]
 pub fn prepare_seed(seed: &str) -> Result<[u8; 64]> {
-    let mnemonic = Mnemonic::from_str(seed).map_err(|_| Error::custom("Invalid mnemonic seed"))?;
-    Ok(mnemonic.to_seed_normalized(""))
+    derive_seed(seed, "")
+}
+
+/// Builds a [`CashuWalletClient`] with a chosen passphrase, unit, transport and storage.
+///
+/// Storage defaults to `wallet.db` under the home directory. Lightning
+/// quotes are kept in a [`QuoteStore`] next to a SQLite database, and the
+/// ones left from an earlier run are resumed when the wallet is built.
+#[derive(Debug, Clone)]
+pub struct CashuWalletClientBuilder {
+    mint_url: String,
+    seed: String,
+    passphrase: String,
+    unit: cdk::nuts::CurrencyUnit,
+    transport: MintTransport,
+    storage: WalletStorage,
+    quote_store: Option<QuoteStore>,
+    resume_pending: bool,
+}
+
+impl CashuWalletClientBuilder {
+    pub fn new(mint_url: &str, seed: &str) -> Self {
+        Self {
+            mint_url: mint_url.to_string(),
+            seed: seed.to_string(),
+            passphrase: String::new(),
+            unit: cdk::nuts::CurrencyUnit::Msat,
+            transport: MintTransport::shared(),
+            storage: WalletStorage::Home("wallet.db".to_string()),
+            quote_store: None,
+            resume_pending: true,
+        }
+    }
+
+    /// BIP39 passphrase protecting the mnemonic
+    pub fn passphrase(mut self, passphrase: &str) -> Self {
+        self.passphrase = passphrase.to_string();
+        self
+    }
+
+    pub fn unit(mut self, unit: cdk::nuts::CurrencyUnit) -> Self {
+        self.unit = unit;
+        self
+    }
+
+    pub fn transport(mut self, transport: MintTransport) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    pub fn storage(mut self, storage: WalletStorage) -> Self {
+        self.storage = storage;
+        self
+    }
+
+    /// SQLite database at `db_name` under the home directory
+    pub fn db_name(self, db_name: &str) -> Self {
+        self.storage(WalletStorage::Home(db_name.to_string()))
+    }
+
+    /// SQLite database at `path`
+    pub fn db_path(self, path: impl Into<PathBuf>) -> Self {
+        self.storage(WalletStorage::Path(path.into()))
+    }
+
+    /// Empty in-memory database
+    pub fn in_memory(self) -> Self {
+        self.storage(WalletStorage::Memory)
+    }
+
+    /// Any cdk wallet database
+    pub fn localstore(self, localstore: WalletStore) -> Self {
+        self.storage(WalletStorage::Custom(localstore))
+    }
+
+    pub fn quote_store(mut self, quote_store: QuoteStore) -> Self {
+        self.quote_store = Some(quote_store);
+        self
+    }
+
+    /// Whether [`Self::build`] resumes the Lightning quotes left from an
+    /// earlier run, on by default
+    pub fn resume_pending(mut self, resume_pending: bool) -> Self {
+        self.resume_pending = resume_pending;
+        self
+    }
+
+    pub async fn build(self) -> Result<CashuWalletClient> {
+        let seed = derive_seed(&self.seed, &self.passphrase)?;
+        let localstore = self.storage.open().await?;
+
+        let mint_url = cdk::mint_url::MintUrl::from_str(&self.mint_url)
+            .map_err(|_| Error::custom("Invalid mint URL"))?;
+        let wallet = WalletBuilder::new()
+            .mint_url(mint_url.clone())
+            .unit(self.unit)
+            .localstore(localstore)
+            .seed(&seed)
+            .client(self.transport.cdk_client(mint_url)?)
+            .build()?;
+
+        let quote_store = match (self.quote_store, &self.storage) {
+            (Some(store), _) => Some(store),
+            (None, WalletStorage::Home(db_name)) => Some(QuoteStore::for_wallet_db(db_name)?),
+            (None, WalletStorage::Path(path)) => {
+                Some(QuoteStore::new(format!("{}.quotes.json", path.display())))
+            }
+            (None, WalletStorage::Memory | WalletStorage::Custom(_)) => None,
+        };
+        let lightning = match quote_store {
+            Some(store) => LightningManager::with_store(self.transport.clone(), store)?,
+            None => LightningManager::with_transport(self.transport.clone()),
+        };
+
+        let mut client = CashuWalletClient {
+            wallet,
+            transport: self.transport,
+            lightning: Arc::new(Mutex::new(lightning)),
+            startup_resume: ResumeReport::default(),
+        };
+        if self.resume_pending {
+            client.startup_resume = client.resume_pending().await?;
+        }
+
+        Ok(client)
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct CashuWalletClient {
     pub wallet: Wallet,
+    transport: MintTransport,
+    /// Tracks this wallet's Lightning quotes in its quote store
+    lightning: Arc<Mutex<LightningManager>>,
+    startup_resume: ResumeReport,
 }
 
 impl CashuWalletClient {
+    pub fn builder(mint_url: &str, seed: &str) -> CashuWalletClientBuilder {
+        CashuWalletClientBuilder::new(mint_url, seed)
+    }
+
     pub async fn from_seed(mint_url: &str, seed: &str, db_name: &str) -> Result<Self> {
-        let s = Mnemonic::from_str(seed).map_err(|_| Error::custom("Invalid mnemonic seed"))?;
-        CashuWalletClient::wallet(mint_url, s, db_name, cdk::nuts::CurrencyUnit::Msat).await
+        Self::builder(mint_url, seed).db_name(db_name).build().await
     }
 
     pub async fn from_seed_with_unit(
@@ -31,24 +176,84 @@ impl CashuWalletClient {
         db_name: &str,
         unit: cdk::nuts::CurrencyUnit,
     ) -> Result<Self> {
-        let s = Mnemonic::from_str(seed).map_err(|_| Error::custom("Invalid mnemonic seed"))?;
-        CashuWalletClient::wallet(mint_url, s, db_name, unit).await
+        Self::from_seed_with_transport(mint_url, seed, db_name, unit, MintTransport::shared()).await
+    }
+
+    pub async fn from_seed_with_transport(
+        mint_url: &str,
+        seed: &str,
+        db_name: &str,
+        unit: cdk::nuts::CurrencyUnit,
+        transport: MintTransport,
+    ) -> Result<Self> {
+        Self::builder(mint_url, seed)
+            .db_name(db_name)
+            .unit(unit)
+            .transport(transport)
+            .build()
+            .await
     }
 
     pub async fn new(mint_url: &str, seed: &mut String, db_name: &str) -> Result<Self> {
         let s = Mnemonic::generate(12).map_err(|_| Error::custom("Failed to generate mnemonic"))?;
         seed.push_str(&s.to_string());
-        CashuWalletClient::wallet(mint_url, s, db_name, cdk::nuts::CurrencyUnit::Msat).await
+        Self::from_seed(mint_url, &s.to_string(), db_name).await
+    }
+
+    /// A raw mint client for this wallet's mint, using the wallet's transport
+    pub fn mint_client(&self) -> Result<MintClient> {
+        self.transport.client_for(&self.wallet.mint_url.to_string())
+    }
+
+    pub fn transport(&self) -> &MintTransport {
+        &self.transport
+    }
+
+    /// The Lightning manager holding this wallet's quotes
+    pub fn lightning(&self) -> Arc<Mutex<LightningManager>> {
+        self.lightning.clone()
+    }
+
+    /// Mint invoices paid while offline, resolve pending melts and drop
+    /// finished quotes; see [`LightningManager::resume_pending`]
+    pub async fn resume_pending(&self) -> Result<ResumeReport> {
+        let mut lightning = self.lightning.lock().await;
+        lightning.resume_pending(&self.wallet).await
+    }
+
+    /// What was resumed when the wallet was built
+    pub fn startup_resume(&self) -> &ResumeReport {
+        &self.startup_resume
     }
 
     pub async fn send(&self, amount: u64) -> Result<String> {
+        self.send_with_options(amount, SendFeeOptions::default())
+            .await
+    }
+
+    pub async fn send_with_options(&self, amount: u64, options: SendFeeOptions) -> Result<String> {
         let prepared_send = self
             .wallet
-            .prepare_send(amount.into(), SendOptions::default())
+            .prepare_send(amount.into(), cdk_send_options(options))
             .await?;
         Ok(self.wallet.send(prepared_send, None).await?.to_string())
     }
 
+    /// The proofs and fees a send of `amount` would use, without sending
+    pub async fn preview_send(&self, amount: u64, options: SendFeeOptions) -> Result<SendPreview> {
+        let prepared_send = self
+            .wallet
+            .prepare_send(amount.into(), cdk_send_options(options))
+            .await?;
+        let preview = SendPreview::from_prepared(
+            &self.wallet.mint_url.to_string(),
+            &self.wallet.unit,
+            &prepared_send,
+        );
+        self.wallet.cancel_send(prepared_send).await?;
+        Ok(preview)
+    }
+
     pub async fn receive(&self, token: &str) -> Result<String> {
         Ok(self
             .wallet
@@ -75,42 +280,6 @@ impl CashuWalletClient {
             .collect())
     }
 
-    async fn wallet(
-        mint_url: &str,
-        s: Mnemonic,
-        db_name: &str,
-        unit: cdk::nuts::CurrencyUnit,
-    ) -> Result<Self> {
-        let home_dir =
-            home::home_dir().ok_or_else(|| Error::custom("Could not determine home directory"))?;
-        let db_path = home_dir.join(db_name);
-
-        if let Some(parent) = db_path.parent() {
-            std::fs::create_dir_all(parent).map_err(|e| {
-                Error::custom(&format!("Failed to create database directory: {}", e))
-            })?;
-        }
-
-        let localstore = WalletSqliteDatabase::new(&db_path)
-            .await
-            .map_err(|e| Error::custom(&format!("Failed to create database: {}", e)))?;
-
-        let seed = s.to_seed_normalized("");
-        let mint_url = cdk::mint_url::MintUrl::from_str(mint_url)
-            .map_err(|_| Error::custom("Invalid mint URL"))?;
-        let mut builder = WalletBuilder::new()
-            .mint_url(mint_url.clone())
-            .unit(unit)
-            .localstore(Arc::new(localstore))
-            .seed(&seed);
-        let http_client = HttpClient::new(mint_url);
-        builder = builder.client(http_client);
-
-        Ok(Self {
-            wallet: builder.build()?,
-        })
-    }
-
     pub async fn redeem_pendings(&self) -> Result<()> {
         let proofs = self.wallet.get_pending_spent_proofs().await?;
         self.wallet
@@ -2,12 +2,18 @@ use bip39::Mnemonic;
 use cdk::nuts::CurrencyUnit;
 use ecash_402_wallet::{
     error::Result,
-    lightning::{LightningManager, TopupRequest},
+    lightning::{InvoiceState, LightningManager, ResumeReport, TopupRequest},
+    mint::{MintTransport, PaymentMethod},
     multimint::MultimintWallet,
+    quote_store::QuoteStore,
     wallet::CashuWalletClient,
 };
 
-#[tokio::main]
+// The test mint pays its own invoices, so topups complete without a Lightning wallet
+const MINT_URL: &str = "https://testnut.cashu.space";
+const DB_NAME: &str = ".cashu_lightning_test/wallet.sqlite";
+
+#[tokio::main(flavor = "current_thread")]
 async fn main() -> Result<()> {
     println!("🚀 Lightning Topup Example");
     println!("==========================\n");
@@ -19,31 +25,32 @@ async fn main() -> Result<()> {
         .to_string_lossy()
         .to_string();
 
-    let mut lightning_manager = LightningManager::new();
-
     println!("📝 Single Wallet Lightning Topup");
     println!("----------------------------------");
 
-    let mint_url = "https://testnut.cashu.space";
-    let wallet = CashuWalletClient::from_seed(mint_url, &seed, "lightning_test_wallet").await?;
+    // The wallet keeps its invoices in a quote store next to its database and
+    // finishes topups left from an earlier run while it is built
+    let mut wallet =
+        CashuWalletClient::from_seed_with_unit(MINT_URL, &seed, DB_NAME, CurrencyUnit::Sat).await?;
+    println!("✅ Created wallet for mint: {}", MINT_URL);
+    print_resumed("wallet", wallet.startup_resume());
 
-    println!("✅ Created wallet for mint: {}", mint_url);
+    let lightning = wallet.lightning();
+    let mut lightning_manager = lightning.lock().await;
 
     let amount = 100;
-    let unit = CurrencyUnit::Msat;
+    let unit = CurrencyUnit::Sat;
 
-    println!(
-        "\n💡 Creating lightning invoice for {} {:?}...",
-        amount, unit
-    );
+    println!("\n💡 Creating lightning invoice for {} {}...", amount, unit);
     let topup_response = lightning_manager
-        .create_invoice_for_wallet(&wallet, mint_url, amount, unit.clone())
+        .create_invoice_for_wallet(&wallet, MINT_URL, amount, unit, PaymentMethod::Bolt11)
         .await?;
+    let quote_id = topup_response.invoice.quote_id.clone();
 
     println!("✅ Invoice created:");
-    println!("   Quote ID: {}", topup_response.invoice.quote_id);
+    println!("   Quote ID: {}", quote_id);
     println!(
-        "   Amount: {} {:?}",
+        "   Amount: {} {}",
         topup_response.invoice.amount, topup_response.invoice.unit
     );
     println!(
@@ -53,144 +60,139 @@ async fn main() -> Result<()> {
     println!("   Expiry: {}", topup_response.invoice.expiry);
     println!("   Message: {}", topup_response.message);
 
-    println!("\n🔍 Checking payment status...");
-    let status = lightning_manager
-        .check_payment_status(&topup_response.invoice.quote_id)
-        .await?;
-
-    println!("✅ Payment Status:");
-    println!("   Quote ID: {}", status.quote_id);
-    println!("   Paid: {}", status.paid);
-    println!("   Amount: {} msats", status.amount);
-    println!("   Expiry: {}", status.expiry);
-
-    if !status.paid {
-        println!("\n⏳ Invoice is not paid yet. In a real scenario, you would:");
-        println!("   1. Display the payment request to the user");
-        println!("   2. Wait for payment using wait_for_payment()");
-        println!("   3. Manually process the mint operation when payment is received");
+    println!("\n⏳ Waiting for payment...");
+    let paid = lightning_manager.wait_for_payment(&quote_id, 60, 2).await?;
+    let status = lightning_manager.check_payment_status(&quote_id).await?;
+    println!("✅ Payment Status: {} (paid: {})", status.state, paid);
+
+    if status.state == InvoiceState::Paid {
+        let result = lightning_manager
+            .complete_topup(&quote_id, &mut wallet)
+            .await?;
+        println!("✅ {}", result.message);
+        println!("💰 Wallet balance: {}", wallet.balance().await?);
+    } else {
+        println!("⚠️  Invoice was not paid in time, it will be resumed on the next run");
     }
 
     println!("\n📝 Multimint Lightning Topup");
     println!("-----------------------------");
 
-    let mut multimint_wallet = MultimintWallet::new(&seed, &db_path).await?;
-
+    let multimint_wallet = MultimintWallet::new(&seed, &db_path).await?;
     multimint_wallet
-        .add_mint("https://testnut.cashu.space", Some(CurrencyUnit::Msat))
+        .add_mint(MINT_URL, Some(CurrencyUnit::Sat))
         .await?;
-    multimint_wallet
-        .add_mint(
-            "https://mint.minibits.cash/Bitcoin",
-            Some(CurrencyUnit::Msat),
-        )
+
+    let mut multimint_lightning = LightningManager::with_store(
+        MintTransport::shared(),
+        QuoteStore::new(format!("{}.topups.json", db_path)),
+    )?;
+    let resumed = multimint_lightning
+        .resume_pending_for_multimint(&multimint_wallet)
         .await?;
+    print_resumed("multimint wallet", &resumed);
 
-    println!(
-        "✅ Created multimint wallet with {} mints",
-        multimint_wallet.list_mints().len()
-    );
-    for mint in multimint_wallet.list_mints() {
+    let mints = multimint_wallet.list_mints().await;
+    println!("✅ Created multimint wallet with {} mints", mints.len());
+    for mint in &mints {
         println!("   - {}", mint);
     }
 
     let topup_request = TopupRequest {
         amount: 200,
-        unit: CurrencyUnit::Msat,
-        mint_url: Some("https://testnut.cashu.space".to_string()),
+        unit: CurrencyUnit::Sat,
+        mint_url: Some(MINT_URL.to_string()),
+        description: None,
+        method: PaymentMethod::Bolt11,
     };
 
     println!("\n💡 Creating lightning invoice for multimint topup...");
-    println!(
-        "   Amount: {} {:?}",
-        topup_request.amount, topup_request.unit
-    );
+    println!("   Amount: {} {}", topup_request.amount, topup_request.unit);
     println!("   Target Mint: {:?}", topup_request.mint_url);
 
-    let multimint_response = lightning_manager
+    let multimint_response = multimint_lightning
         .create_invoice_for_multimint(&multimint_wallet, topup_request)
         .await?;
+    let multimint_quote_id = multimint_response.invoice.quote_id.clone();
 
     println!("✅ Multimint invoice created:");
-    println!("   Quote ID: {}", multimint_response.invoice.quote_id);
-    println!(
-        "   Amount: {} {:?}",
-        multimint_response.invoice.amount, multimint_response.invoice.unit
-    );
+    println!("   Quote ID: {}", multimint_quote_id);
     println!("   Mint URL: {}", multimint_response.invoice.mint_url);
     println!(
         "   Payment Request: {}...",
         &multimint_response.invoice.payment_request[..60]
     );
-    println!("   Message: {}", multimint_response.message);
 
-    println!("\n📋 Lightning Manager State");
-    println!("---------------------------");
-
-    let pending_invoices = lightning_manager.list_pending_invoices();
-    println!("📊 Pending invoices: {}", pending_invoices.len());
-
-    for (i, invoice) in pending_invoices.iter().enumerate() {
-        println!("   {}. Quote ID: {}", i + 1, invoice.quote_id);
-        println!("      Amount: {} {:?}", invoice.amount, invoice.unit);
-        println!("      Mint: {}", invoice.mint_url);
-        println!("      Expiry: {}", invoice.expiry);
+    println!("\n⏳ Waiting for payment...");
+    if multimint_lightning
+        .wait_for_payment(&multimint_quote_id, 60, 2)
+        .await?
+    {
+        let result = multimint_lightning
+            .complete_topup_for_multimint(&multimint_quote_id, &multimint_wallet)
+            .await?;
+        println!("✅ {}", result.message);
+
+        let balance = multimint_wallet.get_total_balance().await?;
+        println!("💰 Multimint balance: {} sats", balance.total_balance);
+    } else {
+        println!("⚠️  Invoice was not paid in time, it will be resumed on the next run");
     }
 
-    println!("\n🔍 Testing invoice retrieval...");
-    if let Some(invoice) = lightning_manager.get_invoice(&topup_response.invoice.quote_id) {
-        println!(
-            "✅ Found invoice: {} {:?} at {}",
-            invoice.amount, invoice.unit, invoice.mint_url
-        );
-    }
+    println!("\n📋 Lightning Manager State");
+    println!("---------------------------");
 
-    println!("\n📱 Testing payment request access...");
-    if let Some(payment_request) =
-        lightning_manager.get_payment_request(&topup_response.invoice.quote_id)
-    {
+    let pending = lightning_manager
+        .list_pending_invoices()
+        .into_iter()
+        .chain(multimint_lightning.list_pending_invoices());
+    for (i, invoice) in pending.enumerate() {
         println!(
-            "✅ Payment request accessible: {}...",
-            &payment_request[..50]
+            "   {}. {} {} at {} ({})",
+            i + 1,
+            invoice.amount,
+            invoice.unit,
+            invoice.mint_url,
+            invoice.state
         );
     }
 
-    println!("\n⏳ Testing payment waiting (will timeout since not paid)...");
-    let paid = lightning_manager
-        .wait_for_payment(&multimint_response.invoice.quote_id, 3, 1)
-        .await?;
-    println!("✅ Payment received within 3 seconds: {}", paid);
-
     println!("\n🧹 Cleanup expired invoices...");
-    lightning_manager.cleanup_expired_invoices();
-    println!("✅ Cleanup completed");
+    lightning_manager.cleanup_expired_invoices()?;
+    multimint_lightning.cleanup_expired_invoices()?;
 
-    println!("\n🗑️ Testing invoice removal...");
-    let removed = lightning_manager.remove_invoice(&topup_response.invoice.quote_id);
-    if removed.is_some() {
-        println!("✅ Successfully removed invoice");
+    if lightning_manager.get_invoice_state(&quote_id) == Some(InvoiceState::Issued) {
+        lightning_manager.remove_invoice(&quote_id)?;
     }
-
-    let remaining = lightning_manager.list_pending_invoices();
-    println!("📊 Remaining invoices: {}", remaining.len());
-
-    println!("\n🎯 Summary");
-    println!("----------");
-    println!("✅ Successfully demonstrated:");
-    println!("   • Lightning invoice creation for single wallet");
-    println!("   • Lightning invoice creation for multimint wallet");
-    println!("   • Payment status checking");
-    println!("   • Invoice management and cleanup");
-    println!("   • Payment request access for QR codes");
-    println!("   • Payment waiting with timeout");
+    if multimint_lightning.get_invoice_state(&multimint_quote_id) == Some(InvoiceState::Issued) {
+        multimint_lightning.remove_invoice(&multimint_quote_id)?;
+    }
+    println!(
+        "📊 Remaining invoices: {}",
+        lightning_manager.list_pending_invoices().len()
+            + multimint_lightning.list_pending_invoices().len()
+    );
 
     println!("\n📚 Integration Notes for UX/Backend:");
-    println!("   • Use create_invoice_for_wallet() for single mint topups");
-    println!("   • Use create_invoice_for_multimint() for multimint topups");
+    println!("   • Keep quotes in a QuoteStore and resume them on startup");
+    println!("   • Use create_invoice_for_wallet() or create_invoice_for_multimint() for topups");
     println!("   • Display payment_request as QR code for user scanning");
-    println!("   • Poll check_payment_status() or use wait_for_payment()");
-    println!("   • Once paid=true, use wallet's mint methods to convert to tokens");
-    println!("   • Use cleanup_expired_invoices() periodically");
+    println!("   • Use wait_for_payment() or check_payment_status() until the invoice is paid");
+    println!("   • Call complete_topup() or complete_topup_for_multimint() to mint the proofs");
 
     Ok(())
 }
+
+fn print_resumed(wallet: &str, report: &ResumeReport) {
+    println!(
+        "🔁 Resumed {} topups of the {} from earlier runs",
+        report.topups.len(),
+        wallet
+    );
+    for failure in &report.failed {
+        println!(
+            "⚠️  Quote {} at {} is still pending: {}",
+            failure.quote_id, failure.mint_url, failure.error
+        );
+    }
+}